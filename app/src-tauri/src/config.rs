@@ -0,0 +1,15 @@
+//! The configuration of the app
+//! It is loaded once, and the app runs in the local profile by default.
+
+use shared::config::{Config, ConfigLoader, Profile};
+use shared::error::Error;
+use std::sync::OnceLock;
+
+pub fn config() -> Result<&'static Config, Error> {
+    static CONFIG: OnceLock<Result<Config, Error>> = OnceLock::new();
+
+    match CONFIG.get_or_init(|| ConfigLoader::new().default_profile(Profile::Local).load()) {
+        Ok(config) => Ok(config),
+        Err(e) => Err(Error::new(e.kind(), e.message())),
+    }
+}
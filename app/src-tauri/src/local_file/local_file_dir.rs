@@ -1,3 +1,4 @@
+use crate::config::config;
 use crate::local_file::local_file_error::ExogenesisEnsembleLocalFileErrors;
 use directories::UserDirs;
 use std::path::PathBuf;
@@ -7,10 +8,25 @@ const DIRECTORY_PATH: &str = "ExogenesisEnsemblePartThreeRedemption";
 
 /// Return the video path
 /// The video directory is OS specific though, under that, a path will be an app name + /yyyy/MM/dd/yyyy-MM-dd-hh-mm-ss.{extension}
+/// If `LIBRARY_DIR` is configured, the path is under it instead.
 pub fn generate_video_file_dir(
     date_time: u128,
     extension: &str,
 ) -> Result<PathBuf, ExogenesisEnsembleLocalFileErrors> {
+    let file_path = match FilePath::new().generate_file_path(date_time, extension) {
+        Ok(path) => path,
+        Err(e) => return Err(ExogenesisEnsembleLocalFileErrors::FileError(e)),
+    };
+
+    let config = match config() {
+        Ok(config) => config,
+        Err(e) => return Err(ExogenesisEnsembleLocalFileErrors::FileError(e.to_string())),
+    };
+
+    if let Some(library_dir) = &config.library_dir {
+        return Ok(library_dir.join(file_path.trim_start_matches('/')));
+    }
+
     let user_dirs = match UserDirs::new() {
         Some(dir) => dir,
         None => return Err(ExogenesisEnsembleLocalFileErrors::DirectoryMountFailed),
@@ -21,11 +37,6 @@ pub fn generate_video_file_dir(
         None => return Err(ExogenesisEnsembleLocalFileErrors::DirectoryMountFailed),
     };
 
-    let file_path = match FilePath::new().generate_file_path(date_time, extension) {
-        Ok(path) => path,
-        Err(e) => return Err(ExogenesisEnsembleLocalFileErrors::FileError(e)),
-    };
-
     let joined_app_name = video_dir.join(format!("{}{}", DIRECTORY_PATH, file_path.as_str()));
 
     Ok(joined_app_name.into())
@@ -1,6 +1,7 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+pub mod config;
 pub mod errors;
 pub mod handlers;
 pub mod local_file;
@@ -17,6 +18,12 @@ fn greet(name: &str) -> String {
 }
 
 fn main() {
+    // the settings are validated before the window opens
+    if let Err(e) = config::config() {
+        eprintln!("The configuration is invalid: {e}");
+        std::process::exit(1);
+    }
+
     tauri::Builder::default()
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_dialog::init())
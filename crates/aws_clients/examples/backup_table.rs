@@ -0,0 +1,58 @@
+//! Export the table to JSON Lines, or import it.
+//!
+//! ```sh
+//! cargo run --example backup_table --features db -- export http://localhost:8000 table-name backup.jsonl
+//! cargo run --example backup_table --features db -- import http://localhost:8000 table-name backup.jsonl [--force]
+//! ```
+
+use aws_clients::dynamodb::backup::ImportOptions;
+use aws_clients::dynamodb::builder::DynamoDbClientBuilder;
+use aws_sdk_dynamodb::config::Credentials;
+use shared::error::{Error, ErrorKind};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+const USAGE: &str =
+    "Usage: backup_table <export|import> <endpoint url> <table name> <file> [--force]";
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Error> {
+    let args = std::env::args().collect::<Vec<String>>();
+
+    let (Some(command), Some(endpoint_url), Some(table_name), Some(file)) =
+        (args.get(1), args.get(2), args.get(3), args.get(4))
+    else {
+        return Err(Error::invalid_input(USAGE));
+    };
+
+    let client = DynamoDbClientBuilder::new()
+        .endpoint_url(endpoint_url)
+        .region("us-west-2")
+        .credentials_provider(Credentials::new("key", "secret", None, None, "local"))
+        .table_name(table_name)
+        .build()
+        .await?;
+
+    match command.as_str() {
+        "export" => {
+            let file =
+                File::create(file).map_err(|e| Error::from_source(ErrorKind::Internal, e))?;
+            let summary = client.export_table(&mut BufWriter::new(file)).await?;
+            println!("{} items are exported", summary.items);
+        }
+        "import" => {
+            let file = File::open(file).map_err(|e| Error::from_source(ErrorKind::Internal, e))?;
+            let options = ImportOptions {
+                force: args.iter().any(|arg| arg == "--force"),
+            };
+            let summary = client.import_table(BufReader::new(file), options).await?;
+            println!(
+                "{} items are read, {} items are written, {} items are skipped",
+                summary.read, summary.written, summary.skipped
+            );
+        }
+        _ => return Err(Error::invalid_input(USAGE)),
+    }
+
+    Ok(())
+}
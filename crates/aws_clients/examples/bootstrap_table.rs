@@ -0,0 +1,35 @@
+//! Create the table and apply the migrations.
+//! This is used to prepare a local DynamoDB stand-in.
+//!
+//! ```sh
+//! cargo run --example bootstrap_table --features db -- http://localhost:8000 table-name
+//! ```
+
+use aws_clients::dynamodb::builder::DynamoDbClientBuilder;
+use aws_sdk_dynamodb::config::Credentials;
+use shared::error::Error;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Error> {
+    let args = std::env::args().collect::<Vec<String>>();
+
+    let (Some(endpoint_url), Some(table_name)) = (args.get(1), args.get(2)) else {
+        return Err(Error::invalid_input(
+            "Usage: bootstrap_table <endpoint url> <table name>",
+        ));
+    };
+
+    let client = DynamoDbClientBuilder::new()
+        .endpoint_url(endpoint_url)
+        .region("us-west-2")
+        .credentials_provider(Credentials::new("key", "secret", None, None, "local"))
+        .table_name(table_name)
+        .build()
+        .await?;
+
+    let version = client.bootstrap().await?;
+
+    println!("{table_name} is ready. The schema version is {version}.");
+
+    Ok(())
+}
@@ -0,0 +1,224 @@
+//! The audit log of the mutating operations
+//! A client that has an [Auditor] emits an [AuditRecord] for each mutating call.
+//! The records are saved by the [AuditSink], e.g. the DynamoDB client saves them in the audit partition.
+
+use futures::future::BoxFuture;
+use shared::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The mutating operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuditAction {
+    /// A collection item is saved
+    PutCollection,
+    /// Some attributes of a collection item are changed
+    UpdateCollection,
+    /// The object started to be unzipped
+    Unzipping,
+    /// The object was unzipped
+    Unzipped,
+    Trash,
+    Restore,
+    /// An object is deleted from the bucket
+    DeleteObject,
+    /// An object is moved to another key in the bucket
+    MoveObject,
+    /// The retention or the legal hold is changed
+    Protect,
+    CreateAlbum,
+    RenameAlbum,
+    DeleteAlbum,
+    AddToAlbum,
+    RemoveFromAlbum,
+    /// The caption, the tags, or the people are changed
+    Annotate,
+    /// The table is imported from a backup
+    Import,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::PutCollection => "PutCollection",
+            AuditAction::UpdateCollection => "UpdateCollection",
+            AuditAction::Unzipping => "Unzipping",
+            AuditAction::Unzipped => "Unzipped",
+            AuditAction::Trash => "Trash",
+            AuditAction::Restore => "Restore",
+            AuditAction::DeleteObject => "DeleteObject",
+            AuditAction::MoveObject => "MoveObject",
+            AuditAction::Protect => "Protect",
+            AuditAction::CreateAlbum => "CreateAlbum",
+            AuditAction::RenameAlbum => "RenameAlbum",
+            AuditAction::DeleteAlbum => "DeleteAlbum",
+            AuditAction::AddToAlbum => "AddToAlbum",
+            AuditAction::RemoveFromAlbum => "RemoveFromAlbum",
+            AuditAction::Annotate => "Annotate",
+            AuditAction::Import => "Import",
+        }
+    }
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let actions = [
+            AuditAction::PutCollection,
+            AuditAction::UpdateCollection,
+            AuditAction::Unzipping,
+            AuditAction::Unzipped,
+            AuditAction::Trash,
+            AuditAction::Restore,
+            AuditAction::DeleteObject,
+            AuditAction::MoveObject,
+            AuditAction::Protect,
+            AuditAction::CreateAlbum,
+            AuditAction::RenameAlbum,
+            AuditAction::DeleteAlbum,
+            AuditAction::AddToAlbum,
+            AuditAction::RemoveFromAlbum,
+            AuditAction::Annotate,
+            AuditAction::Import,
+        ];
+
+        match actions.into_iter().find(|action| action.as_str() == s) {
+            Some(action) => Ok(action),
+            None => Err(Error::internal(format!("Unknown audit action: {s}"))),
+        }
+    }
+}
+
+/// A record of a mutating operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    /// Who did the operation
+    pub actor: String,
+    pub action: AuditAction,
+    /// The key name of the object, or the id of the target, e.g. `Album#{id}`
+    pub key: String,
+    /// The state before the operation in JSON
+    pub before: Option<String>,
+    /// The state after the operation in JSON
+    pub after: Option<String>,
+    /// Epoch time (ms)
+    pub timestamp: u128,
+}
+
+/// The destination of the audit records
+pub trait AuditSink: Send + Sync {
+    fn record(&self, record: AuditRecord) -> BoxFuture<'_, Result<(), Error>>;
+}
+
+/// The actor and the sink that a client emits the records with
+#[derive(Clone)]
+pub struct Auditor {
+    sink: Arc<dyn AuditSink>,
+    actor: String,
+}
+
+impl Auditor {
+    pub fn new(sink: Arc<dyn AuditSink>, actor: impl Into<String>) -> Self {
+        Self {
+            sink,
+            actor: actor.into(),
+        }
+    }
+
+    /// Emit a record of the operation that is done now
+    pub async fn record(
+        &self,
+        action: AuditAction,
+        key: &str,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> Result<(), Error> {
+        let timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(now) => now.as_millis(),
+            Err(_) => return Err(Error::internal("Failed to get current time")),
+        };
+
+        self.sink
+            .record(AuditRecord {
+                actor: self.actor.to_string(),
+                action,
+                key: key.to_string(),
+                before,
+                after,
+                timestamp,
+            })
+            .await
+    }
+}
+
+/// Emit a record if the client has an auditor
+pub(crate) async fn record(
+    auditor: &Option<Auditor>,
+    action: AuditAction,
+    key: &str,
+    before: Option<String>,
+    after: Option<String>,
+) -> Result<(), Error> {
+    match auditor {
+        Some(auditor) => auditor.record(action, key, before, after).await,
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemorySink {
+        records: Mutex<Vec<AuditRecord>>,
+    }
+
+    impl AuditSink for MemorySink {
+        fn record(&self, record: AuditRecord) -> BoxFuture<'_, Result<(), Error>> {
+            self.records.lock().unwrap().push(record);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auditor_records_actor() {
+        // Arrange
+        let sink = Arc::new(MemorySink::default());
+        let auditor = Some(Auditor::new(sink.clone(), "user"));
+
+        // Act
+        record(&auditor, AuditAction::Trash, "key", None, None)
+            .await
+            .unwrap();
+        record(&None, AuditAction::Trash, "key", None, None)
+            .await
+            .unwrap();
+
+        // Assert
+        let records = sink.records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].actor, "user");
+        assert_eq!(records[0].action, AuditAction::Trash);
+    }
+
+    #[test]
+    fn test_action_round_trip() {
+        // Act
+        let result = AuditAction::from_str(AuditAction::RemoveFromAlbum.as_str());
+
+        // Assert
+        assert_eq!(result, Ok(AuditAction::RemoveFromAlbum));
+        assert!(AuditAction::from_str("Unknown").is_err());
+    }
+}
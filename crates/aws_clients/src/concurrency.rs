@@ -0,0 +1,180 @@
+//! The bounded concurrency of the fan-out calls
+//! A fan-out over every object of a backfill would open as many requests as the objects,
+//! so the calls run at most by the limit at once.
+
+use futures::{stream, FutureExt, StreamExt};
+use shared::error::Error;
+use std::future::Future;
+
+/// What happens when a call of the fan-out fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorMode {
+    /// Return the first error, and the calls that are not finished are dropped
+    #[default]
+    FailFast,
+    /// Run all the calls, and return an error if any of them failed
+    CollectAll,
+}
+
+/// The executor of the fan-out calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Concurrency {
+    /// The number of the calls that run at once. 0 is treated as 1.
+    pub limit: usize,
+    pub error_mode: ErrorMode,
+}
+
+impl Default for Concurrency {
+    fn default() -> Self {
+        Self {
+            limit: 16,
+            error_mode: ErrorMode::default(),
+        }
+    }
+}
+
+impl Concurrency {
+    pub fn new(limit: usize, error_mode: ErrorMode) -> Self {
+        Self { limit, error_mode }
+    }
+
+    /// Run the calls, and return the outputs in the order of the calls
+    /// With [ErrorMode::CollectAll], the error has the kind of the first failed call,
+    /// the number of the failures in the message, and the first failure as the source.
+    pub async fn run<T, Fut>(&self, calls: impl IntoIterator<Item = Fut>) -> Result<Vec<T>, Error>
+    where
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        // the calls are collected before they are polled, so the iterator is not held across the awaits,
+        // and the futures don't start until they are polled
+        let calls = calls.into_iter().collect::<Vec<Fut>>();
+        let mut results = stream::iter(calls.into_iter().enumerate())
+            .map(|(index, call)| call.map(move |result| (index, result)))
+            .buffer_unordered(self.limit.max(1));
+
+        let mut outputs = Vec::new();
+        let mut errors = Vec::new();
+        while let Some((index, result)) = results.next().await {
+            match (result, self.error_mode) {
+                (Ok(output), _) => outputs.push((index, output)),
+                (Err(e), ErrorMode::FailFast) => return Err(e),
+                (Err(e), ErrorMode::CollectAll) => errors.push((index, e)),
+            }
+        }
+
+        errors.sort_by_key(|(index, _)| *index);
+        let failures = errors.len();
+        if let Some((_, first)) = errors.into_iter().next() {
+            let message = format!(
+                "{failures} of {} calls failed, the first one: {first}",
+                failures + outputs.len()
+            );
+            return Err(Error::new(first.kind(), message).with_source(first));
+        }
+
+        outputs.sort_by_key(|(index, _)| *index);
+        Ok(outputs.into_iter().map(|(_, output)| output).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::error::Error as _;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// The counts of the calls
+    #[derive(Default)]
+    struct Calls {
+        started: AtomicUsize,
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+    }
+
+    impl Calls {
+        /// The call that fails if the index is in `failing`
+        async fn call(&self, index: usize, failing: &[usize]) -> Result<usize, Error> {
+            self.started.fetch_add(1, Ordering::SeqCst);
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            // the later calls finish first, so the outputs are not in the order of the completion
+            tokio::time::sleep(Duration::from_millis(10 - index as u64 % 10)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            match failing.contains(&index) {
+                true => Err(Error::throttled(format!("call {index} failed"))),
+                false => Ok(index),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_is_bounded_and_ordered() {
+        // Arrange
+        let calls = Calls::default();
+        let concurrency = Concurrency::new(3, ErrorMode::FailFast);
+
+        // Act
+        let result = concurrency
+            .run((0..20).map(|index| calls.call(index, &[])))
+            .await;
+
+        // Assert
+        assert_eq!(result.unwrap(), (0..20).collect::<Vec<usize>>());
+        assert_eq!(calls.max_running.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast_stops_at_the_first_error() {
+        // Arrange
+        let calls = Calls::default();
+        let concurrency = Concurrency::new(1, ErrorMode::FailFast);
+
+        // Act
+        let result = concurrency
+            .run((0..5).map(|index| calls.call(index, &[1, 3])))
+            .await;
+
+        // Assert
+        assert_eq!(result.unwrap_err(), Error::throttled("call 1 failed"));
+        assert_eq!(calls.started.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_collect_all_runs_all_the_calls() {
+        // Arrange
+        let calls = Calls::default();
+        let concurrency = Concurrency::new(2, ErrorMode::CollectAll);
+
+        // Act
+        let result = concurrency
+            .run((0..5).map(|index| calls.call(index, &[1, 3])))
+            .await;
+
+        // Assert
+        let error = result.unwrap_err();
+        assert_eq!(
+            error,
+            Error::throttled("2 of 5 calls failed, the first one: call 1 failed")
+        );
+        assert_eq!(error.source().unwrap().to_string(), "call 1 failed");
+        assert_eq!(calls.started.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_zero_limit_runs_one_at_a_time() {
+        // Arrange
+        let calls = Calls::default();
+        let concurrency = Concurrency::new(0, ErrorMode::FailFast);
+
+        // Act
+        let result = concurrency
+            .run((0..3).map(|index| calls.call(index, &[])))
+            .await;
+
+        // Assert
+        assert_eq!(result.unwrap(), vec![0, 1, 2]);
+        assert_eq!(calls.max_running.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,33 @@
+//! The configuration that the clients read
+//! It is loaded once, and the tests and the fakes run in the local profile.
+
+use shared::config::{Config, ConfigLoader, Profile};
+use shared::error::Error;
+use std::sync::OnceLock;
+
+/// The bucket of the tests and the fakes if it is not set, which LocalStack creates in storage/scripts/ready.d
+#[cfg(any(test, feature = "fake"))]
+const TEST_BUCKET_NAME: &str = "test-bucket";
+
+pub(crate) fn config() -> Result<&'static Config, Error> {
+    static CONFIG: OnceLock<Result<Config, Error>> = OnceLock::new();
+
+    match CONFIG.get_or_init(load) {
+        Ok(config) => Ok(config),
+        Err(e) => Err(Error::new(e.kind(), e.message())),
+    }
+}
+
+#[cfg(not(any(test, feature = "fake")))]
+fn load() -> Result<Config, Error> {
+    ConfigLoader::new().default_profile(Profile::Prod).load()
+}
+
+#[cfg(any(test, feature = "fake"))]
+fn load() -> Result<Config, Error> {
+    let mut config = ConfigLoader::new().default_profile(Profile::Local).load()?;
+    config
+        .bucket_name
+        .get_or_insert_with(|| TEST_BUCKET_NAME.to_string());
+    Ok(config)
+}
@@ -1,6 +1,21 @@
+pub mod album;
+pub mod annotation;
+pub(crate) mod attribute_json;
+pub mod audit_log;
+pub mod backup;
+pub mod builder;
 pub mod client;
+pub mod dedup;
 pub mod entities;
 pub(crate) mod environment_values;
+pub(crate) mod lookup;
+pub mod protection;
+pub mod schema;
+pub mod stats;
+pub mod storage;
+pub mod trash;
+pub mod upload_session;
+pub mod version;
 
 #[cfg(test)]
 pub mod test_util;
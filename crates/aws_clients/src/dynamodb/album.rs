@@ -0,0 +1,610 @@
+//! The albums, which are the named collections of the media across the dates
+//! An album has the members in the order that they are added.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#album>
+
+use crate::audit::{self, AuditAction};
+use crate::dynamodb::client::{get_now, DynamoDbClient};
+use crate::error::aws_error;
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue};
+use futures::TryFutureExt;
+use shared::error::{Error, ErrorKind};
+use std::collections::HashMap;
+
+/// The partition key of the albums
+const ALBUM_KEY: &str = "Album";
+
+/// An album
+#[derive(Debug, Clone, PartialEq)]
+pub struct Album {
+    /// The epoch time (ms) when the album is created
+    pub id: i64,
+    pub name: String,
+    /// The number of the members
+    pub item_count: i64,
+}
+
+/// A page of the members of an album
+#[derive(Debug, PartialEq)]
+pub struct AlbumPage {
+    /// The key names in the order that they are added
+    pub key_names: Vec<String>,
+    /// Pass it to get the next page. If None, this is the last page.
+    pub next_cursor: Option<String>,
+}
+
+impl DynamoDbClient {
+    /// Create a new album
+    /// time is mill sec, and it is the id of the album.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, album = name),
+        err(level = "warn")
+    )]
+    pub async fn create_album(&self, name: &str, time: Option<u128>) -> Result<Album, Error> {
+        let id = get_now(time)? as i64;
+
+        let request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(ALBUM_KEY.to_string()))
+            .item("SK", AttributeValue::N(id.to_string()))
+            .item("Name", AttributeValue::S(name.to_string()))
+            .item("ItemCount", AttributeValue::N("0".to_string()))
+            .item("NextPosition", AttributeValue::N("0".to_string()))
+            .condition_expression("attribute_not_exists(PK)");
+
+        // not retried, since the replay of the applied put fails by its condition
+        if let Err(e) = request.send().await {
+            return match e.as_service_error() {
+                Some(service_error) if service_error.is_conditional_check_failed_exception() => {
+                    Err(Error::conflict(format!("The album {id} already exists")))
+                }
+                _ => Err(aws_error(e)),
+            };
+        }
+
+        audit::record(
+            &self.auditor,
+            AuditAction::CreateAlbum,
+            &members_key(id),
+            None,
+            Some(serde_json::json!({ "name": name }).to_string()),
+        )
+        .await?;
+
+        Ok(Album {
+            id,
+            name: name.to_string(),
+            item_count: 0,
+        })
+    }
+
+    /// Rename the album
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, album_id = %album_id),
+        err(level = "warn")
+    )]
+    pub async fn rename_album(&self, album_id: i64, name: &str) -> Result<(), Error> {
+        let request = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(ALBUM_KEY.to_string()))
+            .key("SK", AttributeValue::N(album_id.to_string()))
+            .update_expression("SET #name = :name")
+            .condition_expression("attribute_exists(PK)")
+            .expression_attribute_names("#name", "Name")
+            .expression_attribute_values(":name", AttributeValue::S(name.to_string()))
+            .return_values(ReturnValue::UpdatedOld);
+
+        // the replay of the applied rename sets the same name, so it is retried
+        let old_name = self
+            .retry
+            .run("UpdateItem", || {
+                request
+                    .clone()
+                    .send()
+                    .map_err(|e| match e.as_service_error() {
+                        Some(service_error)
+                            if service_error.is_conditional_check_failed_exception() =>
+                        {
+                            Error::not_found(format!("The album {album_id} is not found"))
+                        }
+                        _ => aws_error(e),
+                    })
+            })
+            .await?
+            .attributes
+            .and_then(|attributes| attributes.get("Name").cloned())
+            .and_then(|old_name| old_name.as_s().ok().cloned());
+
+        audit::record(
+            &self.auditor,
+            AuditAction::RenameAlbum,
+            &members_key(album_id),
+            Some(serde_json::json!({ "name": old_name }).to_string()),
+            Some(serde_json::json!({ "name": name }).to_string()),
+        )
+        .await
+    }
+
+    /// Delete the album and its members
+    /// The collection items are not deleted.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, album_id = %album_id),
+        err(level = "warn")
+    )]
+    pub async fn delete_album(&self, album_id: i64) -> Result<(), Error> {
+        for member in self.query_partition(&members_key(album_id)).await? {
+            let Some(Ok(key_name)) = member.get("KeyName").map(|key_name| key_name.as_s()) else {
+                return Err(Error::internal(
+                    "KeyName must be a string in the album member",
+                ));
+            };
+            self.delete_item(&membership_key(key_name), &album_id.to_string())
+                .await?;
+            self.delete_item(&members_key(album_id), &position(&member)?.to_string())
+                .await?;
+        }
+
+        self.delete_item(ALBUM_KEY, &album_id.to_string()).await?;
+
+        audit::record(
+            &self.auditor,
+            AuditAction::DeleteAlbum,
+            &members_key(album_id),
+            None,
+            None,
+        )
+        .await
+    }
+
+    /// get the albums in the order of the creation
+    #[tracing::instrument(skip_all, fields(table = %self.table_name), err(level = "warn"))]
+    pub async fn get_albums(&self) -> Result<Vec<Album>, Error> {
+        self.query_partition(ALBUM_KEY)
+            .await?
+            .iter()
+            .map(album_from_attributes)
+            .collect()
+    }
+
+    /// Add the media to the album.
+    /// Returns false if the media is already in the album.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, album_id = %album_id, key = key_name),
+        err(level = "warn")
+    )]
+    pub async fn add_to_album(&self, album_id: i64, key_name: &str) -> Result<bool, Error> {
+        let position = self.next_position(album_id).await?;
+
+        let request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(membership_key(key_name)))
+            .item("SK", AttributeValue::N(album_id.to_string()))
+            .item("Position", AttributeValue::N(position.to_string()))
+            .condition_expression("attribute_not_exists(PK)");
+
+        // not retried, since the replay of the applied put fails by its condition
+        if let Err(e) = request.send().await {
+            return match e.as_service_error() {
+                Some(service_error) if service_error.is_conditional_check_failed_exception() => {
+                    Ok(false)
+                }
+                _ => Err(aws_error(e)),
+            };
+        }
+
+        let request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(members_key(album_id)))
+            .item("SK", AttributeValue::N(position.to_string()))
+            .item("KeyName", AttributeValue::S(key_name.to_string()));
+
+        self.retry
+            .run("PutItem", || request.clone().send().map_err(aws_error))
+            .await?;
+
+        self.add_item_count(album_id, 1).await?;
+
+        audit::record(
+            &self.auditor,
+            AuditAction::AddToAlbum,
+            key_name,
+            None,
+            Some(serde_json::json!({ "album": album_id, "position": position }).to_string()),
+        )
+        .await?;
+
+        Ok(true)
+    }
+
+    /// Remove the media from the album.
+    /// Returns false if the media is not in the album.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, album_id = %album_id, key = key_name),
+        err(level = "warn")
+    )]
+    pub async fn remove_from_album(&self, album_id: i64, key_name: &str) -> Result<bool, Error> {
+        let request = self
+            .client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(membership_key(key_name)))
+            .key("SK", AttributeValue::N(album_id.to_string()))
+            .return_values(ReturnValue::AllOld);
+
+        // not retried, since the replay of the applied delete returns no membership
+        let membership = match request.send().await {
+            Ok(output) => match output.attributes {
+                Some(attributes) => attributes,
+                None => return Ok(false),
+            },
+            Err(e) => return Err(aws_error(e)),
+        };
+
+        let position = position(&membership)?;
+        self.delete_item(&members_key(album_id), &position.to_string())
+            .await?;
+        self.add_item_count(album_id, -1).await?;
+
+        audit::record(
+            &self.auditor,
+            AuditAction::RemoveFromAlbum,
+            key_name,
+            Some(serde_json::json!({ "album": album_id, "position": position }).to_string()),
+            None,
+        )
+        .await?;
+
+        Ok(true)
+    }
+
+    /// Remove the media from all albums.
+    /// This is called when the collection item is deleted.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name),
+        err(level = "warn")
+    )]
+    pub async fn remove_from_all_albums(&self, key_name: &str) -> Result<(), Error> {
+        for membership in self.query_partition(&membership_key(key_name)).await? {
+            let album_id = match membership.get("SK").map(|sk| sk.as_n()) {
+                Some(Ok(sk)) => sk
+                    .parse::<i64>()
+                    .map_err(|e| Error::from_source(ErrorKind::Internal, e))?,
+                _ => {
+                    return Err(Error::internal(
+                        "SK must be a number in the album membership",
+                    ))
+                }
+            };
+            self.remove_from_album(album_id, key_name).await?;
+        }
+
+        Ok(())
+    }
+
+    /// get the members of the album in the order that they are added
+    /// The cursor is the one that is returned by the previous page.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, album_id = %album_id, limit = %limit),
+        err(level = "warn")
+    )]
+    pub async fn get_album_items(
+        &self,
+        album_id: i64,
+        limit: i32,
+        cursor: Option<&str>,
+    ) -> Result<AlbumPage, Error> {
+        let after = match cursor {
+            Some(cursor) => match cursor.parse::<i64>() {
+                Ok(after) => after,
+                Err(_) => return Err(Error::invalid_input(format!("Invalid cursor: {cursor}"))),
+            },
+            None => -1,
+        };
+
+        let request = self
+            .client
+            .query()
+            .table_name(&self.table_name)
+            .key_condition_expression("PK = :pk AND SK > :after")
+            .expression_attribute_values(":pk", AttributeValue::S(members_key(album_id)))
+            .expression_attribute_values(":after", AttributeValue::N(after.to_string()))
+            .limit(limit);
+
+        let output = self
+            .retry
+            .run("Query", || request.clone().send().map_err(aws_error))
+            .await?;
+
+        let mut key_names = Vec::new();
+        let mut last_position = None;
+        for member in output.items() {
+            match member.get("KeyName").map(|key_name| key_name.as_s()) {
+                Some(Ok(key_name)) => key_names.push(key_name.to_owned()),
+                _ => {
+                    return Err(Error::internal(
+                        "KeyName must be a string in the album member",
+                    ))
+                }
+            }
+            last_position = Some(position(member)?);
+        }
+
+        let next_cursor = match output.last_evaluated_key {
+            Some(_) => last_position.map(|position| position.to_string()),
+            None => None,
+        };
+
+        Ok(AlbumPage {
+            key_names,
+            next_cursor,
+        })
+    }
+
+    /// Take the next position of the album
+    /// Returns Err if the album doesn't exist.
+    async fn next_position(&self, album_id: i64) -> Result<i64, Error> {
+        let request = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(ALBUM_KEY.to_string()))
+            .key("SK", AttributeValue::N(album_id.to_string()))
+            .update_expression("ADD NextPosition :one")
+            .condition_expression("attribute_exists(PK)")
+            .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+            .return_values(ReturnValue::UpdatedOld);
+
+        // the replay of the applied update skips a position, which keeps the order
+        let attributes = self
+            .retry
+            .run("UpdateItem", || {
+                request
+                    .clone()
+                    .send()
+                    .map_err(|e| match e.as_service_error() {
+                        Some(service_error)
+                            if service_error.is_conditional_check_failed_exception() =>
+                        {
+                            Error::not_found(format!("The album {album_id} is not found"))
+                        }
+                        _ => aws_error(e),
+                    })
+            })
+            .await?
+            .attributes
+            .unwrap_or_default();
+
+        match attributes
+            .get("NextPosition")
+            .map(|position| position.as_n())
+        {
+            Some(Ok(position)) => position
+                .parse::<i64>()
+                .map_err(|e| Error::from_source(ErrorKind::Internal, e)),
+            _ => Err(Error::internal(
+                "NextPosition must be a number in the album",
+            )),
+        }
+    }
+
+    async fn add_item_count(&self, album_id: i64, count: i64) -> Result<(), Error> {
+        let request = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(ALBUM_KEY.to_string()))
+            .key("SK", AttributeValue::N(album_id.to_string()))
+            .update_expression("ADD ItemCount :count")
+            .condition_expression("attribute_exists(PK)")
+            .expression_attribute_values(":count", AttributeValue::N(count.to_string()));
+
+        // not retried, since the replay of the applied update counts twice
+        match request.send().await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(aws_error(e)),
+        }
+    }
+
+    async fn delete_item(&self, pk: &str, sk: &str) -> Result<(), Error> {
+        let request = self
+            .client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(pk.to_string()))
+            .key("SK", AttributeValue::N(sk.to_string()));
+
+        self.retry
+            .run("DeleteItem", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|_| ())
+    }
+}
+
+/// The partition key of the members of the album
+fn members_key(album_id: i64) -> String {
+    format!("Album#{album_id}")
+}
+
+/// The partition key of the albums that the media belongs to
+fn membership_key(key_name: &str) -> String {
+    format!("AlbumMember#{key_name}")
+}
+
+fn position(item: &HashMap<String, AttributeValue>) -> Result<i64, Error> {
+    // the position is the SK of the member, and the attribute of the membership
+    let attribute = match item.get("Position") {
+        Some(position) => position,
+        None => match item.get("SK") {
+            Some(sk) => sk,
+            None => return Err(Error::internal("The position is not found")),
+        },
+    };
+
+    match attribute.as_n().map(|n| n.parse::<i64>()) {
+        Ok(Ok(position)) => Ok(position),
+        _ => Err(Error::internal("The position must be a number")),
+    }
+}
+
+fn album_from_attributes(item: &HashMap<String, AttributeValue>) -> Result<Album, Error> {
+    let id = match item.get("SK").map(|sk| sk.as_n()) {
+        Some(Ok(sk)) => sk
+            .parse::<i64>()
+            .map_err(|e| Error::from_source(ErrorKind::Internal, e))?,
+        _ => return Err(Error::internal("SK must be a number in the album")),
+    };
+    let name = match item.get("Name").map(|name| name.as_s()) {
+        Some(Ok(name)) => name.to_owned(),
+        _ => return Err(Error::internal("Name must be a string in the album")),
+    };
+    let item_count = match item.get("ItemCount").map(|count| count.as_n()) {
+        Some(Ok(count)) => count
+            .parse::<i64>()
+            .map_err(|e| Error::from_source(ErrorKind::Internal, e))?,
+        _ => return Err(Error::internal("ItemCount must be a number in the album")),
+    };
+
+    Ok(Album {
+        id,
+        name,
+        item_count,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY_NAMES: [&str; 3] = [
+        "1985/04/04/1985-04-04-12-34-50.MOV",
+        "1984/04/04/1984-04-04-12-34-50.MOV",
+        "1984/05/04/1984-05-04-12-34-50.MOV",
+    ];
+
+    #[tokio::test]
+    async fn test_create_rename_and_delete_album() {
+        // Arrange
+        let client = DynamoDbClient::new("test_create_rename_and_delete_album").await;
+
+        // Act
+        let album = client.create_album("First steps", Some(1)).await.unwrap();
+        client.rename_album(album.id, "First walk").await.unwrap();
+
+        // Assert
+        assert_eq!(
+            client.get_albums().await.unwrap(),
+            [Album {
+                id: 1,
+                name: "First walk".to_string(),
+                item_count: 0
+            }]
+        );
+
+        // Act
+        client.delete_album(album.id).await.unwrap();
+
+        // Assert
+        assert!(client.get_albums().await.unwrap().is_empty());
+        assert!(client.rename_album(album.id, "name").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_album_items_are_paginated_in_added_order() {
+        // Arrange
+        let client = DynamoDbClient::new("test_album_items_are_paginated_in_added_order").await;
+        let album = client.create_album("Summer trip", None).await.unwrap();
+        for key_name in KEY_NAMES {
+            assert!(client.add_to_album(album.id, key_name).await.unwrap());
+        }
+
+        // Act
+        let first_page = client.get_album_items(album.id, 2, None).await.unwrap();
+        let second_page = client
+            .get_album_items(album.id, 2, first_page.next_cursor.as_deref())
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(first_page.key_names, KEY_NAMES[..2]);
+        assert_eq!(second_page.key_names, KEY_NAMES[2..]);
+        assert_eq!(second_page.next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove() {
+        // Arrange
+        let client = DynamoDbClient::new("test_add_and_remove").await;
+        let album = client.create_album("Summer trip", None).await.unwrap();
+        client.add_to_album(album.id, KEY_NAMES[0]).await.unwrap();
+
+        // Act
+        let added_twice = client.add_to_album(album.id, KEY_NAMES[0]).await.unwrap();
+        let removed = client
+            .remove_from_album(album.id, KEY_NAMES[0])
+            .await
+            .unwrap();
+        let removed_twice = client
+            .remove_from_album(album.id, KEY_NAMES[0])
+            .await
+            .unwrap();
+
+        // Assert
+        assert!(!added_twice);
+        assert!(removed);
+        assert!(!removed_twice);
+        assert_eq!(client.get_albums().await.unwrap()[0].item_count, 0);
+        assert!(client
+            .get_album_items(album.id, 10, None)
+            .await
+            .unwrap()
+            .key_names
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_add_to_album_not_found() {
+        // Arrange
+        let client = DynamoDbClient::new("test_add_to_album_not_found").await;
+
+        // Act
+        let result = client.add_to_album(1, KEY_NAMES[0]).await;
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remove_from_all_albums() {
+        // Arrange
+        let client = DynamoDbClient::new("test_remove_from_all_albums").await;
+        let first = client.create_album("First", Some(1)).await.unwrap();
+        let second = client.create_album("Second", Some(2)).await.unwrap();
+        for album in [&first, &second] {
+            client.add_to_album(album.id, KEY_NAMES[0]).await.unwrap();
+            client.add_to_album(album.id, KEY_NAMES[1]).await.unwrap();
+        }
+
+        // Act
+        client.remove_from_all_albums(KEY_NAMES[0]).await.unwrap();
+
+        // Assert
+        for album in [&first, &second] {
+            let page = client.get_album_items(album.id, 10, None).await.unwrap();
+            assert_eq!(page.key_names, [KEY_NAMES[1]]);
+        }
+    }
+}
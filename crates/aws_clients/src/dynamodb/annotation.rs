@@ -0,0 +1,452 @@
+//! The annotations of the media, which are the caption, the tags, and the people
+//! The annotation is saved apart from the collection item, so saving the collection item again doesn't clear it.
+//! The inverted index items are maintained to list the media by a tag or a person.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#annotation>
+
+use crate::audit::{self, AuditAction};
+use crate::dynamodb::client::{get_now, DynamoDbClient};
+use crate::error::aws_error;
+use aws_sdk_dynamodb::types::AttributeValue;
+use futures::TryFutureExt;
+use shared::error::Error;
+use std::collections::{BTreeSet, HashMap};
+use time_file_name::file_datetime::PathDateTime;
+
+/// The annotation of a media
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Annotation {
+    pub caption: Option<String>,
+    /// The tags are saved in lowercase.
+    pub tags: BTreeSet<String>,
+    /// The people who are in the media
+    pub people: BTreeSet<String>,
+}
+
+impl Annotation {
+    /// Trim the labels and make the tags lowercase.
+    /// Returns Err if there is an empty label.
+    fn normalized(&self) -> Result<Self, Error> {
+        let normalize = |labels: &BTreeSet<String>, lowercase: bool| {
+            labels
+                .iter()
+                .map(|label| match (label.trim(), lowercase) {
+                    ("", _) => Err(Error::invalid_input("An empty label is not allowed")),
+                    (label, true) => Ok(label.to_lowercase()),
+                    (label, false) => Ok(label.to_string()),
+                })
+                .collect::<Result<BTreeSet<String>, Error>>()
+        };
+
+        Ok(Self {
+            caption: self
+                .caption
+                .as_ref()
+                .map(|caption| caption.trim().to_string())
+                .filter(|caption| !caption.is_empty()),
+            tags: normalize(&self.tags, true)?,
+            people: normalize(&self.people, false)?,
+        })
+    }
+    /// The annotation in JSON for the audit record
+    fn to_json(&self) -> String {
+        serde_json::json!({
+            "caption": self.caption,
+            "tags": self.tags,
+            "people": self.people,
+        })
+        .to_string()
+    }
+}
+
+/// The kind of the inverted index
+#[derive(Clone, Copy)]
+enum Label {
+    Tag,
+    Person,
+}
+
+impl Label {
+    fn key(&self, label: &str) -> String {
+        match self {
+            Label::Tag => format!("Tag#{label}"),
+            Label::Person => format!("Person#{label}"),
+        }
+    }
+}
+
+impl DynamoDbClient {
+    /// get the annotation of the media
+    /// If the media is not annotated, returns the empty one.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name),
+        err(level = "warn")
+    )]
+    pub async fn get_annotation(&self, key_name: &str) -> Result<Annotation, Error> {
+        let request = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(annotation_key(key_name)))
+            .key("SK", AttributeValue::N("0".to_string()));
+
+        let output = self
+            .retry
+            .run("GetItem", || request.clone().send().map_err(aws_error))
+            .await?;
+
+        match output.item {
+            Some(item) => annotation_from_attributes(&item),
+            None => Ok(Annotation::default()),
+        }
+    }
+
+    /// Replace the annotation of the media.
+    /// The inverted index items of the added labels are put, and the ones of the removed labels are deleted.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name),
+        err(level = "warn")
+    )]
+    pub async fn put_annotation(
+        &self,
+        key_name: &str,
+        annotation: &Annotation,
+    ) -> Result<Annotation, Error> {
+        let annotation = annotation.normalized()?;
+        let unix_time = PathDateTime::parse(key_name)
+            .map_err(Error::invalid_input)?
+            .unix_time;
+        let saved = self.get_annotation(key_name).await?;
+
+        let mut request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(annotation_key(key_name)))
+            .item("SK", AttributeValue::N("0".to_string()))
+            .item("KeyName", AttributeValue::S(key_name.to_string()))
+            .item("UpdatedAt", AttributeValue::N(get_now(None)?.to_string()));
+
+        if let Some(caption) = &annotation.caption {
+            request = request.item("Caption", AttributeValue::S(caption.to_string()));
+        }
+        // an empty set is not allowed in DynamoDB
+        if !annotation.tags.is_empty() {
+            request = request.item(
+                "Tags",
+                AttributeValue::Ss(annotation.tags.iter().cloned().collect()),
+            );
+        }
+        if !annotation.people.is_empty() {
+            request = request.item(
+                "People",
+                AttributeValue::Ss(annotation.people.iter().cloned().collect()),
+            );
+        }
+
+        self.retry
+            .run("PutItem", || request.clone().send().map_err(aws_error))
+            .await?;
+
+        for (label, saved_labels, labels) in [
+            (Label::Tag, &saved.tags, &annotation.tags),
+            (Label::Person, &saved.people, &annotation.people),
+        ] {
+            for removed in saved_labels.difference(labels) {
+                self.delete_index_item(label, removed, unix_time).await?;
+            }
+            for added in labels.difference(saved_labels) {
+                self.put_index_item(label, added, unix_time, key_name)
+                    .await?;
+            }
+        }
+
+        if saved != annotation {
+            audit::record(
+                &self.auditor,
+                AuditAction::Annotate,
+                key_name,
+                Some(saved.to_json()),
+                Some(annotation.to_json()),
+            )
+            .await?;
+        }
+
+        Ok(annotation)
+    }
+
+    /// Delete the annotation and its inverted index items.
+    /// This is called when the collection item is deleted.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name),
+        err(level = "warn")
+    )]
+    pub async fn delete_annotation(&self, key_name: &str) -> Result<(), Error> {
+        self.put_annotation(key_name, &Annotation::default())
+            .await?;
+
+        let request = self
+            .client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(annotation_key(key_name)))
+            .key("SK", AttributeValue::N("0".to_string()));
+
+        self.retry
+            .run("DeleteItem", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|_| ())
+    }
+
+    /// get the key names of the media that have the tag, the newest first
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, tag = %tag),
+        err(level = "warn")
+    )]
+    pub async fn get_keys_by_tag(&self, tag: &str) -> Result<Vec<String>, Error> {
+        self.query_index(Label::Tag, &tag.trim().to_lowercase())
+            .await
+    }
+
+    /// get the key names of the media that the person is in, the newest first
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, person = %person),
+        err(level = "warn")
+    )]
+    pub async fn get_keys_by_person(&self, person: &str) -> Result<Vec<String>, Error> {
+        self.query_index(Label::Person, person.trim()).await
+    }
+
+    async fn query_index(&self, label: Label, value: &str) -> Result<Vec<String>, Error> {
+        let mut key_names = Vec::new();
+        let mut exclusive_start_key = None;
+
+        loop {
+            let request = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk")
+                .expression_attribute_values(":pk", AttributeValue::S(label.key(value)))
+                .scan_index_forward(false)
+                .set_exclusive_start_key(exclusive_start_key);
+
+            let output = self
+                .retry
+                .run("Query", || request.clone().send().map_err(aws_error))
+                .await?;
+
+            for item in output.items() {
+                match item.get("KeyName").map(|key_name| key_name.as_s()) {
+                    Some(Ok(key_name)) => key_names.push(key_name.to_owned()),
+                    _ => {
+                        return Err(Error::internal(
+                            "KeyName must be a string in the index item",
+                        ))
+                    }
+                }
+            }
+
+            match output.last_evaluated_key {
+                Some(key) => exclusive_start_key = Some(key),
+                None => break,
+            }
+        }
+
+        Ok(key_names)
+    }
+
+    async fn put_index_item(
+        &self,
+        label: Label,
+        value: &str,
+        unix_time: i64,
+        key_name: &str,
+    ) -> Result<(), Error> {
+        let request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(label.key(value)))
+            .item("SK", AttributeValue::N(unix_time.to_string()))
+            .item("KeyName", AttributeValue::S(key_name.to_string()));
+
+        self.retry
+            .run("PutItem", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|_| ())
+    }
+
+    async fn delete_index_item(
+        &self,
+        label: Label,
+        value: &str,
+        unix_time: i64,
+    ) -> Result<(), Error> {
+        let request = self
+            .client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(label.key(value)))
+            .key("SK", AttributeValue::N(unix_time.to_string()));
+
+        self.retry
+            .run("DeleteItem", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|_| ())
+    }
+}
+
+/// The partition key of the annotation
+fn annotation_key(key_name: &str) -> String {
+    format!("Annotation#{key_name}")
+}
+
+fn annotation_from_attributes(item: &HashMap<String, AttributeValue>) -> Result<Annotation, Error> {
+    let caption = match item.get("Caption").map(|caption| caption.as_s()) {
+        None => None,
+        Some(Ok(caption)) => Some(caption.to_owned()),
+        Some(Err(_)) => return Err(Error::internal("Caption must be a string")),
+    };
+    let labels = |name: &str| match item.get(name).map(|labels| labels.as_ss()) {
+        None => Ok(BTreeSet::new()),
+        Some(Ok(labels)) => Ok(labels.iter().cloned().collect()),
+        Some(Err(_)) => Err(Error::internal(format!("{name} must be a string set"))),
+    };
+
+    Ok(Annotation {
+        caption,
+        tags: labels("Tags")?,
+        people: labels("People")?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const OLD_KEY_NAME: &str = "1984/04/04/1984-04-04-12-34-50.MOV";
+    const NEW_KEY_NAME: &str = "1985/04/04/1985-04-04-12-34-50.MOV";
+
+    fn annotation(caption: &str, tags: &[&str], people: &[&str]) -> Annotation {
+        Annotation {
+            caption: Some(caption.to_string()),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            people: people.iter().map(|person| person.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_put_and_get_annotation() {
+        // Arrange
+        let client = DynamoDbClient::new("test_put_and_get_annotation").await;
+
+        // Act
+        client
+            .put_annotation(
+                OLD_KEY_NAME,
+                &annotation(" First steps ", &["Family", "home"], &["Taro"]),
+            )
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(
+            client.get_annotation(OLD_KEY_NAME).await.unwrap(),
+            annotation("First steps", &["family", "home"], &["Taro"])
+        );
+        assert_eq!(
+            client.get_annotation(NEW_KEY_NAME).await.unwrap(),
+            Annotation::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_keys_by_label_newest_first() {
+        // Arrange
+        let client = DynamoDbClient::new("test_get_keys_by_label_newest_first").await;
+        client
+            .put_annotation(OLD_KEY_NAME, &annotation("old", &["family"], &["Taro"]))
+            .await
+            .unwrap();
+        client
+            .put_annotation(NEW_KEY_NAME, &annotation("new", &["family"], &["Hanako"]))
+            .await
+            .unwrap();
+
+        // Act
+        let by_tag = client.get_keys_by_tag("Family").await.unwrap();
+        let by_person = client.get_keys_by_person("Taro").await.unwrap();
+
+        // Assert
+        assert_eq!(by_tag, [NEW_KEY_NAME, OLD_KEY_NAME]);
+        assert_eq!(by_person, [OLD_KEY_NAME]);
+    }
+
+    #[tokio::test]
+    async fn test_removed_labels_are_removed_from_index() {
+        // Arrange
+        let client = DynamoDbClient::new("test_removed_labels_are_removed_from_index").await;
+        client
+            .put_annotation(
+                OLD_KEY_NAME,
+                &annotation("", &["family", "home"], &["Taro"]),
+            )
+            .await
+            .unwrap();
+
+        // Act
+        client
+            .put_annotation(OLD_KEY_NAME, &annotation("", &["home"], &[]))
+            .await
+            .unwrap();
+
+        // Assert
+        assert!(client.get_keys_by_tag("family").await.unwrap().is_empty());
+        assert_eq!(
+            client.get_keys_by_tag("home").await.unwrap(),
+            [OLD_KEY_NAME]
+        );
+        assert!(client.get_keys_by_person("Taro").await.unwrap().is_empty());
+        assert_eq!(
+            client.get_annotation(OLD_KEY_NAME).await.unwrap().caption,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_annotation() {
+        // Arrange
+        let client = DynamoDbClient::new("test_delete_annotation").await;
+        client
+            .put_annotation(OLD_KEY_NAME, &annotation("old", &["family"], &["Taro"]))
+            .await
+            .unwrap();
+
+        // Act
+        client.delete_annotation(OLD_KEY_NAME).await.unwrap();
+
+        // Assert
+        assert_eq!(
+            client.get_annotation(OLD_KEY_NAME).await.unwrap(),
+            Annotation::default()
+        );
+        assert!(client.get_keys_by_tag("family").await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_empty_label_is_not_allowed() {
+        // Arrange
+        let annotation = annotation("caption", &[" "], &[]);
+
+        // Act
+        let result = annotation.normalized();
+
+        // Assert
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! Conversion between the DynamoDB attribute and the typed JSON.
+//! The format is the same as the DynamoDB JSON, e.g. `{"PK":{"S":"root"},"SK":{"N":"0"}}`.
+//! The binary attributes are not supported because this table doesn't have them.
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use serde_json::{Map, Value};
+use shared::error::Error;
+use std::collections::HashMap;
+
+/// Convert an item to the typed JSON object
+pub(crate) fn item_to_json(item: &HashMap<String, AttributeValue>) -> Result<Value, Error> {
+    let mut object = Map::new();
+
+    for (name, attribute) in item {
+        object.insert(name.to_string(), attribute_to_json(attribute)?);
+    }
+
+    Ok(Value::Object(object))
+}
+
+/// Convert the typed JSON object to an item
+pub(crate) fn json_to_item(json: &Value) -> Result<HashMap<String, AttributeValue>, Error> {
+    let Some(object) = json.as_object() else {
+        return Err(Error::internal("The item must be a JSON object"));
+    };
+
+    let mut item = HashMap::new();
+
+    for (name, value) in object {
+        item.insert(name.to_string(), json_to_attribute(value)?);
+    }
+
+    Ok(item)
+}
+
+fn attribute_to_json(attribute: &AttributeValue) -> Result<Value, Error> {
+    let (type_name, value) = match attribute {
+        AttributeValue::S(s) => ("S", Value::String(s.to_string())),
+        AttributeValue::N(n) => ("N", Value::String(n.to_string())),
+        AttributeValue::Bool(b) => ("BOOL", Value::Bool(*b)),
+        AttributeValue::Null(n) => ("NULL", Value::Bool(*n)),
+        AttributeValue::Ss(ss) => ("SS", string_array(ss)),
+        AttributeValue::Ns(ns) => ("NS", string_array(ns)),
+        AttributeValue::L(l) => (
+            "L",
+            Value::Array(
+                l.iter()
+                    .map(attribute_to_json)
+                    .collect::<Result<Vec<Value>, Error>>()?,
+            ),
+        ),
+        AttributeValue::M(m) => ("M", item_to_json(m)?),
+        _ => return Err(Error::internal("Unsupported attribute type")),
+    };
+
+    let mut object = Map::new();
+    object.insert(type_name.to_string(), value);
+
+    Ok(Value::Object(object))
+}
+
+fn json_to_attribute(json: &Value) -> Result<AttributeValue, Error> {
+    let Some((type_name, value)) = json.as_object().and_then(|object| {
+        if object.len() == 1 {
+            object.iter().next()
+        } else {
+            None
+        }
+    }) else {
+        return Err(Error::internal(format!("Invalid typed attribute: {json}")));
+    };
+
+    let invalid = || Error::internal(format!("Invalid {type_name} attribute: {value}"));
+
+    match type_name.as_str() {
+        "S" => match value.as_str() {
+            Some(s) => Ok(AttributeValue::S(s.to_string())),
+            None => Err(invalid()),
+        },
+        "N" => match value.as_str() {
+            Some(n) if n.parse::<f64>().is_ok() => Ok(AttributeValue::N(n.to_string())),
+            _ => Err(invalid()),
+        },
+        "BOOL" => match value.as_bool() {
+            Some(b) => Ok(AttributeValue::Bool(b)),
+            None => Err(invalid()),
+        },
+        "NULL" => match value.as_bool() {
+            Some(n) => Ok(AttributeValue::Null(n)),
+            None => Err(invalid()),
+        },
+        "SS" => Ok(AttributeValue::Ss(
+            json_string_array(value).ok_or_else(invalid)?,
+        )),
+        "NS" => Ok(AttributeValue::Ns(
+            json_string_array(value).ok_or_else(invalid)?,
+        )),
+        "L" => match value.as_array() {
+            Some(l) => Ok(AttributeValue::L(
+                l.iter()
+                    .map(json_to_attribute)
+                    .collect::<Result<Vec<AttributeValue>, Error>>()?,
+            )),
+            None => Err(invalid()),
+        },
+        "M" => Ok(AttributeValue::M(json_to_item(value)?)),
+        _ => Err(Error::internal(format!(
+            "Unsupported attribute type: {type_name}"
+        ))),
+    }
+}
+
+fn string_array(values: &[String]) -> Value {
+    Value::Array(
+        values
+            .iter()
+            .map(|value| Value::String(value.to_string()))
+            .collect(),
+    )
+}
+
+fn json_string_array(json: &Value) -> Option<Vec<String>> {
+    json.as_array()?
+        .iter()
+        .map(|value| value.as_str().map(|s| s.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        // Arrange
+        let item = HashMap::from([
+            ("PK".to_string(), AttributeValue::S("1984".to_string())),
+            (
+                "SK".to_string(),
+                AttributeValue::N("449930090000".to_string()),
+            ),
+            ("IsUnzipped".to_string(), AttributeValue::Bool(false)),
+            (
+                "SavedDate".to_string(),
+                AttributeValue::L(vec![AttributeValue::S("4".to_string())]),
+            ),
+            (
+                "Tags".to_string(),
+                AttributeValue::Ss(vec!["family".to_string()]),
+            ),
+            (
+                "Nested".to_string(),
+                AttributeValue::M(HashMap::from([(
+                    "Null".to_string(),
+                    AttributeValue::Null(true),
+                )])),
+            ),
+        ]);
+
+        // Act
+        let json = item_to_json(&item).unwrap();
+        let result = json_to_item(&json).unwrap();
+
+        // Assert
+        assert_eq!(result, item);
+        assert_eq!(json["SK"], serde_json::json!({"N": "449930090000"}));
+    }
+
+    #[test]
+    fn test_invalid_number() {
+        // Arrange
+        let json = serde_json::json!({"SK": {"N": "not a number"}});
+
+        // Act
+        let result = json_to_item(&json);
+
+        // Assert
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,316 @@
+//! The audit records in the table
+//! A record is saved in the time-ordered audit partition and in the partition of its key,
+//! so it can be queried by the time window and by the key.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#audit-log>
+
+use crate::audit::{AuditAction, AuditRecord, AuditSink};
+use crate::dynamodb::client::{request_token, DynamoDbClient};
+use crate::error::aws_error;
+use aws_sdk_dynamodb::types::{AttributeValue, Put, TransactWriteItem};
+use futures::future::BoxFuture;
+use futures::TryFutureExt;
+use shared::error::{Error, ErrorKind};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// The partition key of the time-ordered audit records
+const AUDIT_KEY: &str = "Audit";
+
+/// The SK is the timestamp (ms) followed by a sequence of 3 digits,
+/// so the records in the same millisecond don't overwrite each other.
+const SEQUENCE_SIZE: u128 = 1000;
+
+impl AuditSink for DynamoDbClient {
+    fn record(&self, record: AuditRecord) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(self.put_audit_record(record))
+    }
+}
+
+impl DynamoDbClient {
+    /// get the audit records of the key, the oldest first
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = %key),
+        err(level = "warn")
+    )]
+    pub async fn get_audit_records_by_key(&self, key: &str) -> Result<Vec<AuditRecord>, Error> {
+        self.query_partition(&audit_key_key(key))
+            .await?
+            .iter()
+            .map(audit_record_from_attributes)
+            .collect()
+    }
+
+    /// get the audit records in the time window, the oldest first
+    /// from and to are epoch time (ms), and both are inclusive.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, from = %from, to = %to),
+        err(level = "warn")
+    )]
+    pub async fn get_audit_records(&self, from: u128, to: u128) -> Result<Vec<AuditRecord>, Error> {
+        let mut records = Vec::new();
+        let mut exclusive_start_key = None;
+
+        loop {
+            let request = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk AND SK BETWEEN :from AND :to")
+                .expression_attribute_values(":pk", AttributeValue::S(AUDIT_KEY.to_string()))
+                .expression_attribute_values(
+                    ":from",
+                    AttributeValue::N((from * SEQUENCE_SIZE).to_string()),
+                )
+                .expression_attribute_values(
+                    ":to",
+                    AttributeValue::N((to * SEQUENCE_SIZE + SEQUENCE_SIZE - 1).to_string()),
+                )
+                .set_exclusive_start_key(exclusive_start_key);
+
+            let output = self
+                .retry
+                .run("Query", || request.clone().send().map_err(aws_error))
+                .await?;
+
+            for item in output.items() {
+                records.push(audit_record_from_attributes(item)?);
+            }
+
+            match output.last_evaluated_key {
+                Some(key) => exclusive_start_key = Some(key),
+                None => break,
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Save the record in the both partitions at once.
+    /// If the SK is taken by another record, the next sequence is tried.
+    async fn put_audit_record(&self, record: AuditRecord) -> Result<(), Error> {
+        let attributes = audit_record_to_attributes(&record);
+
+        for sequence in 0..SEQUENCE_SIZE {
+            let sk = AttributeValue::N((record.timestamp * SEQUENCE_SIZE + sequence).to_string());
+
+            let mut items = Vec::new();
+            for (pk, condition) in [
+                (AUDIT_KEY.to_string(), Some("attribute_not_exists(PK)")),
+                (audit_key_key(&record.key), None),
+            ] {
+                let mut item = attributes.clone();
+                item.insert("PK".to_string(), AttributeValue::S(pk));
+                item.insert("SK".to_string(), sk.clone());
+
+                let put = Put::builder()
+                    .table_name(&self.table_name)
+                    .set_item(Some(item))
+                    .set_condition_expression(condition.map(|condition| condition.to_string()))
+                    .build()
+                    .map_err(|e| Error::from_source(ErrorKind::Internal, e))?;
+                items.push(TransactWriteItem::builder().put(put).build());
+            }
+
+            // the token makes the replay of the applied transaction succeed instead of failing by its condition
+            let request = self
+                .client
+                .transact_write_items()
+                .set_transact_items(Some(items))
+                .client_request_token(request_token());
+
+            let saved = self
+                .retry
+                .run("TransactWriteItems", || async {
+                    match request.clone().send().await {
+                        Ok(_) => Ok(true),
+                        Err(e) => match e.as_service_error() {
+                            Some(service_error)
+                                if service_error.is_transaction_canceled_exception() =>
+                            {
+                                Ok(false)
+                            }
+                            _ => Err(aws_error(e)),
+                        },
+                    }
+                })
+                .await?;
+            if saved {
+                return Ok(());
+            }
+        }
+
+        Err(Error::internal(format!(
+            "The audit record of {} couldn't be saved",
+            record.key
+        )))
+    }
+}
+
+/// The partition key of the audit records of the key
+fn audit_key_key(key: &str) -> String {
+    format!("AuditKey#{key}")
+}
+
+fn audit_record_to_attributes(record: &AuditRecord) -> HashMap<String, AttributeValue> {
+    let mut item = HashMap::from([
+        (
+            "Actor".to_string(),
+            AttributeValue::S(record.actor.to_string()),
+        ),
+        (
+            "Action".to_string(),
+            AttributeValue::S(record.action.to_string()),
+        ),
+        ("Key".to_string(), AttributeValue::S(record.key.to_string())),
+        (
+            "Timestamp".to_string(),
+            AttributeValue::N(record.timestamp.to_string()),
+        ),
+    ]);
+
+    if let Some(before) = &record.before {
+        item.insert("Before".to_string(), AttributeValue::S(before.to_string()));
+    }
+    if let Some(after) = &record.after {
+        item.insert("After".to_string(), AttributeValue::S(after.to_string()));
+    }
+
+    item
+}
+
+fn audit_record_from_attributes(
+    item: &HashMap<String, AttributeValue>,
+) -> Result<AuditRecord, Error> {
+    let string = |name: &str| match item.get(name).map(|attribute| attribute.as_s()) {
+        None => Ok(None),
+        Some(Ok(value)) => Ok(Some(value.to_owned())),
+        Some(Err(_)) => Err(Error::internal(format!(
+            "{name} must be a string in the audit record"
+        ))),
+    };
+    let required = |name: &str| match string(name)? {
+        Some(value) => Ok(value),
+        None => Err(Error::internal(format!(
+            "{name} is not found in the audit record"
+        ))),
+    };
+
+    let timestamp = match item.get("Timestamp").map(|timestamp| timestamp.as_n()) {
+        Some(Ok(timestamp)) => timestamp
+            .parse::<u128>()
+            .map_err(|e| Error::from_source(ErrorKind::Internal, e))?,
+        _ => {
+            return Err(Error::internal(
+                "Timestamp must be a number in the audit record",
+            ))
+        }
+    };
+
+    Ok(AuditRecord {
+        actor: required("Actor")?,
+        action: AuditAction::from_str(&required("Action")?)?,
+        key: required("Key")?,
+        before: string("Before")?,
+        after: string("After")?,
+        timestamp,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::audit::Auditor;
+    use crate::dynamodb::client::DynamoClientTrait;
+    use crate::dynamodb::entities::collection::CollectionItem;
+    use std::sync::Arc;
+
+    const KEY_NAME: &str = "1984/04/04/1984-04-04-12-34-50.MOV";
+
+    fn record(key: &str, timestamp: u128) -> AuditRecord {
+        AuditRecord {
+            actor: "user".to_string(),
+            action: AuditAction::Trash,
+            key: key.to_string(),
+            before: None,
+            after: Some("{}".to_string()),
+            timestamp,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_records_in_the_same_millisecond() {
+        // Arrange
+        let client = DynamoDbClient::new("test_records_in_the_same_millisecond").await;
+
+        // Act
+        for key in ["first", "second", "first"] {
+            client.put_audit_record(record(key, 10)).await.unwrap();
+        }
+
+        // Assert
+        assert_eq!(client.get_audit_records(10, 10).await.unwrap().len(), 3);
+        assert_eq!(
+            client.get_audit_records_by_key("first").await.unwrap(),
+            [record("first", 10), record("first", 10)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_audit_records_in_time_window() {
+        // Arrange
+        let client = DynamoDbClient::new("test_get_audit_records_in_time_window").await;
+        for timestamp in [1, 2, 3] {
+            client
+                .put_audit_record(record("key", timestamp))
+                .await
+                .unwrap();
+        }
+
+        // Act
+        let result = client.get_audit_records(2, 3).await.unwrap();
+
+        // Assert
+        assert_eq!(result, [record("key", 2), record("key", 3)]);
+    }
+
+    #[tokio::test]
+    async fn test_mutating_calls_are_audited() {
+        // Arrange
+        let table_name = "test_mutating_calls_are_audited";
+        let sink = Arc::new(DynamoDbClient::new(table_name).await);
+        let client = DynamoDbClient::new(table_name)
+            .await
+            .with_auditor(Auditor::new(sink, "grandma"));
+
+        // Act
+        client
+            .put_collection_items(&vec![CollectionItem::dummy_object(KEY_NAME)])
+            .await
+            .unwrap();
+        client
+            .trash_collection_item(KEY_NAME, "grandma", None)
+            .await
+            .unwrap();
+        client.restore_collection_item(KEY_NAME).await.unwrap();
+
+        // Assert
+        let records = client.get_audit_records_by_key(KEY_NAME).await.unwrap();
+        let actions = records
+            .iter()
+            .map(|record| record.action)
+            .collect::<Vec<AuditAction>>();
+        assert_eq!(
+            actions,
+            [
+                AuditAction::PutCollection,
+                AuditAction::Trash,
+                AuditAction::Restore
+            ]
+        );
+        assert!(records.iter().all(|record| record.actor == "grandma"));
+        assert_eq!(records[0].before, None);
+        assert!(records[0].after.is_some());
+    }
+}
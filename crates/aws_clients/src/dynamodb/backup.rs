@@ -0,0 +1,440 @@
+//! Export and import of the whole table as JSON Lines
+//! Each line is an item in the typed JSON, e.g. `{"PK":{"S":"Lookup#root"},"SK":{"N":"1984"},"Entry":{"S":"1984"}}`.
+//! All items, the collections, the date lookups, and the unzip items, are exported.
+
+use crate::audit::{self, AuditAction};
+use crate::dynamodb::attribute_json::{item_to_json, json_to_item};
+use crate::dynamodb::client::DynamoDbClient;
+use crate::error::aws_error;
+use aws_sdk_dynamodb::types::{AttributeValue, KeysAndAttributes, PutRequest, WriteRequest};
+use futures::TryFutureExt;
+use shared::error::{Error, ErrorKind};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+/// The maximum number of items in a batch write request
+const BATCH_SIZE: usize = 25;
+
+/// The maximum number of keys in a batch get request
+const BATCH_GET_SIZE: usize = 100;
+
+/// The maximum number of attempts for the unprocessed items
+const BATCH_MAX_ATTEMPTS: u32 = 5;
+
+type Item = HashMap<String, AttributeValue>;
+
+/// The result of the export
+#[derive(Debug, PartialEq)]
+pub struct ExportSummary {
+    /// The number of exported items
+    pub items: usize,
+}
+
+/// The options of the import
+#[derive(Debug, Default)]
+pub struct ImportOptions {
+    /// If true, the items that are newer than the imported ones are also overwritten.
+    pub force: bool,
+}
+
+/// The result of the import
+#[derive(Debug, PartialEq)]
+pub struct ImportSummary {
+    /// The number of items that are read from the input
+    pub read: usize,
+    /// The number of items that are written to the table
+    pub written: usize,
+    /// The number of items that are not written because the saved ones are newer
+    pub skipped: usize,
+}
+
+impl DynamoDbClient {
+    /// Export every item in the table to the writer as JSON Lines.
+    /// The table is read by the paginated scans, so the whole table is not loaded into the memory.
+    #[tracing::instrument(skip_all, fields(table = %self.table_name), err(level = "warn"))]
+    pub async fn export_table(&self, writer: &mut impl Write) -> Result<ExportSummary, Error> {
+        let mut items = 0;
+        let mut exclusive_start_key = None;
+
+        loop {
+            let request = self
+                .client
+                .scan()
+                .table_name(&self.table_name)
+                .set_exclusive_start_key(exclusive_start_key);
+
+            let output = self
+                .retry
+                .run("Scan", || request.clone().send().map_err(aws_error))
+                .await?;
+
+            for item in output.items() {
+                if let Err(e) = writeln!(writer, "{}", item_to_json(item)?) {
+                    return Err(Error::internal(format!("Writing the export failed: {e}")));
+                }
+                items += 1;
+            }
+
+            match output.last_evaluated_key {
+                Some(key) => exclusive_start_key = Some(key),
+                None => break,
+            }
+        }
+
+        if let Err(e) = writer.flush() {
+            return Err(Error::internal(format!("Writing the export failed: {e}")));
+        }
+
+        Ok(ExportSummary { items })
+    }
+
+    /// Import the JSON Lines that is exported by [DynamoDbClient::export_table].
+    /// An item whose saved `UpdatedAt` is newer than the imported one is not overwritten unless the `force` option is set.
+    /// Returns Err if the number of the written and skipped items doesn't match the number of the read items.
+    #[tracing::instrument(skip_all, fields(table = %self.table_name), err(level = "warn"))]
+    pub async fn import_table(
+        &self,
+        reader: impl BufRead,
+        options: ImportOptions,
+    ) -> Result<ImportSummary, Error> {
+        let mut summary = ImportSummary {
+            read: 0,
+            written: 0,
+            skipped: 0,
+        };
+        let mut batch = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Err(Error::internal(format!("Reading the import failed: {e}"))),
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let json = match serde_json::from_str(&line) {
+                Ok(json) => json,
+                Err(e) => {
+                    return Err(Error::internal(format!(
+                        "Invalid JSON at line {}: {e}",
+                        index + 1
+                    )))
+                }
+            };
+
+            batch.push(json_to_item(&json)?);
+            summary.read += 1;
+
+            if batch.len() == BATCH_SIZE {
+                self.import_batch(std::mem::take(&mut batch), &options, &mut summary)
+                    .await?;
+            }
+        }
+
+        if !batch.is_empty() {
+            self.import_batch(batch, &options, &mut summary).await?;
+        }
+
+        if summary.read != summary.written + summary.skipped {
+            return Err(Error::internal(format!(
+                "The item count doesn't match: read {}, written {}, skipped {}",
+                summary.read, summary.written, summary.skipped
+            )));
+        }
+
+        audit::record(
+            &self.auditor,
+            AuditAction::Import,
+            &self.table_name,
+            None,
+            Some(
+                serde_json::json!({
+                    "read": summary.read,
+                    "written": summary.written,
+                    "skipped": summary.skipped,
+                })
+                .to_string(),
+            ),
+        )
+        .await?;
+
+        Ok(summary)
+    }
+
+    async fn import_batch(
+        &self,
+        items: Vec<Item>,
+        options: &ImportOptions,
+        summary: &mut ImportSummary,
+    ) -> Result<(), Error> {
+        let items = match options.force {
+            true => items,
+            false => {
+                let saved_items = self.batch_get(&items).await?;
+                let (items, skipped): (Vec<Item>, Vec<Item>) =
+                    items.into_iter().partition(|item| {
+                        match saved_items.iter().find(|saved| same_key(saved, item)) {
+                            Some(saved) => updated_at(saved) <= updated_at(item),
+                            None => true,
+                        }
+                    });
+                summary.skipped += skipped.len();
+                items
+            }
+        };
+
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let count = items.len();
+        self.batch_put(items).await?;
+        summary.written += count;
+
+        Ok(())
+    }
+
+    /// Get the saved items that have the same keys as the provided ones
+    async fn batch_get(&self, items: &[Item]) -> Result<Vec<Item>, Error> {
+        let mut keys = Vec::new();
+        for item in items {
+            let (Some(pk), Some(sk)) = (item.get("PK"), item.get("SK")) else {
+                return Err(Error::internal("The imported item doesn't have the key"));
+            };
+            let key = HashMap::from([
+                ("PK".to_string(), pk.clone()),
+                ("SK".to_string(), sk.clone()),
+            ]);
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+
+        self.batch_get_keys(keys).await
+    }
+
+    /// Get the items of the keys by the batch get
+    /// The keys are split into the batches, and the unprocessed ones are retried. The missing items are not returned.
+    pub(crate) async fn batch_get_keys(&self, keys: Vec<Item>) -> Result<Vec<Item>, Error> {
+        let mut saved_items = Vec::new();
+        for batch in keys.chunks(BATCH_GET_SIZE) {
+            saved_items.extend(self.send_batch_get(batch.to_vec()).await?);
+        }
+
+        Ok(saved_items)
+    }
+
+    async fn send_batch_get(&self, keys: Vec<Item>) -> Result<Vec<Item>, Error> {
+        let mut request_items = match KeysAndAttributes::builder().set_keys(Some(keys)).build() {
+            Ok(keys_and_attributes) => {
+                HashMap::from([(self.table_name.to_string(), keys_and_attributes)])
+            }
+            Err(e) => return Err(Error::from_source(ErrorKind::Internal, e)),
+        };
+
+        let mut saved_items = Vec::new();
+
+        for attempt in 0..BATCH_MAX_ATTEMPTS {
+            let request = self
+                .client
+                .batch_get_item()
+                .set_request_items(Some(request_items));
+
+            let output = self
+                .retry
+                .run("BatchGetItem", || request.clone().send().map_err(aws_error))
+                .await?;
+
+            if let Some(responses) = output.responses {
+                for (_, items) in responses {
+                    saved_items.extend(items);
+                }
+            }
+
+            match output.unprocessed_keys {
+                Some(unprocessed) if !unprocessed.is_empty() => request_items = unprocessed,
+                _ => return Ok(saved_items),
+            }
+
+            tokio::time::sleep(backoff(attempt)).await;
+        }
+
+        Err(Error::internal("Some keys couldn't be read"))
+    }
+
+    /// Put the items by the batch write
+    async fn batch_put(&self, items: Vec<Item>) -> Result<(), Error> {
+        let mut write_requests = Vec::new();
+        for item in items {
+            match PutRequest::builder().set_item(Some(item)).build() {
+                Ok(put_request) => {
+                    write_requests.push(WriteRequest::builder().put_request(put_request).build())
+                }
+                Err(e) => return Err(Error::from_source(ErrorKind::Internal, e)),
+            }
+        }
+
+        self.batch_write(write_requests).await
+    }
+
+    /// Send the write requests by the batch write
+    /// The requests are split into the batches, and the unprocessed ones are retried.
+    pub(crate) async fn batch_write(&self, write_requests: Vec<WriteRequest>) -> Result<(), Error> {
+        for batch in write_requests.chunks(BATCH_SIZE) {
+            self.send_batch_write(batch.to_vec()).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_batch_write(&self, write_requests: Vec<WriteRequest>) -> Result<(), Error> {
+        let mut request_items = HashMap::from([(self.table_name.to_string(), write_requests)]);
+
+        for attempt in 0..BATCH_MAX_ATTEMPTS {
+            let request = self
+                .client
+                .batch_write_item()
+                .set_request_items(Some(request_items));
+
+            let output = self
+                .retry
+                .run("BatchWriteItem", || {
+                    request.clone().send().map_err(aws_error)
+                })
+                .await?;
+
+            match output.unprocessed_items {
+                Some(unprocessed) if !unprocessed.is_empty() => request_items = unprocessed,
+                _ => return Ok(()),
+            }
+
+            tokio::time::sleep(backoff(attempt)).await;
+        }
+
+        Err(Error::internal("Some items couldn't be written"))
+    }
+}
+
+fn same_key(a: &Item, b: &Item) -> bool {
+    a.get("PK") == b.get("PK") && a.get("SK") == b.get("SK")
+}
+
+/// The updated time of the item
+/// If the item doesn't have it, it is considered as the oldest.
+fn updated_at(item: &Item) -> u128 {
+    match item.get("UpdatedAt").map(|updated_at| updated_at.as_n()) {
+        Some(Ok(n)) => n.parse::<u128>().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(100 * 2_u64.pow(attempt))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dynamodb::client::DynamoClientTrait;
+    use crate::dynamodb::entities::collection::CollectionItem;
+    use shared::traits::GetFileListTrait;
+
+    async fn save_test_data(client: &DynamoDbClient) {
+        let collections = vec![
+            CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-50.MOV"),
+            CollectionItem::dummy_object("1985/04/04/1985-04-04-12-34-50.MOV"),
+        ];
+        client.put_collection_items(&collections).await.unwrap();
+        client
+            .put_unzipped_item("1984/04/04/1984-04-04-12-34-50.MOV", Some(1))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import() {
+        // Arrange
+        let source = DynamoDbClient::new("test_export_and_import_source").await;
+        let target = DynamoDbClient::new("test_export_and_import_target").await;
+        save_test_data(&source).await;
+        let mut exported = Vec::new();
+
+        // Act
+        let export_summary = source.export_table(&mut exported).await.unwrap();
+        let import_summary = target
+            .import_table(exported.as_slice(), ImportOptions::default())
+            .await
+            .unwrap();
+
+        // Assert
+        // 2 collections, the lookup entries of root, 2 years, 2 months, and 2 days,
+        // an unzipped item, and the statistics of 2 years, 2 months, and 2 days
+        assert_eq!(export_summary, ExportSummary { items: 17 });
+        assert_eq!(
+            import_summary,
+            ImportSummary {
+                read: 17,
+                written: 17,
+                skipped: 0
+            }
+        );
+        assert_eq!(target.get_years().await.unwrap(), ["1984", "1985"]);
+        assert_eq!(
+            target.get_objects(1985, 4, 4).await.unwrap(),
+            ["1985/04/04/1985-04-04-12-34-50.MOV"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_does_not_overwrite_newer_items() {
+        // Arrange
+        let client = DynamoDbClient::new("test_import_does_not_overwrite_newer_items").await;
+        let key_name = "1984/04/04/1984-04-04-12-34-50.MOV";
+        client
+            .put_collection_items(&vec![CollectionItem::dummy_object(key_name)])
+            .await
+            .unwrap();
+        let old_item = format!(
+            r#"{{"PK":{{"S":"1984"}},"SK":{{"N":"449930090000"}},"IsUnzipped":{{"BOOL":true}},"Vault":{{"S":"old"}},"KeyName":{{"S":"{key_name}"}},"UpdatedAt":{{"N":"1"}}}}"#
+        );
+
+        // Act
+        let summary = client
+            .import_table(old_item.as_bytes(), ImportOptions::default())
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(summary.skipped, 1);
+        let saved = client.get_collection_item(key_name).await.unwrap().unwrap();
+        assert_eq!(saved.vault, "vault");
+
+        // Act
+        let summary = client
+            .import_table(old_item.as_bytes(), ImportOptions { force: true })
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(summary.written, 1);
+        let saved = client.get_collection_item(key_name).await.unwrap().unwrap();
+        assert_eq!(saved.vault, "old");
+    }
+
+    #[tokio::test]
+    async fn test_import_invalid_line() {
+        // Arrange
+        let client = DynamoDbClient::new("test_import_invalid_line").await;
+
+        // Act
+        let result = client
+            .import_table("not a json".as_bytes(), ImportOptions::default())
+            .await;
+
+        // Assert
+        assert!(result.is_err());
+    }
+}
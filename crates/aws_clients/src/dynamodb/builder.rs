@@ -0,0 +1,164 @@
+//! The builder of the DynamoDB client
+//! This is used to point the client at any endpoint and table, e.g. a local DynamoDB.
+
+use crate::concurrency::Concurrency;
+use crate::dynamodb::client::DynamoDbClient;
+use crate::retry::RetryPolicy;
+use aws_config::retry::RetryConfig;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::config::{
+    ProvideCredentials, Region, SharedCredentialsProvider, SharedHttpClient,
+};
+use shared::error::Error;
+
+/// The builder of the [DynamoDbClient]
+/// Only the table name is required.
+/// The settings that are not provided are read from the environment as the AWS SDK does.
+///
+/// # Example
+/// ```rust,no_run
+/// # use aws_clients::dynamodb::builder::DynamoDbClientBuilder;
+/// # use aws_sdk_dynamodb::config::Credentials;
+/// # use shared::error::Error;
+/// # async fn run() -> Result<(), Error> {
+/// let client = DynamoDbClientBuilder::new()
+///     .endpoint_url("http://localhost:8000")
+///     .region("us-west-2")
+///     .credentials_provider(Credentials::new("key", "secret", None, None, "local"))
+///     .table_name("table")
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct DynamoDbClientBuilder {
+    endpoint_url: Option<String>,
+    region: Option<Region>,
+    credentials_provider: Option<SharedCredentialsProvider>,
+    table_name: Option<String>,
+    retry_policy: Option<RetryPolicy>,
+    concurrency: Option<Concurrency>,
+    http_client: Option<SharedHttpClient>,
+}
+
+impl DynamoDbClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The endpoint URL, e.g. `http://localhost:8000`
+    pub fn endpoint_url(mut self, endpoint_url: impl Into<String>) -> Self {
+        self.endpoint_url = Some(endpoint_url.into());
+        self
+    }
+
+    /// The region, e.g. `us-west-2`
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(Region::new(region.into()));
+        self
+    }
+
+    /// The credentials provider
+    pub fn credentials_provider(
+        mut self,
+        credentials_provider: impl ProvideCredentials + 'static,
+    ) -> Self {
+        self.credentials_provider = Some(SharedCredentialsProvider::new(credentials_provider));
+        self
+    }
+
+    /// The table name that the client reads and writes
+    pub fn table_name(mut self, table_name: impl Into<String>) -> Self {
+        self.table_name = Some(table_name.into());
+        self
+    }
+
+    /// The retry policy of the writes
+    /// If it is not provided, [RetryPolicy::default] is used.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// The concurrency of the fan-out writes, e.g. of the items of a backfill
+    /// If it is not provided, [Concurrency::default] is used.
+    pub fn concurrency(mut self, concurrency: Concurrency) -> Self {
+        self.concurrency = Some(concurrency);
+        self
+    }
+
+    /// The HTTP client that sends the requests, e.g. the one of the fake
+    pub fn http_client(mut self, http_client: SharedHttpClient) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    /// Create a client
+    /// If the table name is not provided, returns Err.
+    pub async fn build(self) -> Result<DynamoDbClient, Error> {
+        let Some(table_name) = self.table_name else {
+            return Err(Error::invalid_input("The table name is not provided"));
+        };
+
+        // the retry policy retries the calls, so the SDK doesn't
+        let mut loader =
+            aws_config::defaults(BehaviorVersion::latest()).retry_config(RetryConfig::disabled());
+
+        if let Some(endpoint_url) = self.endpoint_url {
+            loader = loader.endpoint_url(endpoint_url);
+        }
+        if let Some(region) = self.region {
+            loader = loader.region(region);
+        }
+        if let Some(credentials_provider) = self.credentials_provider {
+            loader = loader.credentials_provider(credentials_provider);
+        }
+        if let Some(http_client) = self.http_client {
+            loader = loader.http_client(http_client);
+        }
+
+        let config = loader.load().await;
+
+        Ok(DynamoDbClient {
+            client: aws_sdk_dynamodb::Client::new(&config),
+            table_name,
+            auditor: None,
+            retry: self.retry_policy.unwrap_or_default(),
+            concurrency: self.concurrency.unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use shared::error::ErrorKind;
+
+    #[tokio::test]
+    async fn test_build_without_table_name() {
+        // Act
+        let result = DynamoDbClientBuilder::new()
+            .endpoint_url("http://localhost:8000")
+            .build()
+            .await;
+
+        // Assert
+        assert!(matches!(result, Err(e) if e.kind() == ErrorKind::InvalidInput));
+    }
+
+    #[tokio::test]
+    async fn test_build() {
+        // Act
+        let result = DynamoDbClientBuilder::new()
+            .endpoint_url("http://localhost:8000")
+            .region("us-west-2")
+            .table_name("table")
+            .build()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(result.table_name, "table");
+    }
+}
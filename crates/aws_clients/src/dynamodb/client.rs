@@ -1,24 +1,74 @@
-use crate::dynamodb::entities::collection::{CollectionItem, LookUpItems};
-#[cfg(not(test))]
+use crate::audit::{self, AuditAction, Auditor};
+use crate::concurrency::Concurrency;
+use crate::dynamodb::entities::collection::{CollectionItem, Resolution};
 use crate::dynamodb::environment_values::{dynamodb_client, table_name};
+use crate::dynamodb::lookup::ROOT;
+use crate::dynamodb::protection::{LEGAL_HOLD, RETAIN_UNTIL};
+use crate::dynamodb::storage::{StorageState, STORAGE_STATE, VAULT};
+use crate::dynamodb::trash::TRASHED_AT;
+use crate::dynamodb::version::VERSION;
+use crate::error::aws_error;
+use crate::protection::Protection;
+use crate::retry::RetryPolicy;
 use aws_sdk_dynamodb::types::AttributeValue;
-use shared::traits::GetFileListTrait;
+use futures::TryFutureExt;
+use shared::error::Error;
+use shared::traits::{GetFileListTrait, Page};
+use std::collections::HashMap;
 use std::future::Future;
+use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
-
-pub struct DynamoDbClient<'a> {
-    pub(crate) client: &'static aws_sdk_dynamodb::Client,
-    pub(crate) table_name: &'a str,
+use time_file_name::file_datetime::{day_range, PathDateTime};
+
+/// The client for the table
+/// To point the client at a specific endpoint or table, use [DynamoDbClientBuilder](crate::dynamodb::builder::DynamoDbClientBuilder).
+pub struct DynamoDbClient {
+    pub(crate) client: aws_sdk_dynamodb::Client,
+    pub(crate) table_name: String,
+    pub(crate) auditor: Option<Auditor>,
+    /// retries the calls that are safe to send again
+    pub(crate) retry: RetryPolicy,
+    /// bounds the fan-out writes
+    pub(crate) concurrency: Concurrency,
 }
 
-impl DynamoDbClient<'_> {
-    #[allow(dead_code)]
-    #[cfg(not(test))]
-    pub async fn new() -> Self {
-        Self {
-            client: dynamodb_client().await,
-            table_name: table_name(),
-        }
+impl DynamoDbClient {
+    /// Create a client from the environment.
+    /// The table name is read from `TABLE_NAME`, and if it is not set, returns Err.
+    pub async fn from_env() -> Result<Self, Error> {
+        Ok(Self {
+            client: dynamodb_client().await.clone(),
+            table_name: table_name()?,
+            auditor: None,
+            retry: RetryPolicy::default(),
+            concurrency: Concurrency::default(),
+        })
+    }
+
+    /// The table name that this client reads and writes
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// Emit the audit records of the mutating calls of this client
+    pub fn with_auditor(mut self, auditor: Auditor) -> Self {
+        self.auditor = Some(auditor);
+        self
+    }
+
+    /// Retry the calls by the policy
+    /// The reads and the writes that give the same result when they are sent again are retried, e.g. the unconditional transactions.
+    /// The conditional writes and the counter updates are sent once, since a retried write that has been applied
+    /// fails by its own condition or is counted twice.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Bound the fan-out writes, e.g. the items of [put_collection_items](DynamoClientTrait::put_collection_items)
+    pub fn with_concurrency(mut self, concurrency: Concurrency) -> Self {
+        self.concurrency = concurrency;
+        self
     }
 }
 
@@ -29,7 +79,7 @@ pub trait DynamoClientTrait {
     fn put_collection_items(
         &self,
         collection: &Vec<CollectionItem>,
-    ) -> impl Future<Output = Result<(), String>> + Send;
+    ) -> impl Future<Output = Result<(), Error>> + Send;
     // put zipping time
     /// time is mill sec
     #[allow(dead_code)]
@@ -37,7 +87,7 @@ pub trait DynamoClientTrait {
         &self,
         key_name: &str,
         time: Option<u128>,
-    ) -> impl Future<Output = Result<(), String>> + Send;
+    ) -> impl Future<Output = Result<(), Error>> + Send;
     /// put unzipped item
     /// time is mill sec
     #[allow(dead_code)]
@@ -45,358 +95,651 @@ pub trait DynamoClientTrait {
         &self,
         key_name: &str,
         time: Option<u128>,
-    ) -> impl Future<Output = Result<(), String>> + Send;
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+    /// get a collection item by the key name
+    /// If there is no item or the item is in the trash, returns None
+    fn get_collection_item(
+        &self,
+        key_name: &str,
+    ) -> impl Future<Output = Result<Option<CollectionItem>, Error>> + Send;
+    /// get collection items that are recorded on the day
+    /// The items are sorted by the time, and the items in the trash are not included.
+    fn get_collection_items(
+        &self,
+        year: usize,
+        month: usize,
+        day: usize,
+    ) -> impl Future<Output = Result<Vec<CollectionItem>, Error>> + Send;
+    /// get the collection items of the key names, e.g. of a page of the objects
+    /// The items are in the order of the key names, and the missing items and the items in the trash are not included.
+    fn get_collection_items_of(
+        &self,
+        key_names: &[String],
+    ) -> impl Future<Output = Result<Vec<CollectionItem>, Error>> + Send;
 }
 
-impl GetFileListTrait for DynamoDbClient<'_> {
-    async fn get_years(&self) -> Result<Vec<String>, String> {
-        let mut years = self.get_date_list("root").await?;
-        years.sort();
-        Ok(years)
+impl GetFileListTrait for DynamoDbClient {
+    #[tracing::instrument(skip_all, fields(table = %self.table_name), err(level = "warn"))]
+    async fn get_years(&self) -> Result<Vec<String>, Error> {
+        self.get_lookup_entries(ROOT).await
     }
 
-    async fn get_months(&self, year: usize) -> Result<Vec<String>, String> {
-        let mut months = self.get_date_list(format!("{year}").as_str()).await?;
-        months.sort();
-        Ok(months)
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, year = %year),
+        err(level = "warn")
+    )]
+    async fn get_months(&self, year: usize) -> Result<Vec<String>, Error> {
+        self.get_lookup_entries(&format!("{year}")).await
     }
 
-    async fn get_days(&self, year: usize, month: usize) -> Result<Vec<String>, String> {
-        let mut days = self
-            .get_date_list(format!("{year}-{month}").as_str())
-            .await?;
-        days.sort();
-        Ok(days)
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, year = %year, month = %month),
+        err(level = "warn")
+    )]
+    async fn get_days(&self, year: usize, month: usize) -> Result<Vec<String>, Error> {
+        self.get_lookup_entries(&format!("{year}-{month}")).await
     }
 
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, year = %year, month = %month, day = %day),
+        err(level = "warn")
+    )]
     async fn get_objects(
         &self,
         year: usize,
         month: usize,
         day: usize,
-    ) -> Result<Vec<String>, String> {
-        self.get_date_list(format!("{year}-{month}-{day}").as_str())
+    ) -> Result<Vec<String>, Error> {
+        self.get_lookup_entries(&format!("{year}-{month}-{day}"))
             .await
     }
-}
-
-impl crate::dynamodb::client::DynamoClientTrait for DynamoDbClient<'_> {
-    async fn put_collection_items(&self, collections: &Vec<CollectionItem>) -> Result<(), String> {
-        let look_up_items = LookUpItems::new(collections)?;
-
-        // years
-        self.put_years(&look_up_items.years).await?;
 
-        // month
-        let month_results = futures::future::join_all(
-            look_up_items
-                .months
-                .iter()
-                .map(|month| self.put_months(month.0, month.1.as_ref())),
-        )
-        .await;
-
-        for result in month_results {
-            result?;
-        }
-
-        // days
-        let day_results = futures::future::join_all(
-            look_up_items
-                .days
-                .iter()
-                .map(|day| self.put_days(day.0, day.1, day.2.as_ref())),
-        )
-        .await;
-
-        for result in day_results {
-            result?;
-        }
-
-        // objects
-        let object_results = futures::future::join_all(
-            look_up_items
-                .objects
-                .iter()
-                .map(|day| self.put_objects(day.0, day.1, day.2, day.3.as_ref())),
-        )
-        .await;
-
-        for result in object_results {
-            result?;
-        }
-
-        let update_collections = collections
-            .iter()
-            .map(|collection| async { self.put_collection_item(collection).await });
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, year = %year, month = %month, day = %day, limit = %limit),
+        err(level = "warn")
+    )]
+    async fn get_objects_page(
+        &self,
+        year: usize,
+        month: usize,
+        day: usize,
+        limit: i32,
+        cursor: Option<String>,
+    ) -> Result<Page, Error> {
+        self.get_lookup_page(&format!("{year}-{month}-{day}"), Some(limit), cursor)
+            .await
+    }
+}
 
-        let results = futures::future::join_all(update_collections).await;
+impl crate::dynamodb::client::DynamoClientTrait for DynamoDbClient {
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, count = collections.len()),
+        err(level = "warn")
+    )]
+    async fn put_collection_items(&self, collections: &Vec<CollectionItem>) -> Result<(), Error> {
+        self.put_lookups(collections).await?;
+
+        let update_collections = collections.iter().map(|collection| async {
+            match self.put_collection_item(collection).await {
+                Ok(_) => Ok(()),
+                Err(e) => Err(Error::from(e)),
+            }
+        });
 
-        for result in results {
-            result?;
-        }
+        self.concurrency.run(update_collections).await?;
 
         Ok(())
     }
 
-    async fn put_unzipping_item(&self, key_name: &str, time: Option<u128>) -> Result<(), String> {
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name),
+        err(level = "warn")
+    )]
+    async fn put_unzipping_item(&self, key_name: &str, time: Option<u128>) -> Result<(), Error> {
         let now = get_now(time)?;
 
         let request = self
             .client
             .put_item()
-            .table_name(self.table_name)
+            .table_name(&self.table_name)
             .item("PK", AttributeValue::S("Unzipping".to_string()))
             .item("SK", AttributeValue::N(now.to_string()))
             .item("KeyName", AttributeValue::S(key_name.to_string()));
 
-        if let Err(e) = request.send().await {
-            return Err(e.to_string());
-        }
+        self.retry
+            .run("PutItem", || request.clone().send().map_err(aws_error))
+            .await?;
 
-        Ok(())
+        audit::record(&self.auditor, AuditAction::Unzipping, key_name, None, None).await
     }
 
-    async fn put_unzipped_item(&self, key_name: &str, time: Option<u128>) -> Result<(), String> {
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name),
+        err(level = "warn")
+    )]
+    async fn put_unzipped_item(&self, key_name: &str, time: Option<u128>) -> Result<(), Error> {
         let now = get_now(time)?;
 
         let request = self
             .client
             .put_item()
-            .table_name(self.table_name)
+            .table_name(&self.table_name)
             .item("PK", AttributeValue::S("Unzipped".to_string()))
             .item("SK", AttributeValue::N(now.to_string()))
             .item("KeyName", AttributeValue::S(key_name.to_string()));
 
-        if let Err(e) = request.send().await {
-            return Err(e.to_string());
-        }
+        self.retry
+            .run("PutItem", || request.clone().send().map_err(aws_error))
+            .await?;
 
-        Ok(())
+        audit::record(&self.auditor, AuditAction::Unzipped, key_name, None, None).await
     }
-}
 
-impl DynamoDbClient<'_> {
-    /// get date
-    /// doc<https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#date-lookup>
-    async fn get_date_list(&self, key: &str) -> Result<Vec<String>, String> {
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name),
+        err(level = "warn")
+    )]
+    async fn get_collection_item(&self, key_name: &str) -> Result<Option<CollectionItem>, Error> {
+        let path_date_time = PathDateTime::parse(key_name).map_err(Error::invalid_input)?;
+
         let request = self
             .client
             .get_item()
-            .table_name(self.table_name)
-            .key("PK", AttributeValue::S(key.to_string()))
-            .key("SK", AttributeValue::N("0".to_string()));
-
-        let saved_date = match request.send().await {
-            Ok(result) => match result.item {
-                None => return Ok(Vec::new()),
-                Some(item) => match item.get_key_value("SavedDate") {
-                    None => return Err("Saved date is not found".to_string()),
-                    Some(val) => match val.1.as_l() {
-                        Ok(attribute) => attribute.to_owned(),
-                        Err(_) => return Err("Casting to list is failed.".to_string()),
-                    },
-                },
-            },
-            Err(e) => return Err(e.to_string()),
-        };
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(path_date_time.year.to_string()))
+            .key(
+                "SK",
+                AttributeValue::N(path_date_time.unix_time.to_string()),
+            );
 
-        let mut date = Vec::new();
+        let output = self
+            .retry
+            .run("GetItem", || request.clone().send().map_err(aws_error))
+            .await?;
 
-        for attribute in saved_date {
-            match attribute.as_s() {
-                Ok(s) => date.push(s.to_owned()),
-                Err(_) => return Err("Invalid date is stored".to_string()),
+        match output.item {
+            Some(item) if !item.contains_key(TRASHED_AT) => {
+                Ok(Some(collection_item_from_attributes(&item)?))
             }
+            _ => Ok(None),
         }
-        Ok(date)
     }
 
-    /// put a collection item
-    async fn put_collection_item(&self, collection: &CollectionItem) -> Result<(), String> {
-        let request = self
-            .client
-            .put_item()
-            .table_name(self.table_name)
-            .item("PK", AttributeValue::S(collection.year.to_string()))
-            .item("SK", AttributeValue::N(collection.unix_time.to_string()))
-            .item("IsUnzipped", AttributeValue::Bool(collection.is_unzipped))
-            .item("Vault", AttributeValue::S(collection.vault.to_string()))
-            .item(
-                "KeyName",
-                AttributeValue::S(collection.key_name.to_string()),
-            );
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, year = %year, month = %month, day = %day),
+        err(level = "warn")
+    )]
+    async fn get_collection_items(
+        &self,
+        year: usize,
+        month: usize,
+        day: usize,
+    ) -> Result<Vec<CollectionItem>, Error> {
+        let (start, end) =
+            day_range(year as i32, month as u32, day as u32).map_err(Error::invalid_input)?;
+
+        let mut collections = Vec::new();
+        let mut exclusive_start_key = None;
+
+        loop {
+            let request = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk AND SK BETWEEN :start AND :end")
+                .expression_attribute_values(":pk", AttributeValue::S(year.to_string()))
+                .expression_attribute_values(":start", AttributeValue::N(start.to_string()))
+                .expression_attribute_values(":end", AttributeValue::N(end.to_string()))
+                .filter_expression("attribute_not_exists(#trashed_at)")
+                .expression_attribute_names("#trashed_at", TRASHED_AT)
+                .set_exclusive_start_key(exclusive_start_key);
+
+            let output = self
+                .retry
+                .run("Query", || request.clone().send().map_err(aws_error))
+                .await?;
+
+            for item in output.items() {
+                collections.push(collection_item_from_attributes(item)?);
+            }
 
-        if let Err(e) = request.send().await {
-            return Err(e.to_string());
+            match output.last_evaluated_key {
+                Some(key) => exclusive_start_key = Some(key),
+                None => break,
+            }
         }
 
-        Ok(())
+        Ok(collections)
     }
 
-    /// update years lookup
-    async fn put_years(&self, years: &Vec<String>) -> Result<(), String> {
-        let recorded_years = self.get_years().await?;
-
-        let mut concat_years = vec![&recorded_years[..], &years[..]].concat();
-        concat_years.sort_unstable();
-        concat_years.dedup();
-
-        // if the new vector contains new collections
-        if concat_years.len() == recorded_years.len() {
-            return Ok(());
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, count = key_names.len()),
+        err(level = "warn")
+    )]
+    async fn get_collection_items_of(
+        &self,
+        key_names: &[String],
+    ) -> Result<Vec<CollectionItem>, Error> {
+        let mut normalized_key_names = Vec::new();
+        let mut keys = Vec::new();
+        for key_name in key_names {
+            let path_date_time = PathDateTime::parse(key_name).map_err(Error::invalid_input)?;
+            let key = HashMap::from([
+                (
+                    "PK".to_string(),
+                    AttributeValue::S(path_date_time.year.to_string()),
+                ),
+                (
+                    "SK".to_string(),
+                    AttributeValue::N(path_date_time.unix_time.to_string()),
+                ),
+            ]);
+            // the batch get rejects the duplicated keys
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+            normalized_key_names.push(path_date_time.key_name());
         }
 
-        let result = self
-            .client
-            .put_item()
-            .table_name(self.table_name)
-            .item("PK", AttributeValue::S("root".to_string()))
-            .item("SK", AttributeValue::N("0".to_string()))
-            .item(
-                "SavedDate",
-                AttributeValue::L(
-                    concat_years
-                        .iter()
-                        .map(|el| AttributeValue::S(el.to_string()))
-                        .collect(),
-                ),
-            );
+        let mut collections = HashMap::new();
+        for item in self.batch_get_keys(keys).await? {
+            if item.contains_key(TRASHED_AT) {
+                continue;
+            }
+            let collection = collection_item_from_attributes(&item)?;
+            collections.insert(collection.key_name.to_string(), collection);
+        }
 
-        let result = result.send().await;
+        Ok(normalized_key_names
+            .iter()
+            .filter_map(|key_name| collections.remove(key_name))
+            .collect())
+    }
+}
 
-        match result {
-            Ok(_) => Ok(()),
-            Err(e) => return Err(e.to_string()),
+impl DynamoDbClient {
+    /// get all items in the partition
+    pub(crate) async fn query_partition(
+        &self,
+        key: &str,
+    ) -> Result<Vec<HashMap<String, AttributeValue>>, Error> {
+        let mut items = Vec::new();
+        let mut exclusive_start_key = None;
+
+        loop {
+            let request = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk")
+                .expression_attribute_values(":pk", AttributeValue::S(key.to_string()))
+                .set_exclusive_start_key(exclusive_start_key);
+
+            let output = self
+                .retry
+                .run("Query", || request.clone().send().map_err(aws_error))
+                .await?;
+
+            items.extend(output.items().iter().cloned());
+
+            match output.last_evaluated_key {
+                Some(key) => exclusive_start_key = Some(key),
+                None => break,
+            }
         }
+
+        Ok(items)
     }
+}
 
-    async fn put_months(&self, years: usize, months: &Vec<String>) -> Result<(), String> {
-        let recorded_months = self.get_months(years).await?;
+/// this is a helper function.
+/// if there is argument, this function returns it.
+/// If not, this function gets system time.
+pub(crate) fn get_now(time: Option<u128>) -> Result<u128, Error> {
+    match time {
+        Some(now) => Ok(now),
+        None => match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(now) => Ok(now.as_millis()),
+            Err(_) => Err(Error::internal("Failed to get current time")),
+        },
+    }
+}
 
-        let mut concat_months = vec![&recorded_months[..], &months[..]].concat();
-        concat_months.sort_unstable();
-        concat_months.dedup();
+/// The client request token that makes the replay of a transaction idempotent
+/// DynamoDB accepts up to 36 characters, and it remembers the token for 10 minutes.
+pub(crate) fn request_token() -> String {
+    format!("{:032x}", fastrand::u128(..))
+}
 
-        if concat_months.len() == recorded_months.len() {
-            return Ok(());
-        }
-        let result = self
-            .client
-            .put_item()
-            .table_name(self.table_name)
-            .item("PK", AttributeValue::S(format!("{years}")))
-            .item("SK", AttributeValue::N("0".to_string()))
-            .item(
-                "SavedDate",
-                AttributeValue::L(
-                    concat_months
-                        .iter()
-                        .map(|el| AttributeValue::S(el.to_string()))
-                        .collect(),
-                ),
-            );
+/// Convert the collection item to the DynamoDB item.
+/// The metadata is included only when it is set, and the updated time is set to now.
+pub(crate) fn collection_item_to_attributes(
+    collection: &CollectionItem,
+) -> Result<HashMap<String, AttributeValue>, Error> {
+    let mut item = HashMap::from([
+        (
+            "PK".to_string(),
+            AttributeValue::S(collection.year.to_string()),
+        ),
+        (
+            "SK".to_string(),
+            AttributeValue::N(collection.unix_time.to_string()),
+        ),
+        (
+            "IsUnzipped".to_string(),
+            AttributeValue::Bool(collection.is_unzipped),
+        ),
+        (
+            STORAGE_STATE.to_string(),
+            AttributeValue::S(StorageState::of(collection).to_string()),
+        ),
+        (
+            "KeyName".to_string(),
+            AttributeValue::S(collection.key_name.to_string()),
+        ),
+        (
+            "UpdatedAt".to_string(),
+            AttributeValue::N(get_now(None)?.to_string()),
+        ),
+        (
+            VERSION.to_string(),
+            AttributeValue::N(collection.version.to_string()),
+        ),
+    ]);
+
+    let number = |n: Option<i64>| n.map(|n| AttributeValue::N(n.to_string()));
+    let string = |s: &Option<String>| s.as_ref().map(|s| AttributeValue::S(s.to_string()));
+
+    let optional_attributes = [
+        ("Size", number(collection.size)),
+        ("Duration", number(collection.duration)),
+        (
+            "Width",
+            number(
+                collection
+                    .resolution
+                    .map(|resolution| resolution.width.into()),
+            ),
+        ),
+        (
+            "Height",
+            number(
+                collection
+                    .resolution
+                    .map(|resolution| resolution.height.into()),
+            ),
+        ),
+        ("ContentType", string(&collection.content_type)),
+        ("Checksum", string(&collection.checksum)),
+        ("Uploader", string(&collection.uploader)),
+        ("Device", string(&collection.device)),
+        ("ContentHash", string(&collection.content_hash)),
+        ("DuplicateOf", string(&collection.duplicate_of)),
+    ];
+
+    if let Some(retain_until) = collection.protection.retain_until {
+        item.insert(
+            RETAIN_UNTIL.to_string(),
+            AttributeValue::N(retain_until.to_string()),
+        );
+    }
+    // the item that is not held doesn't have the legal hold
+    if collection.protection.legal_hold {
+        item.insert(LEGAL_HOLD.to_string(), AttributeValue::Bool(true));
+    }
 
-        let result = result.send().await;
+    // the index key cannot be empty
+    if !collection.vault.is_empty() {
+        item.insert(
+            VAULT.to_string(),
+            AttributeValue::S(collection.vault.to_string()),
+        );
+    }
 
-        match result {
-            Ok(_) => Ok(()),
-            Err(e) => return Err(e.to_string()),
+    for (name, attribute) in optional_attributes {
+        if let Some(attribute) = attribute {
+            item.insert(name.to_string(), attribute);
         }
     }
 
-    async fn put_days(&self, years: usize, month: usize, days: &Vec<String>) -> Result<(), String> {
-        let recorded_days = self.get_days(years, month).await?;
+    Ok(item)
+}
 
-        let mut concat_days = vec![&recorded_days[..], &days[..]].concat();
-        concat_days.sort_unstable();
-        concat_days.dedup();
+/// Convert the DynamoDB item to the collection item.
+/// The metadata is optional, so the item that doesn't have it is still valid.
+pub(crate) fn collection_item_from_attributes(
+    item: &HashMap<String, AttributeValue>,
+) -> Result<CollectionItem, Error> {
+    let required = |name: &str| match item.get(name) {
+        Some(attribute) => Ok(attribute),
+        None => Err(Error::internal(format!(
+            "{name} is not found in the collection item"
+        ))),
+    };
+
+    let year = match required("PK")?.as_s() {
+        Ok(year) => year.to_owned(),
+        Err(_) => return Err(Error::internal("PK must be a string")),
+    };
+    let unix_time = match required("SK")?.as_n().map(|n| n.parse::<i64>()) {
+        Ok(Ok(unix_time)) => unix_time,
+        _ => return Err(Error::internal("SK must be a number")),
+    };
+    let is_unzipped = match required("IsUnzipped")?.as_bool() {
+        Ok(is_unzipped) => *is_unzipped,
+        Err(_) => return Err(Error::internal("IsUnzipped must be a boolean")),
+    };
+    // the item that is not archived doesn't have the vault
+    let vault = optional_string(item, VAULT)?.unwrap_or_default();
+    let key_name = match required("KeyName")?.as_s() {
+        Ok(key_name) => key_name.to_owned(),
+        Err(_) => return Err(Error::internal("KeyName must be a string")),
+    };
+
+    let resolution = match (
+        optional_number::<u32>(item, "Width")?,
+        optional_number::<u32>(item, "Height")?,
+    ) {
+        (Some(width), Some(height)) => Some(Resolution { width, height }),
+        _ => None,
+    };
+
+    let legal_hold = match item.get(LEGAL_HOLD).map(|legal_hold| legal_hold.as_bool()) {
+        None => false,
+        Some(Ok(legal_hold)) => *legal_hold,
+        Some(Err(_)) => return Err(Error::internal(format!("{LEGAL_HOLD} must be a boolean"))),
+    };
+
+    Ok(CollectionItem {
+        year,
+        unix_time,
+        is_unzipped,
+        vault,
+        key_name,
+        size: optional_number(item, "Size")?,
+        duration: optional_number(item, "Duration")?,
+        resolution,
+        content_type: optional_string(item, "ContentType")?,
+        checksum: optional_string(item, "Checksum")?,
+        uploader: optional_string(item, "Uploader")?,
+        device: optional_string(item, "Device")?,
+        content_hash: optional_string(item, "ContentHash")?,
+        duplicate_of: optional_string(item, "DuplicateOf")?,
+        protection: Protection {
+            retain_until: optional_number(item, RETAIN_UNTIL)?,
+            legal_hold,
+        },
+        version: optional_number(item, VERSION)?.unwrap_or_default(),
+    })
+}
 
-        if concat_days.len() == recorded_days.len() {
-            return Ok(());
-        }
-        let result = self
-            .client
-            .put_item()
-            .table_name(self.table_name)
-            .item("PK", AttributeValue::S(format!("{years}-{month}")))
-            .item("SK", AttributeValue::N("0".to_string()))
-            .item(
-                "SavedDate",
-                AttributeValue::L(
-                    concat_days
-                        .iter()
-                        .map(|el| AttributeValue::S(el.to_string()))
-                        .collect(),
-                ),
-            );
+/// read an optional string attribute
+fn optional_string(
+    item: &HashMap<String, AttributeValue>,
+    name: &str,
+) -> Result<Option<String>, Error> {
+    match item.get(name) {
+        None => Ok(None),
+        Some(attribute) => match attribute.as_s() {
+            Ok(value) => Ok(Some(value.to_owned())),
+            Err(_) => Err(Error::internal(format!("{name} must be a string"))),
+        },
+    }
+}
 
-        let result = result.send().await;
+/// read an optional number attribute
+fn optional_number<T: FromStr>(
+    item: &HashMap<String, AttributeValue>,
+    name: &str,
+) -> Result<Option<T>, Error> {
+    match item.get(name) {
+        None => Ok(None),
+        Some(attribute) => match attribute.as_n().map(|n| n.parse::<T>()) {
+            Ok(Ok(value)) => Ok(Some(value)),
+            _ => Err(Error::internal(format!("{name} must be a number"))),
+        },
+    }
+}
 
-        match result {
-            Ok(_) => Ok(()),
-            Err(e) => return Err(e.to_string()),
-        }
+#[cfg(test)]
+mod collection_item_tests {
+    use super::*;
+    use crate::dynamodb::entities::collection::Resolution;
+
+    #[tokio::test]
+    async fn test_get_collection_item_with_metadata() {
+        // Arrange
+        let table_name = "test_get_collection_item_with_metadata";
+        let client = DynamoDbClient::new(table_name).await;
+        let key_name = "1984/04/04/1984-04-04-12-34-50.MOV";
+        let collection = CollectionItem {
+            size: Some(1024),
+            duration: Some(3000),
+            resolution: Some(Resolution {
+                width: 1920,
+                height: 1080,
+            }),
+            content_type: Some("video/quicktime".to_string()),
+            checksum: Some("checksum".to_string()),
+            uploader: Some("uploader".to_string()),
+            device: Some("iPhone".to_string()),
+            content_hash: Some("hash".to_string()),
+            ..CollectionItem::dummy_object(key_name)
+        };
+        client
+            .put_collection_items(&vec![collection.clone()])
+            .await
+            .unwrap();
+
+        // Act
+        let result = client.get_collection_item(key_name).await.unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            Some(CollectionItem {
+                version: 1,
+                ..collection
+            })
+        );
     }
 
-    async fn put_objects(
-        &self,
-        years: usize,
-        month: usize,
-        day: usize,
-        objects: &Vec<String>,
-    ) -> Result<(), String> {
-        let recorded_months = self.get_objects(years, month, day).await?;
+    #[tokio::test]
+    async fn test_get_collection_item_without_metadata() {
+        // Arrange
+        let table_name = "test_get_collection_item_without_metadata";
+        let client = DynamoDbClient::new(table_name).await;
+        let key_name = "1984/04/04/1984-04-04-12-34-50.MOV";
+        client
+            .put_collection_items(&vec![CollectionItem::dummy_object(key_name)])
+            .await
+            .unwrap();
 
-        let mut concat_objects = vec![&recorded_months[..], &objects[..]].concat();
-        concat_objects.sort_unstable();
-        concat_objects.dedup();
+        // Act
+        let result = client.get_collection_item(key_name).await.unwrap().unwrap();
 
-        if concat_objects.len() == recorded_months.len() {
-            return Ok(());
-        }
+        // Assert
+        assert_eq!(result.size, None);
+        assert_eq!(result.resolution, None);
+        assert_eq!(result.key_name, key_name);
+    }
 
-        let result = self
-            .client
-            .put_item()
-            .table_name(self.table_name)
-            .item("PK", AttributeValue::S(format!("{years}-{month}-{day}")))
-            .item("SK", AttributeValue::N("0".to_string()))
-            .item(
-                "SavedDate",
-                AttributeValue::L(
-                    concat_objects
-                        .iter()
-                        .map(|el| AttributeValue::S(el.to_string()))
-                        .collect(),
-                ),
-            );
+    #[tokio::test]
+    async fn test_get_collection_items() {
+        // Arrange
+        let table_name = "test_get_collection_items";
+        let client = DynamoDbClient::new(table_name).await;
+        let collections = vec![
+            CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-50.MOV"),
+            CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-51.MOV"),
+            CollectionItem::dummy_object("1984/04/05/1984-04-05-12-34-50.MOV"),
+        ];
+        client.put_collection_items(&collections).await.unwrap();
 
-        let result = result.send().await;
+        // Act
+        let result = client.get_collection_items(1984, 4, 4).await.unwrap();
 
-        match result {
-            Ok(_) => Ok(()),
-            Err(e) => return Err(e.to_string()),
+        // Assert
+        assert_eq!(result.len(), 2);
+        for (saved, collection) in std::iter::zip(result, &collections[..2]) {
+            assert_eq!(
+                saved,
+                CollectionItem {
+                    version: 1,
+                    ..collection.clone()
+                }
+            );
         }
     }
-}
 
-/// this is a helper function.
-/// if there is argument, this function returns it.
-/// If not, this function gets system time.
-fn get_now(time: Option<u128>) -> Result<u128, String> {
-    match time {
-        Some(now) => Ok(now),
-        None => match SystemTime::now().duration_since(UNIX_EPOCH) {
-            Ok(now) => Ok(now.as_millis()),
-            Err(_) => Err("Failed to get current time".to_string()),
-        },
+    #[tokio::test]
+    async fn test_get_collection_items_of() {
+        // Arrange
+        let client = DynamoDbClient::new("test_get_collection_items_of").await;
+        let collections = vec![
+            CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-50.MOV"),
+            CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-51.MOV"),
+            CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-52.MOV"),
+        ];
+        client.put_collection_items(&collections).await.unwrap();
+        let key_names = [
+            "1984/04/04/1984-04-04-12-34-52.MOV",
+            "/1984/4/4/1984-4-4-12-34-50.MOV",
+            "1984/04/04/1984-04-04-12-34-53.MOV",
+        ]
+        .map(String::from);
+
+        // Act
+        let result = client.get_collection_items_of(&key_names).await.unwrap();
+
+        // Assert
+        assert_eq!(
+            result
+                .iter()
+                .map(|collection| collection.key_name.as_str())
+                .collect::<Vec<&str>>(),
+            [
+                "1984/04/04/1984-04-04-12-34-52.MOV",
+                "1984/04/04/1984-04-04-12-34-50.MOV"
+            ]
+        );
     }
 }
 
 #[cfg(test)]
 mod get_file_list_tests {
     use super::*;
+    use shared::error::ErrorKind;
 
     #[tokio::test]
     async fn test_get_years() {
@@ -489,6 +832,35 @@ mod get_file_list_tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_get_objects_page() {
+        // Arrange
+        let table_name = "test_get_objects_page";
+        let client = DynamoDbClient::new(table_name).await;
+        save_test_data(table_name).await;
+
+        // Act
+        let first = client.get_objects_page(1984, 4, 4, 1, None).await.unwrap();
+        let mut rest = Vec::new();
+        let mut cursor = first.next_cursor.clone();
+        while let Some(next_cursor) = cursor {
+            let page = client
+                .get_objects_page(1984, 4, 4, 1, Some(next_cursor))
+                .await
+                .unwrap();
+            rest.extend(page.items);
+            cursor = page.next_cursor;
+        }
+        let invalid = client
+            .get_objects_page(1984, 4, 4, 1, Some("cursor".to_string()))
+            .await;
+
+        // Assert
+        assert_eq!(first.items, ["1984/04/04/1984-04-04-12-34-50.MOV"]);
+        assert_eq!(rest, ["1984/04/04/1984-04-04-12-34-51.MOV"]);
+        assert_eq!(invalid.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
     #[tokio::test]
     async fn test_get_objects_with_no_data() {
         // Arrange
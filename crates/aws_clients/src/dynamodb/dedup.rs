@@ -0,0 +1,340 @@
+//! The deduplication by the content hash
+//! The content hash is the SHA-256 of the object, and the hash item points to the first item that has the content.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#content-hash>
+
+use crate::dynamodb::client::DynamoDbClient;
+use crate::dynamodb::entities::collection::CollectionItem;
+use crate::dynamodb::version::WriteError;
+use crate::error::aws_error;
+use aws_sdk_dynamodb::types::AttributeValue;
+use futures::TryFutureExt;
+use sha2::{Digest, Sha256};
+use shared::error::Error;
+use std::io::Read;
+
+/// The buffer size to read the content
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// What to do when the content already exists
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Nothing is saved
+    Skip,
+    /// The item is saved with the key name of the existing one, so the object can be removed.
+    Link,
+}
+
+/// The result of the ingestion
+#[derive(Debug, PartialEq)]
+pub enum IngestOutcome {
+    /// The item is saved as a new content
+    Stored,
+    /// The item is not saved because the content exists
+    Skipped { existing_key: String },
+    /// The item is saved as a link to the existing content
+    Linked { existing_key: String },
+}
+
+/// Calculate the content hash, which is the SHA-256 in lowercase hex
+///
+/// # Example
+/// ```rust
+/// # use aws_clients::dynamodb::dedup::content_hash;
+/// let hash = content_hash("abc".as_bytes()).unwrap();
+/// assert_eq!(hash, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+/// ```
+pub fn content_hash(mut reader: impl Read) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; BUFFER_SIZE];
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) => return Err(Error::internal(format!("Reading the content failed: {e}"))),
+        };
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+impl DynamoDbClient {
+    /// Find the key name of the item that has the content.
+    /// If the item has been purged, it is not considered as a duplicate.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, content_hash = %content_hash),
+        err(level = "warn")
+    )]
+    pub async fn find_duplicate(&self, content_hash: &str) -> Result<Option<String>, Error> {
+        let request = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(hash_key(content_hash)))
+            .key("SK", AttributeValue::N("0".to_string()));
+
+        let output = self
+            .retry
+            .run("GetItem", || request.clone().send().map_err(aws_error))
+            .await?;
+        let existing_key = match output.item {
+            Some(item) => match item.get("KeyName").map(|key_name| key_name.as_s()) {
+                Some(Ok(key_name)) => key_name.to_owned(),
+                _ => return Err(Error::internal("KeyName must be a string in the hash item")),
+            },
+            None => return Ok(None),
+        };
+
+        match self.collection_item_exists(&existing_key).await? {
+            true => Ok(Some(existing_key)),
+            false => Ok(None),
+        }
+    }
+
+    /// Save a new collection item unless the content already exists.
+    /// If the item doesn't have the content hash, it is saved without the check.
+    /// Saving the same key name again is not a duplicate, and the saved item is kept as it is, e.g. for a retry.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = %collection.key_name, policy = ?policy),
+        err(level = "warn")
+    )]
+    pub async fn ingest_collection_item(
+        &self,
+        collection: &CollectionItem,
+        policy: DuplicatePolicy,
+    ) -> Result<IngestOutcome, Error> {
+        let Some(content_hash) = &collection.content_hash else {
+            self.put_new_collection_item(collection).await?;
+            return Ok(IngestOutcome::Stored);
+        };
+
+        let Some(existing_key) = self
+            .claim_content_hash(content_hash, &collection.key_name)
+            .await?
+        else {
+            self.put_new_collection_item(collection).await?;
+            return Ok(IngestOutcome::Stored);
+        };
+
+        match policy {
+            DuplicatePolicy::Skip => Ok(IngestOutcome::Skipped { existing_key }),
+            DuplicatePolicy::Link => {
+                let linked = CollectionItem {
+                    duplicate_of: Some(existing_key.to_string()),
+                    ..collection.clone()
+                };
+                self.put_new_collection_item(&linked).await?;
+                Ok(IngestOutcome::Linked { existing_key })
+            }
+        }
+    }
+
+    /// Save the item with the date lookups.
+    /// If the item has already been saved, it is not overwritten.
+    async fn put_new_collection_item(&self, collection: &CollectionItem) -> Result<(), Error> {
+        self.put_lookups(std::slice::from_ref(collection)).await?;
+
+        match self.put_collection_item(collection).await {
+            Ok(_) | Err(WriteError::Conflict { expected: 0, .. }) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Record the key name as the owner of the content.
+    /// If another item owns the content, returns its key name.
+    /// The hash item of the purged item is taken over.
+    async fn claim_content_hash(
+        &self,
+        content_hash: &str,
+        key_name: &str,
+    ) -> Result<Option<String>, Error> {
+        if self.put_hash_item(content_hash, key_name, true).await? {
+            return Ok(None);
+        }
+
+        if let Some(existing_key) = self.find_duplicate(content_hash).await? {
+            return Ok(Some(existing_key));
+        }
+
+        self.put_hash_item(content_hash, key_name, false).await?;
+        Ok(None)
+    }
+
+    /// put the hash item, and returns false if it is not put
+    /// If `only_new` is true, the item is put only when there is no owner or the owner is the key name.
+    /// The replay of the applied put meets the condition, so it is retried.
+    async fn put_hash_item(
+        &self,
+        content_hash: &str,
+        key_name: &str,
+        only_new: bool,
+    ) -> Result<bool, Error> {
+        let mut request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(hash_key(content_hash)))
+            .item("SK", AttributeValue::N("0".to_string()))
+            .item("KeyName", AttributeValue::S(key_name.to_string()));
+
+        if only_new {
+            request = request
+                .condition_expression("attribute_not_exists(PK) OR KeyName = :key_name")
+                .expression_attribute_values(":key_name", AttributeValue::S(key_name.to_string()));
+        }
+
+        self.retry
+            .run("PutItem", || async {
+                match request.clone().send().await {
+                    Ok(_) => Ok(true),
+                    Err(e) => match e.as_service_error() {
+                        Some(service_error)
+                            if service_error.is_conditional_check_failed_exception() =>
+                        {
+                            Ok(false)
+                        }
+                        _ => Err(aws_error(e)),
+                    },
+                }
+            })
+            .await
+    }
+}
+
+/// The partition key of the hash item
+fn hash_key(content_hash: &str) -> String {
+    format!("Hash#{content_hash}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dynamodb::client::DynamoClientTrait;
+
+    const KEY_NAME: &str = "1984/04/04/1984-04-04-12-34-50.MOV";
+    const DUPLICATE_KEY_NAME: &str = "1984/04/05/1984-04-05-12-34-50.MOV";
+
+    fn hashed_object(key_name: &str) -> CollectionItem {
+        CollectionItem {
+            content_hash: Some(content_hash("video".as_bytes()).unwrap()),
+            ..CollectionItem::dummy_object(key_name)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ingest_new_content() {
+        // Arrange
+        let client = DynamoDbClient::new("test_ingest_new_content").await;
+
+        // Act
+        let result = client
+            .ingest_collection_item(&hashed_object(KEY_NAME), DuplicatePolicy::Skip)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(result, IngestOutcome::Stored);
+        assert_eq!(
+            client
+                .find_duplicate(&content_hash("video".as_bytes()).unwrap())
+                .await
+                .unwrap(),
+            Some(KEY_NAME.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ingest_duplicate_with_skip() {
+        // Arrange
+        let client = DynamoDbClient::new("test_ingest_duplicate_with_skip").await;
+        client
+            .ingest_collection_item(&hashed_object(KEY_NAME), DuplicatePolicy::Skip)
+            .await
+            .unwrap();
+
+        // Act
+        let result = client
+            .ingest_collection_item(&hashed_object(DUPLICATE_KEY_NAME), DuplicatePolicy::Skip)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            IngestOutcome::Skipped {
+                existing_key: KEY_NAME.to_string()
+            }
+        );
+        assert_eq!(
+            client
+                .get_collection_item(DUPLICATE_KEY_NAME)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ingest_duplicate_with_link() {
+        // Arrange
+        let client = DynamoDbClient::new("test_ingest_duplicate_with_link").await;
+        client
+            .ingest_collection_item(&hashed_object(KEY_NAME), DuplicatePolicy::Link)
+            .await
+            .unwrap();
+
+        // Act
+        let result = client
+            .ingest_collection_item(&hashed_object(DUPLICATE_KEY_NAME), DuplicatePolicy::Link)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            IngestOutcome::Linked {
+                existing_key: KEY_NAME.to_string()
+            }
+        );
+        let linked = client
+            .get_collection_item(DUPLICATE_KEY_NAME)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(linked.duplicate_of, Some(KEY_NAME.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_ingest_same_key_again() {
+        // Arrange
+        let client = DynamoDbClient::new("test_ingest_same_key_again").await;
+        client
+            .ingest_collection_item(&hashed_object(KEY_NAME), DuplicatePolicy::Skip)
+            .await
+            .unwrap();
+
+        // Act
+        let result = client
+            .ingest_collection_item(&hashed_object(KEY_NAME), DuplicatePolicy::Skip)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(result, IngestOutcome::Stored);
+    }
+
+    #[test]
+    fn test_content_hash_of_empty_content() {
+        // Act
+        let result = content_hash("".as_bytes()).unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
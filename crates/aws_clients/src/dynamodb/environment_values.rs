@@ -1,56 +1,53 @@
-use std::env::var;
-use std::sync::OnceLock;
+use crate::config::config;
+use aws_config::retry::RetryConfig;
+use aws_config::{BehaviorVersion, Region};
+use shared::error::Error;
 use tokio::sync::OnceCell;
 
+/// The client is shared by the clients created from the environment.
 static DYNAMODB_CLIENT: OnceCell<aws_sdk_dynamodb::Client> = OnceCell::const_new();
 
-#[cfg(not(test))]
-pub fn table_name() -> &'static str {
-    static TABLE_NAME: OnceLock<String> = OnceLock::new();
-    TABLE_NAME.get_or_init(|| var("TABLE_NAME").unwrap())
+/// read the table name from the configuration
+/// If it is not set, returns Err.
+pub(crate) fn table_name() -> Result<String, Error> {
+    Ok(config()?.table_name()?.to_string())
 }
 
 #[cfg(test)]
-fn dynamo_db_url() -> &'static str {
-    static HOST_NAME: OnceLock<String> = OnceLock::new();
-    HOST_NAME.get_or_init(|| {
-        if let Ok(host) = var("DYNAMO_HOST") {
-            format!("http://{host}")
-        } else {
-            "http://localhost:8000".to_string()
-        }
-    })
+pub(crate) fn dynamo_db_url() -> &'static str {
+    config()
+        .ok()
+        .and_then(|config| config.dynamodb_endpoint_url.as_deref())
+        .unwrap_or("http://localhost:8000")
 }
 
-/// The DynamoDB client
-#[allow(dead_code)] // TODO: fix
-#[cfg(all(not(test), feature = "db"))]
-pub async fn dynamodb_client() -> &'static aws_sdk_dynamodb::Client {
+/// The DynamoDB client that is configured by the environment
+/// The endpoint and the region of the configuration are used if they are set.
+/// The SDK doesn't retry, since [RetryPolicy](crate::retry::RetryPolicy) does.
+/// In the tests and with the `fake` feature, it sends the requests to [FakeAws::shared](crate::fake::FakeAws::shared).
+pub(crate) async fn dynamodb_client() -> &'static aws_sdk_dynamodb::Client {
     DYNAMODB_CLIENT
         .get_or_init(|| async {
-            let config = aws_config::load_from_env().await;
-            aws_sdk_dynamodb::Client::new(&config)
-        })
-        .await
-}
+            let mut loader = aws_config::defaults(BehaviorVersion::latest())
+                .retry_config(RetryConfig::disabled());
+            if let Ok(config) = config() {
+                if let Some(endpoint_url) = &config.dynamodb_endpoint_url {
+                    loader = loader.endpoint_url(endpoint_url);
+                }
+                if let Some(region) = &config.region {
+                    loader = loader.region(Region::new(region.to_string()));
+                }
+            }
+            #[cfg(any(test, feature = "fake"))]
+            {
+                loader = loader
+                    .credentials_provider(aws_sdk_dynamodb::config::Credentials::new(
+                        "key", "secret", None, None, "test",
+                    ))
+                    .http_client(crate::fake::FakeAws::shared().http_client());
+            }
 
-#[cfg(test)]
-/// test client
-/// when this function is called at the first time, the
-pub async fn dynamodb_client() -> &'static aws_sdk_dynamodb::Client {
-    use aws_config::BehaviorVersion;
-    use aws_config::Region;
-    use aws_sdk_dynamodb::config::Credentials;
-
-    DYNAMODB_CLIENT
-        .get_or_init(|| async {
-            let config = aws_config::defaults(BehaviorVersion::latest())
-                .endpoint_url(dynamo_db_url())
-                .region(Some(Region::new("us-west-2")))
-                .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
-                .load()
-                .await;
-            aws_sdk_dynamodb::Client::new(&config)
+            aws_sdk_dynamodb::Client::new(&loader.load().await)
         })
         .await
 }
@@ -0,0 +1,643 @@
+//! The date lookups
+//! Each entry of the lookups, a year, a month, a day, or an object, is an item in the partition of its parent.
+//! The lists are read by the paginated query, so they are not limited by the item size.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#date-lookup>
+
+use crate::dynamodb::client::{get_now, DynamoDbClient};
+use crate::dynamodb::entities::collection::CollectionItem;
+use crate::error::aws_error;
+use aws_sdk_dynamodb::operation::put_item::PutItemError;
+use aws_sdk_dynamodb::types::{
+    AttributeValue, DeleteRequest, PutRequest, ReturnValuesOnConditionCheckFailure, WriteRequest,
+};
+use futures::TryFutureExt;
+use shared::error::{Error, ErrorKind};
+use shared::traits::Page;
+use std::collections::{BTreeMap, HashMap};
+use time_file_name::file_datetime::PathDateTime;
+
+/// The prefix of the lookup partitions
+const LOOKUP_PREFIX: &str = "Lookup#";
+
+/// The parent of the years
+pub(crate) const ROOT: &str = "root";
+
+/// The attribute of the entry
+const ENTRY: &str = "Entry";
+
+/// The attribute of the list in the legacy layout
+const SAVED_DATE: &str = "SavedDate";
+
+/// An entry of the date lookups
+#[derive(Debug, Clone, PartialEq)]
+struct LookupEntry {
+    /// `root`, `{year}`, `{year}-{month}`, or `{year}-{month}-{day}`
+    parent: String,
+    /// The year, the month, the day, or the SK of the collection item of the object,
+    /// which is the epoch time in milliseconds
+    sort_key: i64,
+    entry: String,
+}
+
+impl LookupEntry {
+    /// The entries from the year to the object
+    /// The objects that have the same time, e.g. of the different extensions, have the same entry as their items do.
+    fn of(key_name: &str) -> Result<[LookupEntry; 4], Error> {
+        let time = PathDateTime::parse(key_name).map_err(Error::invalid_input)?;
+        let (year, month, day) = (time.year, time.month, time.day);
+
+        Ok([
+            LookupEntry {
+                parent: ROOT.to_string(),
+                sort_key: year as i64,
+                entry: year.to_string(),
+            },
+            LookupEntry {
+                parent: format!("{year}"),
+                sort_key: month as i64,
+                entry: month.to_string(),
+            },
+            LookupEntry {
+                parent: format!("{year}-{month}"),
+                sort_key: day as i64,
+                entry: day.to_string(),
+            },
+            LookupEntry {
+                parent: format!("{year}-{month}-{day}"),
+                sort_key: time.unix_time,
+                entry: time.key_name(),
+            },
+        ])
+    }
+
+    fn key(&self) -> HashMap<String, AttributeValue> {
+        HashMap::from([
+            (
+                "PK".to_string(),
+                AttributeValue::S(lookup_key(&self.parent)),
+            ),
+            (
+                "SK".to_string(),
+                AttributeValue::N(self.sort_key.to_string()),
+            ),
+        ])
+    }
+}
+
+/// The partition of the entries of the parent
+fn lookup_key(parent: &str) -> String {
+    format!("{LOOKUP_PREFIX}{parent}")
+}
+
+impl DynamoDbClient {
+    /// get the entries of the parent in the order of the time
+    /// The partition is read page by page.
+    pub(crate) async fn get_lookup_entries(&self, parent: &str) -> Result<Vec<String>, Error> {
+        let mut entries = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let page = self.get_lookup_page(parent, None, cursor).await?;
+            entries.extend(page.items);
+
+            match page.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => break,
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// get a page of the entries of the parent in the order of the time
+    /// If the limit is not given, the page is limited only by the size of the response.
+    /// The cursor is the SK of the last evaluated entry.
+    pub(crate) async fn get_lookup_page(
+        &self,
+        parent: &str,
+        limit: Option<i32>,
+        cursor: Option<String>,
+    ) -> Result<Page, Error> {
+        let exclusive_start_key = match cursor {
+            Some(cursor) => match cursor.parse::<i64>() {
+                Ok(sort_key) => Some(HashMap::from([
+                    ("PK".to_string(), AttributeValue::S(lookup_key(parent))),
+                    ("SK".to_string(), AttributeValue::N(sort_key.to_string())),
+                ])),
+                Err(_) => return Err(Error::invalid_input(format!("Invalid cursor: {cursor}"))),
+            },
+            None => None,
+        };
+
+        let request = self
+            .client
+            .query()
+            .table_name(&self.table_name)
+            .key_condition_expression("PK = :pk")
+            .projection_expression("#entry")
+            .expression_attribute_names("#entry", ENTRY)
+            .expression_attribute_values(":pk", AttributeValue::S(lookup_key(parent)))
+            .set_exclusive_start_key(exclusive_start_key)
+            .set_limit(limit);
+
+        let output = self
+            .retry
+            .run("Query", || request.clone().send().map_err(aws_error))
+            .await?;
+
+        let mut items = Vec::new();
+        for item in output.items() {
+            match item.get(ENTRY).map(|entry| entry.as_s()) {
+                Some(Ok(entry)) => items.push(entry.to_string()),
+                _ => return Err(Error::internal("Invalid lookup entry is stored")),
+            }
+        }
+
+        let next_cursor = match output
+            .last_evaluated_key()
+            .and_then(|key| key.get("SK"))
+            .map(|sk| sk.as_n())
+        {
+            Some(Ok(sk)) => Some(sk.to_string()),
+            Some(Err(_)) => return Err(Error::internal("The last evaluated key is invalid")),
+            None => None,
+        };
+
+        Ok(Page { items, next_cursor })
+    }
+
+    /// add the collections to the date lookups
+    /// The entries are idempotent, so the existing ones are just overwritten.
+    /// The collections that have the same time as another object, in the batch or in the lookups, are rejected,
+    /// since their items would overwrite each other.
+    pub(crate) async fn put_lookups(&self, collections: &[CollectionItem]) -> Result<(), Error> {
+        let mut key_names = HashMap::new();
+        for collection in collections {
+            let [.., object] = LookupEntry::of(&collection.key_name)?;
+            match key_names.insert(object.sort_key, object.entry.to_string()) {
+                Some(other) if other != object.entry => {
+                    return Err(Error::conflict(format!(
+                        "{other} and {} have the same time",
+                        object.entry
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        self.put_lookup_levels(
+            collections
+                .iter()
+                .map(|collection| collection.key_name.as_str()),
+        )
+        .await
+    }
+
+    /// put the entries of the key names level by level, from the objects to the years
+    /// A parent is written after its children, so if [remove_lookup](Self::remove_lookup) deletes it at the same time,
+    /// either the parent is written after the deletion, or the removal finds the children and restores it.
+    async fn put_lookup_levels<'a>(
+        &self,
+        key_names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), Error> {
+        let mut levels: [Vec<LookupEntry>; 4] = Default::default();
+        for key_name in key_names {
+            for (level, entry) in LookupEntry::of(key_name)?.into_iter().enumerate() {
+                levels[level].push(entry);
+            }
+        }
+        let [years, months, days, objects] = levels;
+
+        let updated_at = get_now(None)?.to_string();
+        self.concurrency
+            .run(
+                objects
+                    .into_iter()
+                    .map(|object| self.put_object_entry(object, &updated_at)),
+            )
+            .await?;
+
+        for entries in [days, months, years] {
+            self.put_lookup_entries(entries).await?;
+        }
+
+        Ok(())
+    }
+
+    /// put the entry of the object unless another object of the same time has it
+    /// The entry is not written by the batch, since the batch write can't have the condition.
+    async fn put_object_entry(&self, object: LookupEntry, updated_at: &str) -> Result<(), Error> {
+        let mut item = object.key();
+        item.insert(
+            ENTRY.to_string(),
+            AttributeValue::S(object.entry.to_string()),
+        );
+        item.insert(
+            "UpdatedAt".to_string(),
+            AttributeValue::N(updated_at.to_string()),
+        );
+
+        let request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(item))
+            .condition_expression("attribute_not_exists(SK) OR #entry = :entry")
+            .expression_attribute_names("#entry", ENTRY)
+            .expression_attribute_values(":entry", AttributeValue::S(object.entry.to_string()))
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld);
+
+        // retried, since the replay of the applied put has the same entry and passes the condition
+        self.retry
+            .run("PutItem", || async {
+                match request.clone().send().await {
+                    Ok(_) => Ok(()),
+                    Err(e) => match e.as_service_error() {
+                        Some(PutItemError::ConditionalCheckFailedException(exception)) => {
+                            let other = exception
+                                .item()
+                                .and_then(|item| item.get(ENTRY))
+                                .and_then(|entry| entry.as_s().ok())
+                                .map_or("another object", |entry| entry.as_str());
+                            Err(Error::conflict(format!(
+                                "{} has the same time as {other}",
+                                object.entry
+                            )))
+                        }
+                        _ => Err(aws_error(e)),
+                    },
+                }
+            })
+            .await
+    }
+
+    /// remove the key from the date lookups
+    /// If the day has no object anymore, the day is also removed from the month, and so on.
+    /// A parent whose children are put while it is deleted is restored, see [put_lookup_levels](Self::put_lookup_levels).
+    pub(crate) async fn remove_lookup(&self, key_name: &str) -> Result<(), Error> {
+        // the partition of the children of the entry, which is None for the object
+        let mut children: Option<&str> = None;
+
+        for entry in LookupEntry::of(key_name)?.iter().rev() {
+            let request = self
+                .client
+                .delete_item()
+                .table_name(&self.table_name)
+                .set_key(Some(entry.key()));
+
+            self.retry
+                .run("DeleteItem", || request.clone().send().map_err(aws_error))
+                .await?;
+
+            if let Some(children) = children {
+                if self.has_lookup_entries(children).await? {
+                    return self.put_lookup_entries([entry.clone()]).await;
+                }
+            }
+
+            if self.has_lookup_entries(&entry.parent).await? {
+                return Ok(());
+            }
+            children = Some(&entry.parent);
+        }
+
+        Ok(())
+    }
+
+    /// The read is strongly consistent, so the entries that are put just before are found.
+    async fn has_lookup_entries(&self, parent: &str) -> Result<bool, Error> {
+        let request = self
+            .client
+            .query()
+            .table_name(&self.table_name)
+            .key_condition_expression("PK = :pk")
+            .expression_attribute_values(":pk", AttributeValue::S(lookup_key(parent)))
+            .consistent_read(true)
+            .limit(1);
+
+        self.retry
+            .run("Query", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|output| output.count > 0)
+    }
+
+    async fn put_lookup_entries(
+        &self,
+        entries: impl IntoIterator<Item = LookupEntry>,
+    ) -> Result<(), Error> {
+        let updated_at = get_now(None)?.to_string();
+
+        // the batch write rejects the duplicated keys
+        let entries = entries
+            .into_iter()
+            .map(|entry| ((entry.parent.to_string(), entry.sort_key), entry))
+            .collect::<BTreeMap<(String, i64), LookupEntry>>();
+
+        let mut write_requests = Vec::new();
+        for entry in entries.into_values() {
+            let mut item = entry.key();
+            item.insert(ENTRY.to_string(), AttributeValue::S(entry.entry));
+            item.insert(
+                "UpdatedAt".to_string(),
+                AttributeValue::N(updated_at.to_string()),
+            );
+
+            match PutRequest::builder().set_item(Some(item)).build() {
+                Ok(put_request) => {
+                    write_requests.push(WriteRequest::builder().put_request(put_request).build())
+                }
+                Err(e) => return Err(Error::from_source(ErrorKind::Internal, e)),
+            }
+        }
+
+        self.batch_write(write_requests).await
+    }
+
+    /// get the list in the legacy layout
+    /// The list was a single item, `{PK: parent, SK: 0, SavedDate: [...]}`.
+    pub(crate) async fn get_legacy_date_list(&self, parent: &str) -> Result<Vec<String>, Error> {
+        let request = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(parent.to_string()))
+            .key("SK", AttributeValue::N("0".to_string()));
+
+        let result = self
+            .retry
+            .run("GetItem", || request.clone().send().map_err(aws_error))
+            .await?;
+        let saved_date = match result.item {
+            None => return Ok(Vec::new()),
+            Some(item) => match item.get(SAVED_DATE) {
+                None => return Err(Error::internal("Saved date is not found")),
+                Some(val) => match val.as_l() {
+                    Ok(attribute) => attribute.to_owned(),
+                    Err(_) => return Err(Error::internal("Casting to list is failed.")),
+                },
+            },
+        };
+
+        let mut date = Vec::new();
+
+        for attribute in saved_date {
+            match attribute.as_s() {
+                Ok(s) => date.push(s.to_owned()),
+                Err(_) => return Err(Error::internal("Invalid date is stored")),
+            }
+        }
+        Ok(date)
+    }
+
+    /// put the list in the legacy layout
+    /// The list that is already saved is replaced by the provided one.
+    pub(crate) async fn put_legacy_date_list(
+        &self,
+        parent: &str,
+        list: &[String],
+    ) -> Result<(), Error> {
+        let request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(parent.to_string()))
+            .item("SK", AttributeValue::N("0".to_string()))
+            .item("UpdatedAt", AttributeValue::N(get_now(None)?.to_string()))
+            .item(
+                SAVED_DATE,
+                AttributeValue::L(
+                    list.iter()
+                        .map(|el| AttributeValue::S(el.to_string()))
+                        .collect(),
+                ),
+            );
+
+        self.retry
+            .run("PutItem", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|_| ())
+    }
+
+    /// Move the lists in the legacy layout to the lookup entries.
+    /// The entries are made from the objects, and the legacy lists are deleted after that.
+    pub(crate) async fn migrate_legacy_lookups(&self) -> Result<(), Error> {
+        let mut objects = Vec::new();
+        let mut legacy_parents = Vec::new();
+
+        let years = self.get_legacy_date_list(ROOT).await?;
+        legacy_parents.push(ROOT.to_string());
+
+        for year in years {
+            let months = self.get_legacy_date_list(&year).await?;
+            legacy_parents.push(year.to_string());
+
+            for month in months {
+                let year_month = format!("{year}-{month}");
+                let days = self.get_legacy_date_list(&year_month).await?;
+                legacy_parents.push(year_month.to_string());
+
+                for day in days {
+                    let year_month_day = format!("{year_month}-{day}");
+                    objects.extend(self.get_legacy_date_list(&year_month_day).await?);
+                    legacy_parents.push(year_month_day);
+                }
+            }
+        }
+
+        self.put_lookup_levels(objects.iter().map(|object| object.as_str()))
+            .await?;
+
+        let mut write_requests = Vec::new();
+        for parent in legacy_parents {
+            let delete_request = DeleteRequest::builder()
+                .key("PK", AttributeValue::S(parent))
+                .key("SK", AttributeValue::N("0".to_string()))
+                .build();
+            match delete_request {
+                Ok(delete_request) => write_requests.push(
+                    WriteRequest::builder()
+                        .delete_request(delete_request)
+                        .build(),
+                ),
+                Err(e) => return Err(Error::from_source(ErrorKind::Internal, e)),
+            }
+        }
+
+        self.batch_write(write_requests).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dynamodb::client::DynamoClientTrait;
+    use shared::traits::GetFileListTrait;
+
+    #[tokio::test]
+    async fn test_get_lookup_entries_over_pages() {
+        // Arrange
+        let client = DynamoDbClient::new("test_get_lookup_entries_over_pages").await;
+        // more than a batch write
+        let collections = (0..60)
+            .map(|second| {
+                CollectionItem::dummy_object(&format!(
+                    "1984/04/04/1984-04-04-12-{:02}-{:02}.MOV",
+                    second / 60,
+                    second % 60
+                ))
+            })
+            .collect::<Vec<CollectionItem>>();
+        client.put_collection_items(&collections).await.unwrap();
+
+        // Act
+        let result = client.get_objects(1984, 4, 4).await.unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 60);
+        assert_eq!(result[0], "1984/04/04/1984-04-04-12-00-00.MOV");
+        assert_eq!(result[59], "1984/04/04/1984-04-04-12-00-59.MOV");
+    }
+
+    #[tokio::test]
+    async fn test_remove_lookup() {
+        // Arrange
+        let client = DynamoDbClient::new("test_remove_lookup").await;
+        let collections = vec![
+            CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-50.MOV"),
+            CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-51.MOV"),
+            CollectionItem::dummy_object("1985/04/04/1985-04-04-12-34-50.MOV"),
+        ];
+        client.put_collection_items(&collections).await.unwrap();
+
+        // Act
+        client
+            .remove_lookup("1984/04/04/1984-04-04-12-34-50.MOV")
+            .await
+            .unwrap();
+        let after_first = client.get_objects(1984, 4, 4).await.unwrap();
+        client
+            .remove_lookup("1984/04/04/1984-04-04-12-34-51.MOV")
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(after_first, ["1984/04/04/1984-04-04-12-34-51.MOV"]);
+        assert!(client.get_objects(1984, 4, 4).await.unwrap().is_empty());
+        assert!(client.get_days(1984, 4).await.unwrap().is_empty());
+        assert!(client.get_months(1984).await.unwrap().is_empty());
+        assert_eq!(client.get_years().await.unwrap(), ["1985"]);
+    }
+
+    #[tokio::test]
+    async fn test_put_lookups_of_the_same_time() {
+        // Arrange
+        let client = DynamoDbClient::new("test_put_lookups_of_the_same_time").await;
+        let collections = vec![
+            CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-50.MOV"),
+            CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-50.JPG"),
+        ];
+
+        // Act
+        let same_key = client
+            .put_lookups(&[collections[0].clone(), collections[0].clone()])
+            .await;
+        let same_time = client.put_lookups(&collections).await;
+
+        // Assert
+        assert!(same_key.is_ok());
+        assert_eq!(same_time.unwrap_err().kind(), ErrorKind::Conflict);
+        assert_eq!(
+            client.get_objects(1984, 4, 4).await.unwrap(),
+            ["1984/04/04/1984-04-04-12-34-50.MOV"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_put_collection_items_of_the_same_time() {
+        // Arrange
+        let client = DynamoDbClient::new("test_put_collection_items_of_the_same_time").await;
+        let first = "1984/04/04/1984-04-04-12-34-50.MOV";
+        let second = "1984/04/04/1984-04-04-12-34-50.MP4";
+        client
+            .put_collection_items(&vec![CollectionItem::dummy_object(first)])
+            .await
+            .unwrap();
+
+        // Act
+        let result = client
+            .put_collection_items(&vec![CollectionItem::dummy_object(second)])
+            .await;
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Conflict);
+        assert_eq!(client.get_objects(1984, 4, 4).await.unwrap(), [first]);
+        let saved = client.get_collection_item(first).await.unwrap().unwrap();
+        assert_eq!(saved.key_name, first);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_legacy_lookups() {
+        // Arrange
+        let client = DynamoDbClient::new("test_migrate_legacy_lookups").await;
+        for (parent, list) in [
+            ("root", vec!["1984"]),
+            ("1984", vec!["4"]),
+            ("1984-4", vec!["4", "5"]),
+            ("1984-4-4", vec!["1984/04/04/1984-04-04-12-34-50.MOV"]),
+            ("1984-4-5", vec!["/1984/4/5/1984-4-5-12-34-50.MOV"]),
+        ] {
+            let list = list.iter().map(|el| el.to_string()).collect::<Vec<_>>();
+            client.put_legacy_date_list(parent, &list).await.unwrap();
+        }
+
+        // Act
+        client.migrate_legacy_lookups().await.unwrap();
+
+        // Assert
+        assert_eq!(client.get_years().await.unwrap(), ["1984"]);
+        assert_eq!(client.get_days(1984, 4).await.unwrap(), ["4", "5"]);
+        assert_eq!(
+            client.get_objects(1984, 4, 5).await.unwrap(),
+            ["1984/04/05/1984-04-05-12-34-50.MOV"]
+        );
+        assert!(client
+            .get_legacy_date_list("root")
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(client
+            .get_legacy_date_list("1984-4-4")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_lookup_entries_of_key_name() {
+        // Arrange
+        let key_name = "/1984/4/4/1984-4-4-12-34-50.MOV";
+
+        // Act
+        let result = LookupEntry::of(key_name).unwrap();
+
+        // Assert
+        let parents = result
+            .iter()
+            .map(|entry| entry.parent.as_str())
+            .collect::<Vec<&str>>();
+        let entries = result
+            .iter()
+            .map(|entry| entry.entry.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(parents, ["root", "1984", "1984-4", "1984-4-4"]);
+        assert_eq!(
+            entries,
+            ["1984", "4", "4", "1984/04/04/1984-04-04-12-34-50.MOV"]
+        );
+        assert_eq!(result[0].sort_key, 1984);
+        assert_eq!(
+            result[3].sort_key,
+            PathDateTime::parse(key_name).unwrap().unix_time
+        );
+    }
+}
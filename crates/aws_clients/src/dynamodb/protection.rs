@@ -0,0 +1,285 @@
+//! The retention and the legal hold of the collection items
+//! A protected item cannot be trashed or moved to another vault, so it is never purged.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#protection>
+
+use crate::audit::{self, AuditAction};
+use crate::dynamodb::attribute_json::item_to_json;
+use crate::dynamodb::client::{collection_item_from_attributes, get_now, DynamoDbClient};
+use crate::dynamodb::entities::collection::CollectionItem;
+use crate::dynamodb::trash::TRASHED_AT;
+use crate::dynamodb::version::VERSION;
+use crate::error::aws_service_error;
+use aws_sdk_dynamodb::operation::update_item::builders::UpdateItemFluentBuilder;
+use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue, ReturnValuesOnConditionCheckFailure};
+use shared::error::Error;
+use time_file_name::file_datetime::PathDateTime;
+
+/// The attribute of the retention (epoch time in ms)
+pub(crate) const RETAIN_UNTIL: &str = "RetainUntil";
+
+/// The attribute of the legal hold
+/// The item that is not held doesn't have it.
+pub(crate) const LEGAL_HOLD: &str = "LegalHold";
+
+/// The condition that the item is not protected at `:now`
+/// The names and the values are added by [with_unprotected_values].
+pub(crate) const UNPROTECTED_CONDITION: &str =
+    "(attribute_not_exists(#retain_until) OR #retain_until <= :now) AND attribute_not_exists(#legal_hold)";
+
+/// Add the names and the values of [UNPROTECTED_CONDITION] to the request
+pub(crate) fn with_unprotected_values(
+    request: UpdateItemFluentBuilder,
+    now: u128,
+) -> UpdateItemFluentBuilder {
+    request
+        .expression_attribute_names("#retain_until", RETAIN_UNTIL)
+        .expression_attribute_names("#legal_hold", LEGAL_HOLD)
+        .expression_attribute_values(":now", AttributeValue::N(now.to_string()))
+}
+
+impl DynamoDbClient {
+    /// Retain the collection item until the time.
+    /// The retention can be extended, but it cannot be shortened.
+    /// The item in the trash cannot be retained.
+    /// retain_until is mill sec
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name, retain_until = %retain_until),
+        err(level = "warn")
+    )]
+    pub async fn set_retention(
+        &self,
+        key_name: &str,
+        retain_until: u128,
+    ) -> Result<CollectionItem, Error> {
+        self.update_protection(
+            key_name,
+            Some(AttributeValue::N(retain_until.to_string())),
+            RETAIN_UNTIL,
+            Some("attribute_not_exists(#protection) OR #protection <= :protection"),
+        )
+        .await
+    }
+
+    /// Place or remove the legal hold of the collection item.
+    /// The item in the trash cannot be held.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name, legal_hold = %legal_hold),
+        err(level = "warn")
+    )]
+    pub async fn set_legal_hold(
+        &self,
+        key_name: &str,
+        legal_hold: bool,
+    ) -> Result<CollectionItem, Error> {
+        let value = match legal_hold {
+            true => Some(AttributeValue::Bool(true)),
+            false => None,
+        };
+
+        self.update_protection(key_name, value, LEGAL_HOLD, None)
+            .await
+    }
+
+    /// Set the attribute if the value is given, otherwise remove it.
+    /// The extra condition can refer to the attribute as `#protection` and the value as `:protection`.
+    async fn update_protection(
+        &self,
+        key_name: &str,
+        value: Option<AttributeValue>,
+        attribute: &str,
+        extra_condition: Option<&str>,
+    ) -> Result<CollectionItem, Error> {
+        let path_date_time = PathDateTime::parse(key_name).map_err(Error::invalid_input)?;
+
+        let update_expression = match value {
+            Some(_) => "SET UpdatedAt = :updated_at, #protection = :protection ADD #version :one",
+            None => "SET UpdatedAt = :updated_at REMOVE #protection ADD #version :one",
+        };
+        let condition = match extra_condition {
+            Some(extra_condition) => format!(
+                "attribute_exists(PK) AND attribute_not_exists(#trashed_at) AND ({extra_condition})"
+            ),
+            None => "attribute_exists(PK) AND attribute_not_exists(#trashed_at)".to_string(),
+        };
+
+        let request = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(path_date_time.year.to_string()))
+            .key(
+                "SK",
+                AttributeValue::N(path_date_time.unix_time.to_string()),
+            )
+            .update_expression(update_expression)
+            .condition_expression(condition)
+            .expression_attribute_names("#protection", attribute)
+            .expression_attribute_names("#trashed_at", TRASHED_AT)
+            .expression_attribute_names("#version", VERSION)
+            .expression_attribute_values(
+                ":updated_at",
+                AttributeValue::N(get_now(None)?.to_string()),
+            )
+            .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+            .return_values(ReturnValue::AllNew)
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld);
+        let request = match value {
+            Some(value) => request.expression_attribute_values(":protection", value),
+            None => request,
+        };
+
+        // not retried, since the replay of the applied update counts the version twice
+        let attributes = match request.send().await {
+            Ok(output) => match output.attributes {
+                Some(attributes) => attributes,
+                None => return Err(Error::internal("The protected item is not returned")),
+            },
+            Err(e) => {
+                return Err(match e.into_service_error() {
+                    UpdateItemError::ConditionalCheckFailedException(exception) => {
+                        match exception.item {
+                            Some(item) if !item.contains_key(TRASHED_AT) => Error::conflict(
+                                format!("The {attribute} of {key_name} cannot be shortened"),
+                            ),
+                            _ => {
+                                Error::not_found(format!("{key_name} is not found or in the trash"))
+                            }
+                        }
+                    }
+                    service_error => aws_service_error(service_error),
+                })
+            }
+        };
+        let collection = collection_item_from_attributes(&attributes)?;
+
+        audit::record(
+            &self.auditor,
+            AuditAction::Protect,
+            &collection.key_name,
+            None,
+            Some(item_to_json(&attributes)?.to_string()),
+        )
+        .await?;
+
+        Ok(collection)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dynamodb::client::DynamoClientTrait;
+    use crate::dynamodb::version::CollectionUpdate;
+
+    const KEY_NAME: &str = "1984/04/04/1984-04-04-12-34-50.MOV";
+
+    async fn save_test_data(client: &DynamoDbClient) {
+        client
+            .put_collection_items(&vec![CollectionItem::dummy_object(KEY_NAME)])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retained_item_cannot_be_trashed() {
+        // Arrange
+        let client = DynamoDbClient::new("test_retained_item_cannot_be_trashed").await;
+        save_test_data(&client).await;
+        client.set_retention(KEY_NAME, 2_000).await.unwrap();
+
+        // Act
+        let retained = client
+            .trash_collection_item(KEY_NAME, "user", Some(1_999))
+            .await;
+        let expired = client
+            .trash_collection_item(KEY_NAME, "user", Some(2_000))
+            .await;
+
+        // Assert
+        assert_eq!(
+            retained,
+            Err(Error::conflict(format!(
+                "{KEY_NAME} is retained until 2000"
+            )))
+        );
+        assert!(expired.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_retention_cannot_be_shortened() {
+        // Arrange
+        let client = DynamoDbClient::new("test_retention_cannot_be_shortened").await;
+        save_test_data(&client).await;
+        client.set_retention(KEY_NAME, 2_000).await.unwrap();
+
+        // Act
+        let extended = client.set_retention(KEY_NAME, 3_000).await;
+        let shortened = client.set_retention(KEY_NAME, 1_000).await;
+
+        // Assert
+        assert_eq!(extended.unwrap().protection.retain_until, Some(3_000));
+        assert!(shortened.is_err());
+        let saved = client.get_collection_item(KEY_NAME).await.unwrap().unwrap();
+        assert_eq!(saved.protection.retain_until, Some(3_000));
+    }
+
+    #[tokio::test]
+    async fn test_held_item_cannot_be_trashed_or_moved() {
+        // Arrange
+        let client = DynamoDbClient::new("test_held_item_cannot_be_trashed_or_moved").await;
+        save_test_data(&client).await;
+        let held = client.set_legal_hold(KEY_NAME, true).await.unwrap();
+        let move_to_another_vault = CollectionUpdate {
+            vault: Some("another".to_string()),
+            ..CollectionUpdate::default()
+        };
+
+        // Act
+        let trashed = client.trash_collection_item(KEY_NAME, "user", None).await;
+        let moved = client
+            .update_collection_item(KEY_NAME, &move_to_another_vault, None)
+            .await;
+        client.set_legal_hold(KEY_NAME, false).await.unwrap();
+        let moved_after_release = client
+            .update_collection_item(KEY_NAME, &move_to_another_vault, None)
+            .await;
+
+        // Assert
+        assert!(held.protection.legal_hold);
+        assert_eq!(
+            trashed,
+            Err(Error::conflict(format!(
+                "{KEY_NAME} is under the legal hold"
+            )))
+        );
+        assert!(moved.is_err());
+        let moved_after_release = moved_after_release.unwrap();
+        assert_eq!(moved_after_release.vault, "another");
+        assert!(!moved_after_release.protection.legal_hold);
+    }
+
+    #[tokio::test]
+    async fn test_trashed_item_cannot_be_protected() {
+        // Arrange
+        let client = DynamoDbClient::new("test_trashed_item_cannot_be_protected").await;
+        save_test_data(&client).await;
+        client
+            .trash_collection_item(KEY_NAME, "user", None)
+            .await
+            .unwrap();
+
+        // Act
+        let result = client.set_legal_hold(KEY_NAME, true).await;
+
+        // Assert
+        assert_eq!(
+            result,
+            Err(Error::not_found(format!(
+                "{KEY_NAME} is not found or in the trash"
+            )))
+        );
+    }
+}
@@ -0,0 +1,683 @@
+//! The table layout and the schema migrations
+//! The table and the global secondary indexes are created from the definition in this module.
+//! The applied migration version is recorded in the table.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#schema>
+
+use crate::dynamodb::client::DynamoDbClient;
+use crate::dynamodb::lookup::ROOT;
+use crate::dynamodb::storage::{storage_indexes, StorageState, STORAGE_STATE, VAULT};
+use crate::dynamodb::trash::TTL_ATTRIBUTE;
+use crate::dynamodb::upload_session::upload_session_indexes;
+use crate::error::aws_error;
+use aws_sdk_dynamodb::types::{
+    AttributeDefinition, AttributeValue, BillingMode, CreateGlobalSecondaryIndexAction,
+    GlobalSecondaryIndex, GlobalSecondaryIndexUpdate, IndexStatus, KeySchemaElement, KeyType,
+    Projection, ProjectionType, ScalarAttributeType, TableDescription, TableStatus,
+    TimeToLiveSpecification, TimeToLiveStatus,
+};
+use futures::future::BoxFuture;
+use futures::TryFutureExt;
+use shared::error::{Error, ErrorKind};
+use std::time::Duration;
+use time_file_name::file_datetime::PathDateTime;
+
+/// The partition key of the schema version item
+const SCHEMA_KEY: &str = "Schema";
+
+/// The interval to check if the table is active
+const WAITING_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The maximum count to check if the table is active
+const WAITING_MAX_ATTEMPTS: usize = 240;
+
+/// The definition of a global secondary index
+/// All attributes are projected.
+pub struct IndexDefinition {
+    pub name: &'static str,
+    pub partition_key: (&'static str, ScalarAttributeType),
+    pub sort_key: (&'static str, ScalarAttributeType),
+}
+
+/// The global secondary indexes of the table
+pub fn global_secondary_indexes() -> Vec<IndexDefinition> {
+    let mut indexes = storage_indexes();
+    indexes.extend(upload_session_indexes());
+    indexes
+}
+
+/// A migration of the table
+/// The migrations are applied in the order of the version.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub apply: fn(&DynamoDbClient) -> BoxFuture<'_, Result<(), Error>>,
+}
+
+/// The migrations of the table
+/// A new migration must be appended with the next version.
+pub fn migrations() -> Vec<Migration> {
+    vec![
+        Migration {
+            version: 1,
+            description: "Re-key the key names to the zero-padded ones",
+            apply: |client| Box::pin(zero_padded_key_names(client)),
+        },
+        Migration {
+            version: 2,
+            description: "Compute the statistics of the saved items",
+            apply: |client| Box::pin(compute_stats(client)),
+        },
+        Migration {
+            version: 3,
+            description: "Index the storage states of the saved items",
+            apply: |client| Box::pin(index_storage_states(client)),
+        },
+        Migration {
+            version: 4,
+            description: "Split the date lookup lists into the entries",
+            apply: |client| Box::pin(client.migrate_legacy_lookups()),
+        },
+        Migration {
+            version: 5,
+            description: "Index the pending upload sessions",
+            apply: |client| Box::pin(client.index_pending_upload_sessions()),
+        },
+    ]
+}
+
+impl DynamoDbClient {
+    /// Create the table and apply the migrations.
+    /// This function can be called many times.
+    /// Returns the schema version.
+    #[tracing::instrument(skip_all, fields(table = %self.table_name), err(level = "warn"))]
+    pub async fn bootstrap(&self) -> Result<u32, Error> {
+        self.ensure_table().await?;
+        self.migrate().await
+    }
+
+    /// Create the table and the global secondary indexes if they don't exist, and enable the TTL.
+    /// This function waits until the table becomes active.
+    #[tracing::instrument(skip_all, fields(table = %self.table_name), err(level = "warn"))]
+    pub async fn ensure_table(&self) -> Result<(), Error> {
+        match self.describe_table().await? {
+            None => self.create_table_from_definition().await?,
+            Some(table) => self.create_missing_indexes(&table).await?,
+        }
+
+        self.wait_for_active().await?;
+        self.enable_time_to_live().await
+    }
+
+    /// Apply the migrations that have not been applied yet.
+    /// Returns the schema version.
+    #[tracing::instrument(skip_all, fields(table = %self.table_name), err(level = "warn"))]
+    pub async fn migrate(&self) -> Result<u32, Error> {
+        self.migrate_with(&migrations()).await
+    }
+
+    /// Apply the provided migrations that have not been applied yet.
+    /// The migrations must be ordered by the version.
+    /// Returns the schema version.
+    #[tracing::instrument(skip_all, fields(table = %self.table_name), err(level = "warn"))]
+    pub async fn migrate_with(&self, migrations: &[Migration]) -> Result<u32, Error> {
+        if migrations
+            .windows(2)
+            .any(|pair| pair[0].version >= pair[1].version)
+        {
+            return Err(Error::internal(
+                "The migrations are not ordered by the version",
+            ));
+        }
+
+        let mut current_version = self.schema_version().await?;
+
+        for migration in migrations {
+            if migration.version <= current_version {
+                continue;
+            }
+
+            if let Err(e) = (migration.apply)(self).await {
+                return Err(Error::internal(format!(
+                    "Migration {} ({}) failed: {}",
+                    migration.version, migration.description, e
+                )));
+            }
+
+            self.put_schema_version(migration.version).await?;
+            current_version = migration.version;
+        }
+
+        Ok(current_version)
+    }
+
+    /// Get the schema version that is recorded in the table.
+    /// If nothing is recorded, returns 0.
+    #[tracing::instrument(skip_all, fields(table = %self.table_name), err(level = "warn"))]
+    pub async fn schema_version(&self) -> Result<u32, Error> {
+        let request = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(SCHEMA_KEY.to_string()))
+            .key("SK", AttributeValue::N("0".to_string()));
+
+        let output = self
+            .retry
+            .run("GetItem", || request.clone().send().map_err(aws_error))
+            .await?;
+        let Some(item) = output.item else {
+            return Ok(0);
+        };
+
+        match item.get("Version").map(|version| version.as_n()) {
+            Some(Ok(version)) => match version.parse::<u32>() {
+                Ok(version) => Ok(version),
+                Err(_) => Err(Error::internal("Invalid schema version is recorded")),
+            },
+            _ => Err(Error::internal("Schema version is not found")),
+        }
+    }
+
+    /// Record the schema version
+    async fn put_schema_version(&self, version: u32) -> Result<(), Error> {
+        let request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(SCHEMA_KEY.to_string()))
+            .item("SK", AttributeValue::N("0".to_string()))
+            .item("Version", AttributeValue::N(version.to_string()));
+
+        self.retry
+            .run("PutItem", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|_| ())
+    }
+
+    /// Describe the table
+    /// If there is no table, returns None.
+    pub(crate) async fn describe_table(&self) -> Result<Option<TableDescription>, Error> {
+        let request = self.client.describe_table().table_name(&self.table_name);
+
+        // the missing table is not an error here, so it is found before the error is classified
+        self.retry
+            .run("DescribeTable", || async {
+                match request.clone().send().await {
+                    Ok(output) => Ok(output.table),
+                    Err(e) => match e.as_service_error() {
+                        Some(service_error) if service_error.is_resource_not_found_exception() => {
+                            Ok(None)
+                        }
+                        _ => Err(aws_error(e)),
+                    },
+                }
+            })
+            .await
+    }
+
+    async fn create_table_from_definition(&self) -> Result<(), Error> {
+        let mut request = self
+            .client
+            .create_table()
+            .table_name(&self.table_name)
+            .billing_mode(BillingMode::PayPerRequest)
+            .key_schema(key_schema_element("PK", KeyType::Hash)?)
+            .key_schema(key_schema_element("SK", KeyType::Range)?);
+
+        for attribute_definition in attribute_definitions(&global_secondary_indexes())? {
+            request = request.attribute_definitions(attribute_definition);
+        }
+
+        for index in global_secondary_indexes() {
+            request = request.global_secondary_indexes(
+                GlobalSecondaryIndex::builder()
+                    .index_name(index.name)
+                    .key_schema(key_schema_element(index.partition_key.0, KeyType::Hash)?)
+                    .key_schema(key_schema_element(index.sort_key.0, KeyType::Range)?)
+                    .projection(all_projection())
+                    .build()
+                    .map_err(|e| Error::from_source(ErrorKind::Internal, e))?,
+            );
+        }
+
+        // not retried, since the replay of the applied creation fails as the table is in use
+        match request.send().await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(aws_error(e)),
+        }
+    }
+
+    /// Create the global secondary indexes that the table doesn't have.
+    /// An index is created one by one because DynamoDB doesn't accept multiple creations at once.
+    async fn create_missing_indexes(&self, table: &TableDescription) -> Result<(), Error> {
+        let existing_indexes = table
+            .global_secondary_indexes()
+            .iter()
+            .filter_map(|index| index.index_name())
+            .collect::<Vec<&str>>();
+
+        for index in global_secondary_indexes() {
+            if existing_indexes.contains(&index.name) {
+                continue;
+            }
+
+            self.wait_for_active().await?;
+
+            let create_action = CreateGlobalSecondaryIndexAction::builder()
+                .index_name(index.name)
+                .key_schema(key_schema_element(index.partition_key.0, KeyType::Hash)?)
+                .key_schema(key_schema_element(index.sort_key.0, KeyType::Range)?)
+                .projection(all_projection())
+                .build()
+                .map_err(|e| Error::from_source(ErrorKind::Internal, e))?;
+
+            let mut request = self
+                .client
+                .update_table()
+                .table_name(&self.table_name)
+                .global_secondary_index_updates(
+                    GlobalSecondaryIndexUpdate::builder()
+                        .create(create_action)
+                        .build(),
+                );
+
+            for attribute_definition in attribute_definitions(&[index])? {
+                request = request.attribute_definitions(attribute_definition);
+            }
+
+            // not retried, since the replay of the applied update fails as the index exists
+            if let Err(e) = request.send().await {
+                return Err(aws_error(e));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Enable the TTL on [TTL_ATTRIBUTE] if it is not enabled yet
+    async fn enable_time_to_live(&self) -> Result<(), Error> {
+        let request = self
+            .client
+            .describe_time_to_live()
+            .table_name(&self.table_name);
+        let description = self
+            .retry
+            .run("DescribeTimeToLive", || {
+                request.clone().send().map_err(aws_error)
+            })
+            .await?
+            .time_to_live_description;
+
+        if let Some(description) = description {
+            if matches!(
+                description.time_to_live_status(),
+                Some(TimeToLiveStatus::Enabled | TimeToLiveStatus::Enabling)
+            ) {
+                return Ok(());
+            }
+        }
+
+        let specification = TimeToLiveSpecification::builder()
+            .attribute_name(TTL_ATTRIBUTE)
+            .enabled(true)
+            .build()
+            .map_err(|e| Error::from_source(ErrorKind::Internal, e))?;
+
+        let request = self
+            .client
+            .update_time_to_live()
+            .table_name(&self.table_name)
+            .time_to_live_specification(specification);
+
+        // not retried, since the replay of the applied update fails as the TTL is enabled
+        match request.send().await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(aws_error(e)),
+        }
+    }
+
+    /// Wait until the table and the indexes become active
+    async fn wait_for_active(&self) -> Result<(), Error> {
+        for _ in 0..WAITING_MAX_ATTEMPTS {
+            if let Some(table) = self.describe_table().await? {
+                let is_table_active = table.table_status() == Some(&TableStatus::Active);
+                let are_indexes_active = table
+                    .global_secondary_indexes()
+                    .iter()
+                    .all(|index| index.index_status() == Some(&IndexStatus::Active));
+
+                if is_table_active && are_indexes_active {
+                    return Ok(());
+                }
+            }
+
+            tokio::time::sleep(WAITING_INTERVAL).await;
+        }
+
+        Err(Error::internal(format!(
+            "The table {} didn't become active",
+            self.table_name
+        )))
+    }
+}
+
+/// The attribute definitions of the table keys and the index keys
+fn attribute_definitions(indexes: &[IndexDefinition]) -> Result<Vec<AttributeDefinition>, Error> {
+    let mut attributes = vec![
+        ("PK", ScalarAttributeType::S),
+        ("SK", ScalarAttributeType::N),
+    ];
+
+    for index in indexes {
+        for (name, attribute_type) in [&index.partition_key, &index.sort_key] {
+            if attributes.iter().all(|(defined, _)| defined != name) {
+                attributes.push((*name, attribute_type.clone()));
+            }
+        }
+    }
+
+    attributes
+        .into_iter()
+        .map(|(name, attribute_type)| {
+            AttributeDefinition::builder()
+                .attribute_name(name)
+                .attribute_type(attribute_type)
+                .build()
+                .map_err(|e| Error::from_source(ErrorKind::Internal, e))
+        })
+        .collect()
+}
+
+fn key_schema_element(name: &str, key_type: KeyType) -> Result<KeySchemaElement, Error> {
+    KeySchemaElement::builder()
+        .attribute_name(name)
+        .key_type(key_type)
+        .build()
+        .map_err(|e| Error::from_source(ErrorKind::Internal, e))
+}
+
+fn all_projection() -> Projection {
+    Projection::builder()
+        .projection_type(ProjectionType::All)
+        .build()
+}
+
+/// Migration 1
+/// The key names are re-keyed to the zero-padded ones that follow the bucket convention.
+/// Both the collection items and the object lookups are updated.
+/// The migrations before the version 4 read the lookups in the legacy layout.
+async fn zero_padded_key_names(client: &DynamoDbClient) -> Result<(), Error> {
+    for year in client.get_legacy_date_list(ROOT).await? {
+        // collections
+        for item in client.query_partition(&year).await? {
+            let Some(Ok(key_name)) = item.get("KeyName").map(|key_name| key_name.as_s()) else {
+                // the date lookup item doesn't have a key name
+                continue;
+            };
+
+            let padded_key_name = PathDateTime::parse(key_name)
+                .map_err(Error::invalid_input)?
+                .key_name();
+
+            if &padded_key_name == key_name {
+                continue;
+            }
+
+            let (Some(pk), Some(sk)) = (item.get("PK"), item.get("SK")) else {
+                return Err(Error::internal(
+                    "The key of the collection item is not found",
+                ));
+            };
+
+            let request = client
+                .client
+                .update_item()
+                .table_name(&client.table_name)
+                .key("PK", pk.clone())
+                .key("SK", sk.clone())
+                .update_expression("SET KeyName = :key_name")
+                .expression_attribute_values(":key_name", AttributeValue::S(padded_key_name));
+
+            client
+                .retry
+                .run("UpdateItem", || request.clone().send().map_err(aws_error))
+                .await?;
+        }
+
+        // object lookups
+        for month in client.get_legacy_date_list(&year).await? {
+            for day in client
+                .get_legacy_date_list(&format!("{year}-{month}"))
+                .await?
+            {
+                let key = format!("{year}-{month}-{day}");
+                let objects = client.get_legacy_date_list(&key).await?;
+
+                let mut padded_objects = Vec::new();
+                for object in &objects {
+                    padded_objects.push(
+                        PathDateTime::parse(object)
+                            .map_err(Error::invalid_input)?
+                            .key_name(),
+                    );
+                }
+                padded_objects.sort_unstable();
+                padded_objects.dedup();
+
+                if padded_objects != objects {
+                    client.put_legacy_date_list(&key, &padded_objects).await?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Migration 2
+/// The statistics are computed from the collection items that were saved before the statistics existed.
+async fn compute_stats(client: &DynamoDbClient) -> Result<(), Error> {
+    for year in client.get_legacy_date_list(ROOT).await? {
+        let Ok(year) = year.parse::<i32>() else {
+            return Err(Error::internal(format!("Invalid year is recorded: {year}")));
+        };
+        client.recompute_stats(year).await?;
+    }
+
+    Ok(())
+}
+
+/// Migration 3
+/// The storage state is set to the collection items that were saved before the storage indexes existed.
+/// The empty vault is removed, because it cannot be an index key.
+async fn index_storage_states(client: &DynamoDbClient) -> Result<(), Error> {
+    for year in client.get_legacy_date_list(ROOT).await? {
+        for item in client.query_partition(&year).await? {
+            // the date lookup item doesn't have a key name
+            if !item.contains_key("KeyName") || item.contains_key(STORAGE_STATE) {
+                continue;
+            }
+
+            let (Some(pk), Some(sk)) = (item.get("PK"), item.get("SK")) else {
+                return Err(Error::internal(
+                    "The key of the collection item is not found",
+                ));
+            };
+
+            let vault = match item.get(VAULT).map(|vault| vault.as_s()) {
+                Some(Ok(vault)) => vault.as_str(),
+                _ => "",
+            };
+            let (update_expression, storage_state) = match vault.is_empty() {
+                true => (
+                    "SET #storage_state = :storage_state REMOVE #vault",
+                    StorageState::Standard,
+                ),
+                false => (
+                    "SET #storage_state = :storage_state",
+                    StorageState::Archived,
+                ),
+            };
+
+            let mut request = client
+                .client
+                .update_item()
+                .table_name(&client.table_name)
+                .key("PK", pk.clone())
+                .key("SK", sk.clone())
+                .update_expression(update_expression)
+                .expression_attribute_names("#storage_state", STORAGE_STATE)
+                .expression_attribute_values(
+                    ":storage_state",
+                    AttributeValue::S(storage_state.to_string()),
+                );
+            if vault.is_empty() {
+                request = request.expression_attribute_names("#vault", VAULT);
+            }
+
+            client
+                .retry
+                .run("UpdateItem", || request.clone().send().map_err(aws_error))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dynamodb::client::DynamoClientTrait;
+    use crate::dynamodb::entities::collection::CollectionItem;
+    use shared::traits::GetFileListTrait;
+
+    #[tokio::test]
+    async fn test_ensure_table_is_idempotent() {
+        // Arrange
+        let client = DynamoDbClient::new("test_ensure_table_is_idempotent").await;
+
+        // Act
+        let first = client.ensure_table().await;
+        let second = client.ensure_table().await;
+
+        // Assert
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        assert!(client.describe_table().await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_records_schema_version() {
+        // Arrange
+        let client = DynamoDbClient::new("test_bootstrap_records_schema_version").await;
+
+        // Act
+        let version = client.bootstrap().await.unwrap();
+
+        // Assert
+        let latest_version = migrations().last().unwrap().version;
+        assert_eq!(version, latest_version);
+        assert_eq!(client.schema_version().await.unwrap(), latest_version);
+        assert_eq!(client.migrate().await.unwrap(), latest_version);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_with_unordered_migrations() {
+        // Arrange
+        let client = DynamoDbClient::new("test_migrate_with_unordered_migrations").await;
+        let unordered = vec![
+            Migration {
+                version: 2,
+                description: "second",
+                apply: |_| Box::pin(async { Ok(()) }),
+            },
+            Migration {
+                version: 1,
+                description: "first",
+                apply: |_| Box::pin(async { Ok(()) }),
+            },
+        ];
+
+        // Act
+        let result = client.migrate_with(&unordered).await;
+
+        // Assert
+        assert!(result.is_err());
+        assert_eq!(client.schema_version().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_zero_padded_key_names() {
+        // Arrange
+        let client = DynamoDbClient::new("test_zero_padded_key_names").await;
+        let collections = vec![
+            CollectionItem::dummy_object("/1984/4/4/1984-4-4-12-34-50.MOV"),
+            CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-51.MOV"),
+        ];
+        client.put_collection_items(&collections).await.unwrap();
+        // the lookups of the legacy layout
+        for (parent, list) in [
+            ("root", vec!["1984"]),
+            ("1984", vec!["4"]),
+            ("1984-4", vec!["4"]),
+            (
+                "1984-4-4",
+                vec![
+                    "/1984/4/4/1984-4-4-12-34-50.MOV",
+                    "1984/04/04/1984-04-04-12-34-51.MOV",
+                ],
+            ),
+        ] {
+            let list = list.iter().map(|el| el.to_string()).collect::<Vec<_>>();
+            client.put_legacy_date_list(parent, &list).await.unwrap();
+        }
+
+        // Act
+        client.migrate_with(&migrations()[..1]).await.unwrap();
+
+        // Assert
+        assert_eq!(
+            client.get_legacy_date_list("1984-4-4").await.unwrap(),
+            [
+                "1984/04/04/1984-04-04-12-34-50.MOV",
+                "1984/04/04/1984-04-04-12-34-51.MOV"
+            ]
+        );
+        let collection = client
+            .get_collection_item("1984/04/04/1984-04-04-12-34-50.MOV")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(collection.key_name, "1984/04/04/1984-04-04-12-34-50.MOV");
+    }
+
+    #[tokio::test]
+    async fn test_split_date_lookups() {
+        // Arrange
+        let client = DynamoDbClient::new("test_split_date_lookups").await;
+        for (parent, list) in [
+            ("root", vec!["1984"]),
+            ("1984", vec!["4"]),
+            ("1984-4", vec!["4"]),
+            ("1984-4-4", vec!["/1984/4/4/1984-4-4-12-34-50.MOV"]),
+        ] {
+            let list = list.iter().map(|el| el.to_string()).collect::<Vec<_>>();
+            client.put_legacy_date_list(parent, &list).await.unwrap();
+        }
+
+        // Act
+        client.migrate().await.unwrap();
+
+        // Assert
+        assert_eq!(
+            client.get_objects(1984, 4, 4).await.unwrap(),
+            ["1984/04/04/1984-04-04-12-34-50.MOV"]
+        );
+        assert!(client
+            .get_legacy_date_list("root")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,457 @@
+//! The statistics per year, month, and day
+//! The counters are updated by `ADD` when a collection item is saved or trashed, so they are never read-modified-written.
+//! All statistics of a year are in the same partition, so they can be read by one query.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#statistics>
+
+use crate::dynamodb::client::{collection_item_from_attributes, DynamoDbClient};
+use crate::dynamodb::entities::collection::CollectionItem;
+use crate::dynamodb::trash::TRASHED_AT;
+use crate::error::aws_error;
+use aws_sdk_dynamodb::types::AttributeValue;
+use futures::TryFutureExt;
+use shared::error::{Error, ErrorKind};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::{Add, Neg};
+use time_file_name::file_datetime::PathDateTime;
+
+/// The extensions of the videos
+const VIDEO_EXTENSIONS: [&str; 7] = ["mov", "mp4", "m4v", "avi", "mkv", "webm", "3gp"];
+
+/// The extensions of the images
+const IMAGE_EXTENSIONS: [&str; 7] = ["jpg", "jpeg", "png", "heic", "heif", "gif", "webp"];
+
+/// The kind of the media
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaKind {
+    Video,
+    Image,
+    Other,
+}
+
+impl MediaKind {
+    /// The kind is decided by the content type, and if it is not set, by the extension.
+    pub fn of(collection: &CollectionItem) -> Self {
+        if let Some(content_type) = &collection.content_type {
+            if content_type.starts_with("video/") {
+                return Self::Video;
+            }
+            if content_type.starts_with("image/") {
+                return Self::Image;
+            }
+        }
+
+        let extension = match collection.key_name.rsplit_once('.') {
+            Some((_, extension)) => extension.to_lowercase(),
+            None => return Self::Other,
+        };
+
+        if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+            Self::Video
+        } else if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            Self::Image
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// The statistics of a period
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PeriodStats {
+    pub object_count: i64,
+    /// The sum of the size in bytes. The item whose size is unknown is counted as zero.
+    pub total_bytes: i64,
+    pub video_count: i64,
+    pub image_count: i64,
+    pub other_count: i64,
+}
+
+impl PeriodStats {
+    /// The statistics of a collection item
+    pub fn of(collection: &CollectionItem) -> Self {
+        let kind = MediaKind::of(collection);
+
+        Self {
+            object_count: 1,
+            total_bytes: collection.size.unwrap_or(0),
+            video_count: i64::from(kind == MediaKind::Video),
+            image_count: i64::from(kind == MediaKind::Image),
+            other_count: i64::from(kind == MediaKind::Other),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Add for PeriodStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            object_count: self.object_count + other.object_count,
+            total_bytes: self.total_bytes + other.total_bytes,
+            video_count: self.video_count + other.video_count,
+            image_count: self.image_count + other.image_count,
+            other_count: self.other_count + other.other_count,
+        }
+    }
+}
+
+impl Neg for PeriodStats {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            object_count: -self.object_count,
+            total_bytes: -self.total_bytes,
+            video_count: -self.video_count,
+            image_count: -self.image_count,
+            other_count: -self.other_count,
+        }
+    }
+}
+
+/// The statistics of a year
+#[derive(Debug, Default, PartialEq)]
+pub struct YearStats {
+    pub total: PeriodStats,
+    /// month -> statistics
+    pub months: BTreeMap<u32, PeriodStats>,
+    /// (month, day) -> statistics
+    pub days: BTreeMap<(u32, u32), PeriodStats>,
+}
+
+impl DynamoDbClient {
+    /// get the statistics of the year by one query
+    /// If there is no item in the year, all statistics are zero.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, year = %year),
+        err(level = "warn")
+    )]
+    pub async fn get_year_stats(&self, year: i32) -> Result<YearStats, Error> {
+        let mut year_stats = YearStats::default();
+
+        for item in self.query_partition(&stats_key(year)).await? {
+            let sk = match item.get("SK").map(|sk| sk.as_n()) {
+                Some(Ok(sk)) => sk
+                    .parse::<u32>()
+                    .map_err(|e| Error::from_source(ErrorKind::Internal, e))?,
+                _ => {
+                    return Err(Error::internal(
+                        "SK must be a number in the statistics item",
+                    ))
+                }
+            };
+            let stats = period_stats_from_attributes(&item)?;
+
+            match (sk / 100, sk % 100) {
+                (0, _) => year_stats.total = stats,
+                (month, 0) => {
+                    year_stats.months.insert(month, stats);
+                }
+                (month, day) => {
+                    year_stats.days.insert((month, day), stats);
+                }
+            }
+        }
+
+        Ok(year_stats)
+    }
+
+    /// Rebuild the statistics of the year from the collection items.
+    /// The items in the trash are not counted.
+    /// This should not run with the ingestion at the same time, otherwise the counters can drift.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, year = %year),
+        err(level = "warn")
+    )]
+    pub async fn recompute_stats(&self, year: i32) -> Result<YearStats, Error> {
+        let mut year_stats = YearStats::default();
+
+        for item in self.query_partition(&year.to_string()).await? {
+            // the date lookup item doesn't have a key name
+            if !item.contains_key("KeyName") || item.contains_key(TRASHED_AT) {
+                continue;
+            }
+
+            let collection = collection_item_from_attributes(&item)?;
+            let time = PathDateTime::parse(&collection.key_name).map_err(Error::invalid_input)?;
+            let stats = PeriodStats::of(&collection);
+
+            year_stats.total = year_stats.total + stats;
+            let month = year_stats.months.entry(time.month).or_default();
+            *month = *month + stats;
+            let day = year_stats.days.entry((time.month, time.day)).or_default();
+            *day = *day + stats;
+        }
+
+        for item in self.query_partition(&stats_key(year)).await? {
+            let (Some(pk), Some(sk)) = (item.get("PK"), item.get("SK")) else {
+                return Err(Error::internal(
+                    "The key of the statistics item is not found",
+                ));
+            };
+
+            let request = self
+                .client
+                .delete_item()
+                .table_name(&self.table_name)
+                .key("PK", pk.clone())
+                .key("SK", sk.clone());
+
+            self.retry
+                .run("DeleteItem", || request.clone().send().map_err(aws_error))
+                .await?;
+        }
+
+        let mut periods = vec![(0, year_stats.total)];
+        periods.extend(
+            year_stats
+                .months
+                .iter()
+                .map(|(month, stats)| (month * 100, *stats)),
+        );
+        periods.extend(
+            year_stats
+                .days
+                .iter()
+                .map(|((month, day), stats)| (month * 100 + day, *stats)),
+        );
+
+        for (sk, stats) in periods {
+            if !stats.is_zero() {
+                self.add_period_stats(year, sk, stats).await?;
+            }
+        }
+
+        Ok(year_stats)
+    }
+
+    /// Apply the difference of the collection item to the statistics.
+    /// `old` is the item that was saved before, and `new` is the item that is saved now.
+    /// None means that the item is not counted, e.g. it doesn't exist or it is in the trash.
+    pub(crate) async fn update_stats(
+        &self,
+        old: Option<&CollectionItem>,
+        new: Option<&CollectionItem>,
+    ) -> Result<(), Error> {
+        let Some(key_name) = new.or(old).map(|collection| collection.key_name.as_str()) else {
+            return Ok(());
+        };
+
+        let delta = new.map(PeriodStats::of).unwrap_or_default()
+            + -old.map(PeriodStats::of).unwrap_or_default();
+
+        if delta.is_zero() {
+            return Ok(());
+        }
+
+        let time = PathDateTime::parse(key_name).map_err(Error::invalid_input)?;
+
+        for sk in [0, time.month * 100, time.month * 100 + time.day] {
+            self.add_period_stats(time.year, sk, delta).await?;
+        }
+
+        Ok(())
+    }
+
+    /// add the statistics to the counters of the period atomically
+    async fn add_period_stats(&self, year: i32, sk: u32, stats: PeriodStats) -> Result<(), Error> {
+        let request = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(stats_key(year)))
+            .key("SK", AttributeValue::N(sk.to_string()))
+            .update_expression(
+                "ADD ObjectCount :object_count, TotalBytes :total_bytes, VideoCount :video_count, ImageCount :image_count, OtherCount :other_count",
+            )
+            .expression_attribute_values(
+                ":object_count",
+                AttributeValue::N(stats.object_count.to_string()),
+            )
+            .expression_attribute_values(
+                ":total_bytes",
+                AttributeValue::N(stats.total_bytes.to_string()),
+            )
+            .expression_attribute_values(
+                ":video_count",
+                AttributeValue::N(stats.video_count.to_string()),
+            )
+            .expression_attribute_values(
+                ":image_count",
+                AttributeValue::N(stats.image_count.to_string()),
+            )
+            .expression_attribute_values(
+                ":other_count",
+                AttributeValue::N(stats.other_count.to_string()),
+            );
+
+        // not retried, since the replay of the applied update counts twice
+        match request.send().await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(aws_error(e)),
+        }
+    }
+}
+
+/// The partition key of the statistics
+fn stats_key(year: i32) -> String {
+    format!("Stats#{year}")
+}
+
+fn period_stats_from_attributes(
+    item: &HashMap<String, AttributeValue>,
+) -> Result<PeriodStats, Error> {
+    let number = |name: &str| match item.get(name).map(|attribute| attribute.as_n()) {
+        None => Ok(0),
+        Some(Ok(n)) => n
+            .parse::<i64>()
+            .map_err(|e| Error::from_source(ErrorKind::Internal, e)),
+        Some(Err(_)) => Err(Error::internal(format!("{name} must be a number"))),
+    };
+
+    Ok(PeriodStats {
+        object_count: number("ObjectCount")?,
+        total_bytes: number("TotalBytes")?,
+        video_count: number("VideoCount")?,
+        image_count: number("ImageCount")?,
+        other_count: number("OtherCount")?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dynamodb::client::DynamoClientTrait;
+
+    fn sized_object(key_name: &str, size: i64) -> CollectionItem {
+        CollectionItem {
+            size: Some(size),
+            ..CollectionItem::dummy_object(key_name)
+        }
+    }
+
+    async fn save_test_data(client: &DynamoDbClient) {
+        let collections = vec![
+            sized_object("1984/04/04/1984-04-04-12-34-50.MOV", 100),
+            sized_object("1984/04/04/1984-04-04-12-34-51.JPG", 10),
+            sized_object("1984/05/04/1984-05-04-12-34-50.MOV", 1000),
+        ];
+        client.put_collection_items(&collections).await.unwrap();
+    }
+
+    fn stats(
+        object_count: i64,
+        total_bytes: i64,
+        video_count: i64,
+        image_count: i64,
+    ) -> PeriodStats {
+        PeriodStats {
+            object_count,
+            total_bytes,
+            video_count,
+            image_count,
+            other_count: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stats_are_updated_by_saving() {
+        // Arrange
+        let client = DynamoDbClient::new("test_stats_are_updated_by_saving").await;
+
+        // Act
+        save_test_data(&client).await;
+        // saving the same item again doesn't count it twice
+        let saved = client.get_collection_items(1984, 4, 4).await.unwrap();
+        client.put_collection_items(&saved).await.unwrap();
+
+        // Assert
+        let result = client.get_year_stats(1984).await.unwrap();
+        assert_eq!(result.total, stats(3, 1110, 2, 1));
+        assert_eq!(result.months[&4], stats(2, 110, 1, 1));
+        assert_eq!(result.months[&5], stats(1, 1000, 1, 0));
+        assert_eq!(result.days[&(4, 4)], stats(2, 110, 1, 1));
+        assert_eq!(result.days.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stats_are_updated_by_trash_and_restore() {
+        // Arrange
+        let client = DynamoDbClient::new("test_stats_are_updated_by_trash_and_restore").await;
+        save_test_data(&client).await;
+        let key_name = "1984/05/04/1984-05-04-12-34-50.MOV";
+
+        // Act
+        client
+            .trash_collection_item(key_name, "user", None)
+            .await
+            .unwrap();
+
+        // Assert
+        let result = client.get_year_stats(1984).await.unwrap();
+        assert_eq!(result.total, stats(2, 110, 1, 1));
+        assert_eq!(result.months[&5], PeriodStats::default());
+
+        // Act
+        client.restore_collection_item(key_name).await.unwrap();
+
+        // Assert
+        let result = client.get_year_stats(1984).await.unwrap();
+        assert_eq!(result.total, stats(3, 1110, 2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_recompute_stats() {
+        // Arrange
+        let client = DynamoDbClient::new("test_recompute_stats").await;
+        save_test_data(&client).await;
+        let expected = client.get_year_stats(1984).await.unwrap();
+        client
+            .add_period_stats(1984, 0, stats(10, 10, 10, 0))
+            .await
+            .unwrap();
+
+        // Act
+        let result = client.recompute_stats(1984).await.unwrap();
+
+        // Assert
+        assert_eq!(result, expected);
+        assert_eq!(client.get_year_stats(1984).await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_get_year_stats_with_no_data() {
+        // Arrange
+        let client = DynamoDbClient::new("test_get_year_stats_with_no_data").await;
+
+        // Act
+        let result = client.get_year_stats(1984).await.unwrap();
+
+        // Assert
+        assert_eq!(result, YearStats::default());
+    }
+
+    #[test]
+    fn test_media_kind() {
+        // Arrange
+        let video = CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-50.MOV");
+        let image = CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-50.heic");
+        let typed = CollectionItem {
+            content_type: Some("image/png".to_string()),
+            ..CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-50.bin")
+        };
+        let other = CollectionItem::dummy_object("1984/04/04/1984-04-04-12-34-50.txt");
+
+        // Assert
+        assert_eq!(MediaKind::of(&video), MediaKind::Video);
+        assert_eq!(MediaKind::of(&image), MediaKind::Image);
+        assert_eq!(MediaKind::of(&typed), MediaKind::Image);
+        assert_eq!(MediaKind::of(&other), MediaKind::Other);
+    }
+}
@@ -0,0 +1,347 @@
+//! The queries of the collection items by the vault and by the storage state
+//! The queries are backed by the global secondary indexes, so they don't scan the table.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#storage-indexes>
+
+use crate::dynamodb::client::{collection_item_from_attributes, DynamoDbClient};
+use crate::dynamodb::entities::collection::CollectionItem;
+use crate::dynamodb::schema::IndexDefinition;
+use crate::dynamodb::trash::TRASHED_AT;
+use crate::error::aws_error;
+use aws_sdk_dynamodb::types::{AttributeValue, ScalarAttributeType};
+use futures::TryFutureExt;
+use shared::error::Error;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The index of the collection items by the vault
+pub const VAULT_INDEX: &str = "VaultIndex";
+
+/// The index of the collection items by the storage state
+pub const STORAGE_STATE_INDEX: &str = "StorageStateIndex";
+
+/// The attribute of the vault
+/// The item that is not archived doesn't have it, because an index key cannot be empty.
+pub(crate) const VAULT: &str = "Vault";
+
+/// The attribute of the storage state
+pub(crate) const STORAGE_STATE: &str = "StorageState";
+
+/// Where the object is stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageState {
+    /// The object is only in the standard bucket
+    Standard,
+    /// The object is archived in the vault
+    Archived,
+}
+
+impl StorageState {
+    pub fn of(collection: &CollectionItem) -> Self {
+        match collection.vault.is_empty() {
+            true => StorageState::Standard,
+            false => StorageState::Archived,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageState::Standard => "Standard",
+            StorageState::Archived => "Archived",
+        }
+    }
+}
+
+impl Display for StorageState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for StorageState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Standard" => Ok(StorageState::Standard),
+            "Archived" => Ok(StorageState::Archived),
+            _ => Err(Error::invalid_input(format!("Unknown storage state: {s}"))),
+        }
+    }
+}
+
+/// A page of the collection items
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionPage {
+    /// The items in the order of the time
+    pub items: Vec<CollectionItem>,
+    /// Pass it to get the next page. If None, this is the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// The indexes of this module
+pub(crate) fn storage_indexes() -> Vec<IndexDefinition> {
+    vec![
+        IndexDefinition {
+            name: VAULT_INDEX,
+            partition_key: (VAULT, ScalarAttributeType::S),
+            sort_key: ("SK", ScalarAttributeType::N),
+        },
+        IndexDefinition {
+            name: STORAGE_STATE_INDEX,
+            partition_key: (STORAGE_STATE, ScalarAttributeType::S),
+            sort_key: ("SK", ScalarAttributeType::N),
+        },
+    ]
+}
+
+impl DynamoDbClient {
+    /// get the collection items that are archived in the vault
+    /// The cursor is the one that is returned by the previous page.
+    /// The items in the trash are not included, so a page can have fewer items than the limit.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, vault = %vault, limit = %limit),
+        err(level = "warn")
+    )]
+    pub async fn get_collection_items_by_vault(
+        &self,
+        vault: &str,
+        limit: i32,
+        cursor: Option<&str>,
+    ) -> Result<CollectionPage, Error> {
+        self.query_collection_index(VAULT_INDEX, VAULT, vault, limit, cursor)
+            .await
+    }
+
+    /// get the collection items in the storage state
+    /// The cursor is the one that is returned by the previous page.
+    /// The items in the trash are not included, so a page can have fewer items than the limit.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, storage_state = storage_state.as_str(), limit = %limit),
+        err(level = "warn")
+    )]
+    pub async fn get_collection_items_by_storage_state(
+        &self,
+        storage_state: StorageState,
+        limit: i32,
+        cursor: Option<&str>,
+    ) -> Result<CollectionPage, Error> {
+        self.query_collection_index(
+            STORAGE_STATE_INDEX,
+            STORAGE_STATE,
+            storage_state.as_str(),
+            limit,
+            cursor,
+        )
+        .await
+    }
+
+    async fn query_collection_index(
+        &self,
+        index_name: &str,
+        attribute: &str,
+        value: &str,
+        limit: i32,
+        cursor: Option<&str>,
+    ) -> Result<CollectionPage, Error> {
+        let exclusive_start_key = match cursor {
+            Some(cursor) => {
+                let mut key = cursor_to_key(cursor)?;
+                key.insert(attribute.to_string(), AttributeValue::S(value.to_string()));
+                Some(key)
+            }
+            None => None,
+        };
+
+        let request = self
+            .client
+            .query()
+            .table_name(&self.table_name)
+            .index_name(index_name)
+            .key_condition_expression("#attribute = :value")
+            .filter_expression("attribute_not_exists(#trashed_at)")
+            .expression_attribute_names("#attribute", attribute)
+            .expression_attribute_names("#trashed_at", TRASHED_AT)
+            .expression_attribute_values(":value", AttributeValue::S(value.to_string()))
+            .set_exclusive_start_key(exclusive_start_key)
+            .limit(limit);
+
+        let output = self
+            .retry
+            .run("Query", || request.clone().send().map_err(aws_error))
+            .await?;
+
+        let items = output
+            .items()
+            .iter()
+            .map(collection_item_from_attributes)
+            .collect::<Result<Vec<CollectionItem>, Error>>()?;
+
+        let next_cursor = match &output.last_evaluated_key {
+            Some(key) => Some(key_to_cursor(key)?),
+            None => None,
+        };
+
+        Ok(CollectionPage { items, next_cursor })
+    }
+}
+
+/// The cursor is `{PK}:{SK}` of the last evaluated item
+fn key_to_cursor(key: &HashMap<String, AttributeValue>) -> Result<String, Error> {
+    match (
+        key.get("PK").map(|pk| pk.as_s()),
+        key.get("SK").map(|sk| sk.as_n()),
+    ) {
+        (Some(Ok(pk)), Some(Ok(sk))) => Ok(format!("{pk}:{sk}")),
+        _ => Err(Error::internal("The last evaluated key is invalid")),
+    }
+}
+
+fn cursor_to_key(cursor: &str) -> Result<HashMap<String, AttributeValue>, Error> {
+    match cursor.split_once(':') {
+        Some((pk, sk)) if !pk.is_empty() && sk.parse::<i64>().is_ok() => Ok(HashMap::from([
+            ("PK".to_string(), AttributeValue::S(pk.to_string())),
+            ("SK".to_string(), AttributeValue::N(sk.to_string())),
+        ])),
+        _ => Err(Error::invalid_input(format!("Invalid cursor: {cursor}"))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dynamodb::client::DynamoClientTrait;
+    use crate::dynamodb::version::CollectionUpdate;
+
+    fn object_in(key_name: &str, vault: &str) -> CollectionItem {
+        CollectionItem::new_object(key_name, vault).unwrap()
+    }
+
+    async fn save_test_data(client: &DynamoDbClient) {
+        let collections = vec![
+            object_in("1984/04/04/1984-04-04-12-34-50.MOV", "vault"),
+            object_in("1984/04/04/1984-04-04-12-34-51.MOV", "vault"),
+            object_in("1984/04/05/1984-04-05-12-34-50.MOV", "another"),
+            object_in("1985/04/04/1985-04-04-12-34-50.MOV", "vault"),
+            object_in("1985/04/05/1985-04-05-12-34-50.MOV", ""),
+        ];
+        client.put_collection_items(&collections).await.unwrap();
+    }
+
+    fn key_names(page: &CollectionPage) -> Vec<&str> {
+        page.items
+            .iter()
+            .map(|item| item.key_name.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_get_collection_items_by_vault() {
+        // Arrange
+        let client = DynamoDbClient::new("test_get_collection_items_by_vault").await;
+        save_test_data(&client).await;
+        client
+            .trash_collection_item("1984/04/04/1984-04-04-12-34-51.MOV", "user", None)
+            .await
+            .unwrap();
+
+        // Act
+        let mut key_names_in_vault = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = client
+                .get_collection_items_by_vault("vault", 1, cursor.as_deref())
+                .await
+                .unwrap();
+            key_names_in_vault.extend(key_names(&page).iter().map(|key| key.to_string()));
+            match page.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => break,
+            }
+        }
+
+        // Assert
+        key_names_in_vault.sort();
+        assert_eq!(
+            key_names_in_vault,
+            [
+                "1984/04/04/1984-04-04-12-34-50.MOV",
+                "1985/04/04/1985-04-04-12-34-50.MOV"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_collection_items_by_storage_state() {
+        // Arrange
+        let client = DynamoDbClient::new("test_get_collection_items_by_storage_state").await;
+        save_test_data(&client).await;
+
+        // Act
+        let standard = client
+            .get_collection_items_by_storage_state(StorageState::Standard, 10, None)
+            .await
+            .unwrap();
+        let archived = client
+            .get_collection_items_by_storage_state(StorageState::Archived, 10, None)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(key_names(&standard), ["1985/04/05/1985-04-05-12-34-50.MOV"]);
+        assert_eq!(standard.items[0].vault, "");
+        assert_eq!(archived.items.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_move_to_another_vault() {
+        // Arrange
+        let client = DynamoDbClient::new("test_move_to_another_vault").await;
+        save_test_data(&client).await;
+        let key_name = "1985/04/05/1985-04-05-12-34-50.MOV";
+
+        // Act
+        client
+            .update_collection_item(
+                key_name,
+                &CollectionUpdate {
+                    vault: Some("another".to_string()),
+                    ..CollectionUpdate::default()
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        // Assert
+        let another = client
+            .get_collection_items_by_vault("another", 10, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            key_names(&another),
+            ["1984/04/05/1984-04-05-12-34-50.MOV", key_name]
+        );
+        let standard = client
+            .get_collection_items_by_storage_state(StorageState::Standard, 10, None)
+            .await
+            .unwrap();
+        assert!(standard.items.is_empty());
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        // Arrange
+        let key = cursor_to_key("1984:449930090000").unwrap();
+
+        // Act
+        let result = key_to_cursor(&key);
+
+        // Assert
+        assert_eq!(result, Ok("1984:449930090000".to_string()));
+        assert!(cursor_to_key("1984").is_err());
+        assert!(cursor_to_key("1984:abc").is_err());
+    }
+}
@@ -1,18 +1,15 @@
+use crate::dynamodb::builder::DynamoDbClientBuilder;
 use crate::dynamodb::client::{DynamoClientTrait, DynamoDbClient};
 use crate::dynamodb::entities::collection::CollectionItem;
-use crate::dynamodb::environment_values::dynamodb_client;
-use aws_sdk_dynamodb::types::{
-    AttributeDefinition, KeySchemaElement, KeyType, ProvisionedThroughput, ScalarAttributeType,
-};
+use crate::dynamodb::environment_values::dynamo_db_url;
+use crate::fake::FakeAws;
+use aws_sdk_dynamodb::config::Credentials;
+use shared::error::Error;
 
-impl<'a> DynamoDbClient<'a> {
+impl DynamoDbClient {
     /// when this function is called, the new table, which is provided by argument, will be created.
-    #[cfg(test)]
-    pub async fn new(table_name: &'a str) -> Self {
-        let client = Self {
-            client: dynamodb_client().await,
-            table_name,
-        };
+    pub async fn new(table_name: &str) -> Self {
+        let client = test_client(table_name).await;
 
         client.create_table().await;
 
@@ -22,19 +19,17 @@ impl<'a> DynamoDbClient<'a> {
     /// create a new dynamodb table named by the argument
     /// if that table already exists, this function delete it before creating a table..
     pub async fn create_table(&self) {
-        match table_existence(self.table_name).await {
-            true => {
-                self.delete_table().await;
-                _create_table(self.table_name).await;
-            }
-            false => _create_table(self.table_name).await,
+        if self.describe_table().await.unwrap().is_some() {
+            self.delete_table().await;
         }
+
+        self.ensure_table().await.expect("couldn't create a table");
     }
 
     pub async fn delete_table(&self) {
         self.client
             .delete_table()
-            .table_name(self.table_name)
+            .table_name(&self.table_name)
             .send()
             .await
             .expect("delete table failed");
@@ -42,76 +37,24 @@ impl<'a> DynamoDbClient<'a> {
 
     /// add dummy data
     /// the key must be the file name
-    async fn add_dummy_data(&self, key_name: &str) -> Result<(), String> {
+    async fn add_dummy_data(&self, key_name: &str) -> Result<(), Error> {
         let collection = CollectionItem::dummy_object(key_name);
         self.put_collection_items(&vec![collection]).await?;
         Ok(())
     }
 }
 
-async fn _create_table(table_name: &str) {
-    let pk_ad = AttributeDefinition::builder()
-        .attribute_name("PK")
-        .attribute_type(ScalarAttributeType::S)
-        .build()
-        .expect("pk attribute error");
-
-    let pk_ks = KeySchemaElement::builder()
-        .attribute_name("PK")
-        .key_type(KeyType::Hash)
-        .build()
-        .expect("pk key error");
-
-    let sk_ad = AttributeDefinition::builder()
-        .attribute_name("SK")
-        .attribute_type(ScalarAttributeType::N)
-        .build()
-        .expect("sort attribute error");
-
-    let sk_ks = KeySchemaElement::builder()
-        .attribute_name("SK")
-        .key_type(KeyType::Range)
-        .build()
-        .expect("sort key error");
-
-    let pt = ProvisionedThroughput::builder()
-        .read_capacity_units(10)
-        .write_capacity_units(10)
-        .build()
-        .unwrap();
-
-    dynamodb_client()
-        .await
-        .create_table()
+/// The client for the fake DynamoDB
+async fn test_client(table_name: &str) -> DynamoDbClient {
+    DynamoDbClientBuilder::new()
+        .endpoint_url(dynamo_db_url())
+        .http_client(FakeAws::shared().http_client())
+        .region("us-west-2")
+        .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
         .table_name(table_name)
-        .key_schema(pk_ks)
-        .key_schema(sk_ks)
-        .attribute_definitions(pk_ad)
-        .attribute_definitions(sk_ad)
-        .set_provisioned_throughput(Some(pt))
-        .send()
-        .await
-        .expect("couldn't create a table");
-}
-
-/// Check if the table exists or not by describing the table.
-/// If there is no table, the `describe_table` returns Err.
-/// It can be considered as there is no table thus this function returns false.
-async fn table_existence(table_name: &str) -> bool {
-    let result = dynamodb_client()
+        .build()
         .await
-        .describe_table()
-        .table_name(table_name)
-        .send()
-        .await;
-
-    match result {
-        Ok(table) => match table.table {
-            Some(_) => true,
-            None => false,
-        },
-        Err(_) => false,
-    }
+        .expect("couldn't build a test client")
 }
 
 #[cfg(test)]
@@ -122,24 +65,27 @@ mod test {
 
     #[tokio::test]
     async fn test_table_existence() {
+        // Arrange
+        let client = test_client("non-existing-table").await;
+
         // Act
-        let result = table_existence("non-existing-table").await;
+        let result = client.describe_table().await.unwrap();
 
         // Assert
-        assert_eq!(result, false);
+        assert!(result.is_none());
     }
 
     #[tokio::test]
     async fn test_create_table() {
         // Arrange
         let table_name = "new-table";
-        let _ = DynamoDbClient::new(table_name).await;
+        let client = DynamoDbClient::new(table_name).await;
 
         // Act
-        let result = table_existence(table_name).await;
+        let result = client.describe_table().await.unwrap();
 
         // Assert
-        assert!(result);
+        assert!(result.is_some());
     }
 
     #[tokio::test]
@@ -0,0 +1,421 @@
+//! The trash of the collection items
+//! A trashed item is hidden from the listings, and it can be restored until it expires.
+//! The expiry is driven by the TTL of the table, so DynamoDB purges the item after the retention period.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#trash>
+
+use crate::audit::{self, AuditAction};
+use crate::dynamodb::attribute_json::item_to_json;
+use crate::dynamodb::client::{collection_item_from_attributes, get_now, DynamoDbClient};
+use crate::dynamodb::protection::{with_unprotected_values, UNPROTECTED_CONDITION};
+use crate::dynamodb::version::VERSION;
+use crate::error::{aws_error, aws_service_error};
+use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue, ReturnValuesOnConditionCheckFailure};
+use futures::TryFutureExt;
+use shared::error::Error;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::Duration;
+use time_file_name::file_datetime::PathDateTime;
+
+/// The period that a trashed item can be restored
+pub const RETENTION_PERIOD: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The attribute that TTL of the table refers to (epoch time in seconds)
+pub const TTL_ATTRIBUTE: &str = "ExpireAt";
+
+/// The attribute of the trashed time
+pub(crate) const TRASHED_AT: &str = "TrashedAt";
+
+/// The partition key of the trash entries
+const TRASH_KEY: &str = "Trash";
+
+/// An item in the trash
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrashedItem {
+    pub key_name: String,
+    /// Epoch time (ms) when the item was trashed
+    pub trashed_at: u128,
+    /// Who trashed the item
+    pub trashed_by: String,
+    /// Epoch time (sec) after that the item is purged
+    pub expire_at: u64,
+}
+
+impl TrashedItem {
+    /// If true, the item cannot be restored anymore.
+    /// DynamoDB may keep the item for a while after the expiry, but it is considered as purged.
+    pub fn is_expired(&self, now: u128) -> bool {
+        u128::from(self.expire_at) * 1000 <= now
+    }
+}
+
+impl DynamoDbClient {
+    /// Move the collection item to the trash.
+    /// The item is removed from the date lookups and the statistics, and it will be purged after [RETENTION_PERIOD].
+    /// The item that is retained or held cannot be trashed.
+    /// time is mill sec
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name, trashed_by = %trashed_by),
+        err(level = "warn")
+    )]
+    pub async fn trash_collection_item(
+        &self,
+        key_name: &str,
+        trashed_by: &str,
+        time: Option<u128>,
+    ) -> Result<TrashedItem, Error> {
+        let now = get_now(time)?;
+        let expire_at = (now / 1000) as u64 + RETENTION_PERIOD.as_secs();
+        let (year, unix_time) = collection_key(key_name)?;
+
+        let request = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(year))
+            .key("SK", AttributeValue::N(unix_time.to_string()))
+            .update_expression(
+                "SET #trashed_at = :trashed_at, TrashedBy = :trashed_by, #expire_at = :expire_at ADD #version :one",
+            )
+            .condition_expression(format!(
+                "attribute_exists(PK) AND attribute_not_exists(#trashed_at) AND {UNPROTECTED_CONDITION}"
+            ))
+            .expression_attribute_names("#trashed_at", TRASHED_AT)
+            .expression_attribute_names("#expire_at", TTL_ATTRIBUTE)
+            .expression_attribute_names("#version", VERSION)
+            .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+            .expression_attribute_values(":trashed_at", AttributeValue::N(now.to_string()))
+            .expression_attribute_values(":trashed_by", AttributeValue::S(trashed_by.to_string()))
+            .expression_attribute_values(":expire_at", AttributeValue::N(expire_at.to_string()))
+            .return_values(ReturnValue::AllNew)
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld);
+        let request = with_unprotected_values(request, now);
+
+        // not retried, since the replay of the applied trash fails by its condition
+        let attributes = match request.send().await {
+            Ok(output) => match output.attributes {
+                Some(attributes) => attributes,
+                None => return Err(Error::internal("The trashed item is not returned")),
+            },
+            Err(e) => match e.into_service_error() {
+                UpdateItemError::ConditionalCheckFailedException(exception) => {
+                    // tell why the protected item cannot be trashed
+                    if let Some(item) = exception.item.filter(|item| !item.contains_key(TRASHED_AT))
+                    {
+                        collection_item_from_attributes(&item)?
+                            .protection
+                            .check(key_name, now)?;
+                    }
+                    return Err(Error::not_found(format!(
+                        "{key_name} is not found or already in the trash"
+                    )));
+                }
+                service_error => return Err(aws_service_error(service_error)),
+            },
+        };
+        let collection = collection_item_from_attributes(&attributes)?;
+
+        let trashed_item = TrashedItem {
+            key_name: collection.key_name.to_string(),
+            trashed_at: now,
+            trashed_by: trashed_by.to_string(),
+            expire_at,
+        };
+
+        self.put_trash_entry(unix_time, &trashed_item).await?;
+        self.remove_lookup(&trashed_item.key_name).await?;
+        self.update_stats(Some(&collection), None).await?;
+
+        audit::record(
+            &self.auditor,
+            AuditAction::Trash,
+            &trashed_item.key_name,
+            None,
+            Some(item_to_json(&attributes)?.to_string()),
+        )
+        .await?;
+
+        Ok(trashed_item)
+    }
+
+    /// Restore the collection item from the trash.
+    /// Returns Err if the item is not in the trash or it has been purged.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name),
+        err(level = "warn")
+    )]
+    pub async fn restore_collection_item(&self, key_name: &str) -> Result<(), Error> {
+        let (year, unix_time) = collection_key(key_name)?;
+
+        let request = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(year))
+            .key("SK", AttributeValue::N(unix_time.to_string()))
+            .update_expression("REMOVE #trashed_at, TrashedBy, #expire_at ADD #version :one")
+            .condition_expression("attribute_exists(#trashed_at)")
+            .expression_attribute_names("#trashed_at", TRASHED_AT)
+            .expression_attribute_names("#expire_at", TTL_ATTRIBUTE)
+            .expression_attribute_names("#version", VERSION)
+            .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+            .return_values(ReturnValue::AllNew);
+
+        // not retried, since the replay of the applied restore fails by its condition
+        let attributes = match request.send().await {
+            Ok(output) => match output.attributes {
+                Some(attributes) => attributes,
+                None => return Err(Error::internal("The restored item is not returned")),
+            },
+            Err(e) => match e.as_service_error() {
+                Some(service_error) if service_error.is_conditional_check_failed_exception() => {
+                    return Err(Error::not_found(format!("{key_name} is not in the trash")))
+                }
+                _ => return Err(aws_error(e)),
+            },
+        };
+        let collection = collection_item_from_attributes(&attributes)?;
+
+        let restored_key_name = collection.key_name.to_string();
+
+        self.update_stats(None, Some(&collection)).await?;
+        self.put_lookups(std::slice::from_ref(&collection)).await?;
+        self.delete_trash_entry(key_name).await?;
+
+        audit::record(
+            &self.auditor,
+            AuditAction::Restore,
+            &restored_key_name,
+            None,
+            Some(item_to_json(&attributes)?.to_string()),
+        )
+        .await
+    }
+
+    /// get the items in the trash
+    /// The items are sorted by the trashed time, the newest first.
+    /// The expired items are included until their objects are cleaned up.
+    #[tracing::instrument(skip_all, fields(table = %self.table_name), err(level = "warn"))]
+    pub async fn get_trashed_items(&self) -> Result<Vec<TrashedItem>, Error> {
+        let mut trashed_items = self
+            .query_partition(TRASH_KEY)
+            .await?
+            .iter()
+            .map(trashed_item_from_attributes)
+            .collect::<Result<Vec<TrashedItem>, Error>>()?;
+
+        trashed_items.sort_by_key(|item| Reverse(item.trashed_at));
+
+        Ok(trashed_items)
+    }
+
+    /// check if the collection item still exists in the table, including the trashed one
+    pub(crate) async fn collection_item_exists(&self, key_name: &str) -> Result<bool, Error> {
+        let (year, unix_time) = collection_key(key_name)?;
+
+        let request = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(year))
+            .key("SK", AttributeValue::N(unix_time.to_string()))
+            .projection_expression("PK");
+
+        self.retry
+            .run("GetItem", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|output| output.item.is_some())
+    }
+
+    /// delete the trash entry
+    pub(crate) async fn delete_trash_entry(&self, key_name: &str) -> Result<(), Error> {
+        let (_, unix_time) = collection_key(key_name)?;
+
+        let request = self
+            .client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(TRASH_KEY.to_string()))
+            .key("SK", AttributeValue::N(unix_time.to_string()));
+
+        self.retry
+            .run("DeleteItem", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|_| ())
+    }
+
+    /// put the trash entry
+    /// The entry doesn't have the TTL attribute, so it remains until the object is cleaned up.
+    async fn put_trash_entry(&self, unix_time: i64, item: &TrashedItem) -> Result<(), Error> {
+        let request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(TRASH_KEY.to_string()))
+            .item("SK", AttributeValue::N(unix_time.to_string()))
+            .item("KeyName", AttributeValue::S(item.key_name.to_string()))
+            .item(TRASHED_AT, AttributeValue::N(item.trashed_at.to_string()))
+            .item("TrashedBy", AttributeValue::S(item.trashed_by.to_string()))
+            .item("PurgeAt", AttributeValue::N(item.expire_at.to_string()));
+
+        self.retry
+            .run("PutItem", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|_| ())
+    }
+}
+
+/// The partition key and the sort key of the collection item
+fn collection_key(key_name: &str) -> Result<(String, i64), Error> {
+    let path_date_time = PathDateTime::parse(key_name).map_err(Error::invalid_input)?;
+    Ok((path_date_time.year.to_string(), path_date_time.unix_time))
+}
+
+fn trashed_item_from_attributes(
+    item: &HashMap<String, AttributeValue>,
+) -> Result<TrashedItem, Error> {
+    let string = |name: &str| match item.get(name).map(|attribute| attribute.as_s()) {
+        Some(Ok(value)) => Ok(value.to_owned()),
+        _ => Err(Error::internal(format!(
+            "{name} must be a string in the trash entry"
+        ))),
+    };
+    let number = |name: &str| match item.get(name).map(|attribute| attribute.as_n()) {
+        Some(Ok(value)) => Ok(value.to_owned()),
+        _ => Err(Error::internal(format!(
+            "{name} must be a number in the trash entry"
+        ))),
+    };
+
+    let Ok(trashed_at) = number(TRASHED_AT)?.parse::<u128>() else {
+        return Err(Error::internal("Invalid trashed time"));
+    };
+    let Ok(expire_at) = number("PurgeAt")?.parse::<u64>() else {
+        return Err(Error::internal("Invalid expiry time"));
+    };
+
+    Ok(TrashedItem {
+        key_name: string("KeyName")?,
+        trashed_at,
+        trashed_by: string("TrashedBy")?,
+        expire_at,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dynamodb::client::DynamoClientTrait;
+    use crate::dynamodb::entities::collection::CollectionItem;
+    use shared::traits::GetFileListTrait;
+
+    const KEY_NAME: &str = "1984/04/04/1984-04-04-12-34-50.MOV";
+
+    async fn save_test_data(client: &DynamoDbClient) {
+        let collections = vec![
+            CollectionItem::dummy_object(KEY_NAME),
+            CollectionItem::dummy_object("1985/04/04/1985-04-04-12-34-50.MOV"),
+        ];
+        client.put_collection_items(&collections).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_trash_collection_item() {
+        // Arrange
+        let client = DynamoDbClient::new("test_trash_collection_item").await;
+        save_test_data(&client).await;
+
+        // Act
+        let trashed_item = client
+            .trash_collection_item(KEY_NAME, "user", Some(1_000_000))
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(trashed_item.expire_at, 1_000 + RETENTION_PERIOD.as_secs());
+        assert_eq!(client.get_collection_item(KEY_NAME).await.unwrap(), None);
+        assert!(client
+            .get_collection_items(1984, 4, 4)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(client.get_objects(1984, 4, 4).await.unwrap().is_empty());
+        assert_eq!(client.get_years().await.unwrap(), ["1985"]);
+        assert_eq!(client.get_trashed_items().await.unwrap(), [trashed_item]);
+    }
+
+    #[tokio::test]
+    async fn test_trash_collection_item_twice() {
+        // Arrange
+        let client = DynamoDbClient::new("test_trash_collection_item_twice").await;
+        save_test_data(&client).await;
+        client
+            .trash_collection_item(KEY_NAME, "user", None)
+            .await
+            .unwrap();
+
+        // Act
+        let result = client.trash_collection_item(KEY_NAME, "user", None).await;
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restore_collection_item() {
+        // Arrange
+        let client = DynamoDbClient::new("test_restore_collection_item").await;
+        save_test_data(&client).await;
+        client
+            .trash_collection_item(KEY_NAME, "user", None)
+            .await
+            .unwrap();
+
+        // Act
+        client.restore_collection_item(KEY_NAME).await.unwrap();
+
+        // Assert
+        assert_eq!(
+            client.get_collection_item(KEY_NAME).await.unwrap(),
+            // saved, trashed, and restored
+            Some(CollectionItem {
+                version: 3,
+                ..CollectionItem::dummy_object(KEY_NAME)
+            })
+        );
+        assert_eq!(client.get_objects(1984, 4, 4).await.unwrap(), [KEY_NAME]);
+        assert_eq!(client.get_years().await.unwrap(), ["1984", "1985"]);
+        assert!(client.get_trashed_items().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restore_item_not_in_trash() {
+        // Arrange
+        let client = DynamoDbClient::new("test_restore_item_not_in_trash").await;
+        save_test_data(&client).await;
+
+        // Act
+        let result = client.restore_collection_item(KEY_NAME).await;
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_is_expired() {
+        // Arrange
+        let trashed_item = TrashedItem {
+            key_name: KEY_NAME.to_string(),
+            trashed_at: 0,
+            trashed_by: "user".to_string(),
+            expire_at: 10,
+        };
+
+        // Assert
+        assert!(!trashed_item.is_expired(9_999));
+        assert!(trashed_item.is_expired(10_000));
+    }
+}
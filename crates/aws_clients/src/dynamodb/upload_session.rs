@@ -0,0 +1,787 @@
+//! The upload sessions
+//! A session is created when a pre-signed URL is handed out, and it is completed when the object is uploaded.
+//! The sessions that are not completed in time are listed as stale, so they can be retried or cleaned up.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#upload-session>
+
+use crate::dynamodb::client::{get_now, request_token, DynamoDbClient};
+use crate::dynamodb::schema::IndexDefinition;
+use crate::dynamodb::trash::TTL_ATTRIBUTE;
+use crate::error::aws_error;
+use aws_sdk_dynamodb::types::{AttributeValue, ScalarAttributeType, TransactWriteItem, Update};
+use futures::TryFutureExt;
+use shared::error::{Error, ErrorKind};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use time_file_name::file_datetime::PathDateTime;
+
+/// The index of the sessions that are not completed, sorted by the expiry
+/// The sessions of the objects that are uploaded in the same second have the same time,
+/// so they are listed by the index, whose keys don't have to be unique.
+pub const PENDING_UPLOAD_INDEX: &str = "PendingUploadIndex";
+
+/// The attribute of the session that is not completed, which is the partition key of [PENDING_UPLOAD_INDEX]
+/// Its value is the name itself, so the index has one partition. The completed session doesn't have it.
+/// It was the partition key of the pending entries before the index.
+const PENDING_UPLOAD: &str = "PendingUpload";
+
+/// The period that a completed session is kept
+pub const COMPLETED_RETENTION_PERIOD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The state of an upload session
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UploadState {
+    /// The object has not been uploaded yet
+    Pending,
+    /// The object has been uploaded as expected
+    Completed,
+    /// The object has been uploaded, but its size or checksum is not the expected one
+    Failed,
+}
+
+impl UploadState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UploadState::Pending => "Pending",
+            UploadState::Completed => "Completed",
+            UploadState::Failed => "Failed",
+        }
+    }
+}
+
+impl FromStr for UploadState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Pending" => Ok(UploadState::Pending),
+            "Completed" => Ok(UploadState::Completed),
+            "Failed" => Ok(UploadState::Failed),
+            _ => Err(Error::internal(format!("Unknown upload state: {s}"))),
+        }
+    }
+}
+
+/// An upload session
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadSession {
+    /// The key name that follows the bucket convention
+    pub key_name: String,
+    /// Who uploads the object
+    pub uploader: Option<String>,
+    /// The object size in bytes that the client reported
+    pub expected_size: Option<i64>,
+    /// The checksum that the client reported
+    pub expected_checksum: Option<String>,
+    /// Epoch time (ms)
+    pub created_at: u128,
+    /// Epoch time (ms) when the pre-signed URL expires
+    pub expires_at: u128,
+    pub state: UploadState,
+    /// Epoch time (ms) when the object was uploaded
+    pub completed_at: Option<u128>,
+}
+
+impl UploadSession {
+    /// create a new pending session
+    /// The key name is normalized to the one that follows the bucket convention.
+    /// time is mill sec
+    pub fn new(key_name: &str, expires_in: Duration, time: Option<u128>) -> Result<Self, Error> {
+        let created_at = get_now(time)?;
+
+        Ok(UploadSession {
+            key_name: PathDateTime::parse(key_name)
+                .map_err(Error::invalid_input)?
+                .key_name(),
+            uploader: None,
+            expected_size: None,
+            expected_checksum: None,
+            created_at,
+            expires_at: created_at + expires_in.as_millis(),
+            state: UploadState::Pending,
+            completed_at: None,
+        })
+    }
+
+    /// If true, the object is not uploaded as expected, and the session should be retried or cleaned up.
+    pub fn is_stale(&self, now: u128) -> bool {
+        match self.state {
+            UploadState::Pending => self.expires_at <= now,
+            UploadState::Completed => false,
+            UploadState::Failed => true,
+        }
+    }
+
+    /// The state that the uploaded object results in
+    /// The expectations that are not reported are not checked.
+    fn state_of(&self, size: Option<i64>, checksum: Option<&str>) -> UploadState {
+        let is_size_mismatched = matches!(
+            (self.expected_size, size),
+            (Some(expected), Some(actual)) if expected != actual
+        );
+        let is_checksum_mismatched = matches!(
+            (self.expected_checksum.as_deref(), checksum),
+            (Some(expected), Some(actual)) if expected != actual
+        );
+
+        match is_size_mismatched || is_checksum_mismatched {
+            true => UploadState::Failed,
+            false => UploadState::Completed,
+        }
+    }
+}
+
+impl DynamoDbClient {
+    /// Save the new session
+    /// The session of the same key name is replaced, e.g. when the client retries.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = %session.key_name),
+        err(level = "warn")
+    )]
+    pub async fn put_upload_session(&self, session: &UploadSession) -> Result<(), Error> {
+        let mut item = upload_session_to_attributes(session);
+        item.insert(
+            "PK".to_string(),
+            AttributeValue::S(session_key(&session.key_name)),
+        );
+        item.insert("SK".to_string(), AttributeValue::N("0".to_string()));
+        if session.state != UploadState::Completed {
+            item.insert(
+                PENDING_UPLOAD.to_string(),
+                AttributeValue::S(PENDING_UPLOAD.to_string()),
+            );
+        }
+
+        let request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(item));
+
+        self.retry
+            .run("PutItem", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|_| ())
+    }
+
+    /// get the session of the key name
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name),
+        err(level = "warn")
+    )]
+    pub async fn get_upload_session(&self, key_name: &str) -> Result<Option<UploadSession>, Error> {
+        let key_name = PathDateTime::parse(key_name)
+            .map_err(Error::invalid_input)?
+            .key_name();
+
+        let request = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(session_key(&key_name)))
+            .key("SK", AttributeValue::N("0".to_string()));
+
+        let output = self
+            .retry
+            .run("GetItem", || request.clone().send().map_err(aws_error))
+            .await?;
+
+        match output.item {
+            Some(item) => Ok(Some(upload_session_from_attributes(&item)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Mark the session of the uploaded object as completed.
+    /// If the size or the checksum is not the expected one, the session is marked as failed, and it stays stale.
+    /// Returns None if there is no pending session, e.g. the object was not uploaded via the pre-signed URL.
+    /// time is mill sec
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name, size = ?size),
+        err(level = "warn")
+    )]
+    pub async fn complete_upload_session(
+        &self,
+        key_name: &str,
+        size: Option<i64>,
+        checksum: Option<&str>,
+        time: Option<u128>,
+    ) -> Result<Option<UploadSession>, Error> {
+        let now = get_now(time)?;
+
+        let Some(session) = self.get_upload_session(key_name).await? else {
+            return Ok(None);
+        };
+        if session.state != UploadState::Pending {
+            return Ok(None);
+        }
+
+        let state = session.state_of(size, checksum);
+        let completed = UploadSession {
+            state,
+            completed_at: Some(now),
+            ..session
+        };
+
+        // the completed session is kept for a while, and the failed one stays stale
+        let expire_at = match state {
+            UploadState::Completed => {
+                Some((now / 1000) as u64 + COMPLETED_RETENTION_PERIOD.as_secs())
+            }
+            _ => None,
+        };
+        let update = self.state_update(&completed, expire_at)?;
+
+        // the update is sent as a transaction, since the token makes the replay of the applied one succeed
+        // instead of failing by its condition
+        let request = self
+            .client
+            .transact_write_items()
+            .transact_items(TransactWriteItem::builder().update(update).build())
+            .client_request_token(request_token());
+
+        self.retry
+            .run("TransactWriteItems", || async {
+                match request.clone().send().await {
+                    Ok(_) => Ok(Some(completed.clone())),
+                    Err(e) => match e.as_service_error() {
+                        // completed by another call at the same time
+                        Some(service_error)
+                            if service_error.is_transaction_canceled_exception() =>
+                        {
+                            Ok(None)
+                        }
+                        _ => Err(aws_error(e)),
+                    },
+                }
+            })
+            .await
+    }
+
+    /// The update of the state of the pending session
+    /// The completed session is removed from the pending upload index.
+    fn state_update(
+        &self,
+        session: &UploadSession,
+        expire_at: Option<u64>,
+    ) -> Result<Update, Error> {
+        let mut update_expression = "SET #state = :state, CompletedAt = :completed_at".to_string();
+        let mut update = Update::builder()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(session_key(&session.key_name)))
+            .key("SK", AttributeValue::N("0".to_string()))
+            .condition_expression("#state = :pending")
+            .expression_attribute_names("#state", "State")
+            .expression_attribute_values(
+                ":state",
+                AttributeValue::S(session.state.as_str().to_string()),
+            )
+            .expression_attribute_values(
+                ":completed_at",
+                AttributeValue::N(session.completed_at.unwrap_or_default().to_string()),
+            )
+            .expression_attribute_values(
+                ":pending",
+                AttributeValue::S(UploadState::Pending.as_str().to_string()),
+            );
+
+        if let Some(expire_at) = expire_at {
+            update_expression.push_str(", #expire_at = :expire_at REMOVE #pending_upload");
+            update = update
+                .expression_attribute_names("#expire_at", TTL_ATTRIBUTE)
+                .expression_attribute_names("#pending_upload", PENDING_UPLOAD)
+                .expression_attribute_values(
+                    ":expire_at",
+                    AttributeValue::N(expire_at.to_string()),
+                );
+        }
+
+        update
+            .update_expression(update_expression)
+            .build()
+            .map_err(|e| Error::from_source(ErrorKind::Internal, e))
+    }
+
+    /// get the sessions that are not completed in time or whose object is not the expected one
+    /// The sessions are sorted by the expiry, the oldest first.
+    /// time is mill sec
+    #[tracing::instrument(skip_all, fields(table = %self.table_name), err(level = "warn"))]
+    pub async fn get_stale_upload_sessions(
+        &self,
+        time: Option<u128>,
+    ) -> Result<Vec<UploadSession>, Error> {
+        let now = get_now(time)?;
+
+        let mut sessions = Vec::new();
+        let mut exclusive_start_key = None;
+
+        loop {
+            let request = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .index_name(PENDING_UPLOAD_INDEX)
+                .key_condition_expression("#pending_upload = :pending_upload")
+                .expression_attribute_names("#pending_upload", PENDING_UPLOAD)
+                .expression_attribute_values(
+                    ":pending_upload",
+                    AttributeValue::S(PENDING_UPLOAD.to_string()),
+                )
+                .set_exclusive_start_key(exclusive_start_key);
+
+            let output = self
+                .retry
+                .run("Query", || request.clone().send().map_err(aws_error))
+                .await?;
+
+            for item in output.items() {
+                let session = upload_session_from_attributes(item)?;
+                if session.is_stale(now) {
+                    sessions.push(session);
+                }
+            }
+
+            match output.last_evaluated_key {
+                Some(key) => exclusive_start_key = Some(key),
+                None => break,
+            }
+        }
+        sessions.sort_by_key(|session| session.expires_at);
+
+        Ok(sessions)
+    }
+
+    /// delete the session, e.g. after the stale session is cleaned up
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name),
+        err(level = "warn")
+    )]
+    pub async fn delete_upload_session(&self, key_name: &str) -> Result<(), Error> {
+        let key_name = PathDateTime::parse(key_name)
+            .map_err(Error::invalid_input)?
+            .key_name();
+
+        let request = self
+            .client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(session_key(&key_name)))
+            .key("SK", AttributeValue::N("0".to_string()));
+
+        self.retry
+            .run("DeleteItem", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|_| ())
+    }
+
+    /// Move the pending entries of the sessions, which were sorted by the time of the key name,
+    /// to the pending upload index.
+    /// The entry whose session doesn't exist anymore is just deleted.
+    pub(crate) async fn index_pending_upload_sessions(&self) -> Result<(), Error> {
+        for entry in self.query_partition(PENDING_UPLOAD).await? {
+            let session = upload_session_from_attributes(&entry)?;
+
+            if self.get_upload_session(&session.key_name).await?.is_some() {
+                let request = self
+                    .client
+                    .update_item()
+                    .table_name(&self.table_name)
+                    .key("PK", AttributeValue::S(session_key(&session.key_name)))
+                    .key("SK", AttributeValue::N("0".to_string()))
+                    .update_expression("SET #pending_upload = :pending_upload")
+                    .expression_attribute_names("#pending_upload", PENDING_UPLOAD)
+                    .expression_attribute_values(
+                        ":pending_upload",
+                        AttributeValue::S(PENDING_UPLOAD.to_string()),
+                    );
+
+                self.retry
+                    .run("UpdateItem", || request.clone().send().map_err(aws_error))
+                    .await?;
+            }
+
+            let Some(sk) = entry.get("SK").cloned() else {
+                return Err(Error::internal("SK is not found in the pending upload"));
+            };
+            let request = self
+                .client
+                .delete_item()
+                .table_name(&self.table_name)
+                .key("PK", AttributeValue::S(PENDING_UPLOAD.to_string()))
+                .key("SK", sk);
+
+            self.retry
+                .run("DeleteItem", || request.clone().send().map_err(aws_error))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The indexes of this module
+pub(crate) fn upload_session_indexes() -> Vec<IndexDefinition> {
+    vec![IndexDefinition {
+        name: PENDING_UPLOAD_INDEX,
+        partition_key: (PENDING_UPLOAD, ScalarAttributeType::S),
+        sort_key: ("ExpiresAt", ScalarAttributeType::N),
+    }]
+}
+
+/// The partition key of the session
+fn session_key(key_name: &str) -> String {
+    format!("UploadSession#{key_name}")
+}
+
+fn upload_session_to_attributes(session: &UploadSession) -> HashMap<String, AttributeValue> {
+    let mut item = HashMap::from([
+        (
+            "KeyName".to_string(),
+            AttributeValue::S(session.key_name.to_string()),
+        ),
+        (
+            "CreatedAt".to_string(),
+            AttributeValue::N(session.created_at.to_string()),
+        ),
+        (
+            "ExpiresAt".to_string(),
+            AttributeValue::N(session.expires_at.to_string()),
+        ),
+        (
+            "State".to_string(),
+            AttributeValue::S(session.state.as_str().to_string()),
+        ),
+    ]);
+
+    let optional_attributes = [
+        (
+            "Uploader",
+            session
+                .uploader
+                .as_ref()
+                .map(|uploader| AttributeValue::S(uploader.to_string())),
+        ),
+        (
+            "ExpectedSize",
+            session
+                .expected_size
+                .map(|size| AttributeValue::N(size.to_string())),
+        ),
+        (
+            "ExpectedChecksum",
+            session
+                .expected_checksum
+                .as_ref()
+                .map(|checksum| AttributeValue::S(checksum.to_string())),
+        ),
+        (
+            "CompletedAt",
+            session
+                .completed_at
+                .map(|completed_at| AttributeValue::N(completed_at.to_string())),
+        ),
+    ];
+
+    for (name, attribute) in optional_attributes {
+        if let Some(attribute) = attribute {
+            item.insert(name.to_string(), attribute);
+        }
+    }
+
+    item
+}
+
+fn upload_session_from_attributes(
+    item: &HashMap<String, AttributeValue>,
+) -> Result<UploadSession, Error> {
+    let string = |name: &str| match item.get(name).map(|attribute| attribute.as_s()) {
+        None => Ok(None),
+        Some(Ok(value)) => Ok(Some(value.to_owned())),
+        Some(Err(_)) => Err(Error::internal(format!(
+            "{name} must be a string in the upload session"
+        ))),
+    };
+    let number = |name: &str| match item.get(name).map(|attribute| attribute.as_n()) {
+        None => Ok(None),
+        Some(Ok(value)) => match value.parse::<u128>() {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(Error::internal(format!(
+                "{name} must be a number in the upload session"
+            ))),
+        },
+        Some(Err(_)) => Err(Error::internal(format!(
+            "{name} must be a number in the upload session"
+        ))),
+    };
+    let required_string = |name: &str| match string(name)? {
+        Some(value) => Ok(value),
+        None => Err(Error::internal(format!(
+            "{name} is not found in the upload session"
+        ))),
+    };
+    let required_number = |name: &str| match number(name)? {
+        Some(value) => Ok(value),
+        None => Err(Error::internal(format!(
+            "{name} is not found in the upload session"
+        ))),
+    };
+
+    let expected_size = match item.get("ExpectedSize").map(|size| size.as_n()) {
+        None => None,
+        Some(Ok(size)) => match size.parse::<i64>() {
+            Ok(size) => Some(size),
+            Err(_) => {
+                return Err(Error::internal(
+                    "ExpectedSize must be a number in the upload session",
+                ))
+            }
+        },
+        Some(Err(_)) => {
+            return Err(Error::internal(
+                "ExpectedSize must be a number in the upload session",
+            ))
+        }
+    };
+
+    Ok(UploadSession {
+        key_name: required_string("KeyName")?,
+        uploader: string("Uploader")?,
+        expected_size,
+        expected_checksum: string("ExpectedChecksum")?,
+        created_at: required_number("CreatedAt")?,
+        expires_at: required_number("ExpiresAt")?,
+        state: UploadState::from_str(&required_string("State")?)?,
+        completed_at: number("CompletedAt")?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY_NAME: &str = "1984/04/04/1984-04-04-12-34-50.MOV";
+    const EXPIRES_IN: Duration = Duration::from_secs(60);
+
+    fn session(key_name: &str, created_at: u128) -> UploadSession {
+        UploadSession {
+            uploader: Some("grandma".to_string()),
+            expected_size: Some(1024),
+            expected_checksum: Some("checksum".to_string()),
+            ..UploadSession::new(key_name, EXPIRES_IN, Some(created_at)).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_complete_upload_session() {
+        // Arrange
+        let client = DynamoDbClient::new("test_complete_upload_session").await;
+        client
+            .put_upload_session(&session(KEY_NAME, 0))
+            .await
+            .unwrap();
+
+        // Act
+        let result = client
+            .complete_upload_session(
+                "/1984/4/4/1984-4-4-12-34-50.MOV",
+                Some(1024),
+                None,
+                Some(1_000),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Assert
+        assert_eq!(result.state, UploadState::Completed);
+        assert_eq!(result.completed_at, Some(1_000));
+        assert_eq!(
+            client.get_upload_session(KEY_NAME).await.unwrap(),
+            Some(result)
+        );
+        assert!(client
+            .get_stale_upload_sessions(Some(100_000))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_complete_upload_session_with_wrong_size() {
+        // Arrange
+        let client = DynamoDbClient::new("test_complete_upload_session_with_wrong_size").await;
+        client
+            .put_upload_session(&session(KEY_NAME, 0))
+            .await
+            .unwrap();
+
+        // Act
+        let result = client
+            .complete_upload_session(KEY_NAME, Some(1), Some("checksum"), Some(1_000))
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Assert
+        assert_eq!(result.state, UploadState::Failed);
+        assert_eq!(
+            client.get_stale_upload_sessions(Some(1_000)).await.unwrap(),
+            [result]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_complete_without_session() {
+        // Arrange
+        let client = DynamoDbClient::new("test_complete_without_session").await;
+
+        // Act
+        let result = client
+            .complete_upload_session(KEY_NAME, None, None, None)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(result, None);
+    }
+
+    #[tokio::test]
+    async fn test_get_stale_upload_sessions() {
+        // Arrange
+        let client = DynamoDbClient::new("test_get_stale_upload_sessions").await;
+        let old_session = session("1984/04/04/1984-04-04-12-34-51.MOV", 0);
+        let new_session = session(KEY_NAME, 100_000);
+        client.put_upload_session(&old_session).await.unwrap();
+        client.put_upload_session(&new_session).await.unwrap();
+
+        // Act
+        let result = client
+            .get_stale_upload_sessions(Some(100_000))
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0], old_session);
+
+        // Act
+        client
+            .delete_upload_session(&old_session.key_name)
+            .await
+            .unwrap();
+
+        // Assert
+        assert!(client
+            .get_stale_upload_sessions(Some(100_000))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sessions_in_the_same_second() {
+        // Arrange
+        let client = DynamoDbClient::new("test_sessions_in_the_same_second").await;
+        let video = session(KEY_NAME, 0);
+        let image = session("1984/04/04/1984-04-04-12-34-50.JPG", 0);
+        client.put_upload_session(&video).await.unwrap();
+        client.put_upload_session(&image).await.unwrap();
+
+        // Act
+        let completed = client
+            .complete_upload_session(&video.key_name, Some(1024), None, Some(1_000))
+            .await
+            .unwrap();
+
+        // Assert
+        assert!(completed.is_some());
+        assert_eq!(
+            client
+                .get_stale_upload_sessions(Some(100_000))
+                .await
+                .unwrap(),
+            [image]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_index_pending_upload_sessions() {
+        // Arrange
+        let client = DynamoDbClient::new("test_index_pending_upload_sessions").await;
+        let pending = session(KEY_NAME, 0);
+        let orphan = session("1984/04/04/1984-04-04-12-34-51.MOV", 0);
+        client.put_upload_session(&pending).await.unwrap();
+        for (session, unix_time) in [(&pending, 0), (&orphan, 1)] {
+            let mut item = upload_session_to_attributes(session);
+            item.insert(
+                "PK".to_string(),
+                AttributeValue::S(PENDING_UPLOAD.to_string()),
+            );
+            item.insert("SK".to_string(), AttributeValue::N(unix_time.to_string()));
+            client
+                .client
+                .put_item()
+                .table_name(&client.table_name)
+                .set_item(Some(item))
+                .send()
+                .await
+                .unwrap();
+        }
+        client
+            .client
+            .update_item()
+            .table_name(&client.table_name)
+            .key("PK", AttributeValue::S(session_key(KEY_NAME)))
+            .key("SK", AttributeValue::N("0".to_string()))
+            .update_expression("REMOVE PendingUpload")
+            .send()
+            .await
+            .unwrap();
+
+        // Act
+        client.index_pending_upload_sessions().await.unwrap();
+
+        // Assert
+        assert_eq!(
+            client
+                .get_stale_upload_sessions(Some(100_000))
+                .await
+                .unwrap(),
+            [pending]
+        );
+        assert!(client
+            .query_partition(PENDING_UPLOAD)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_state_of_upload() {
+        // Arrange
+        let session = session(KEY_NAME, 0);
+
+        // Assert
+        assert_eq!(
+            session.state_of(Some(1024), Some("checksum")),
+            UploadState::Completed
+        );
+        assert_eq!(session.state_of(None, None), UploadState::Completed);
+        assert_eq!(session.state_of(Some(1), None), UploadState::Failed);
+        assert_eq!(session.state_of(None, Some("other")), UploadState::Failed);
+    }
+
+    #[test]
+    fn test_is_stale() {
+        // Arrange
+        let session = session(KEY_NAME, 0);
+
+        // Assert
+        assert!(!session.is_stale(EXPIRES_IN.as_millis() - 1));
+        assert!(session.is_stale(EXPIRES_IN.as_millis()));
+    }
+}
@@ -0,0 +1,701 @@
+//! The optimistic concurrency of the collection items
+//! A collection item has a version that is incremented by every write.
+//! A write is conditional on the version that the item was read with, so it doesn't overwrite the changes made in between.
+//! <https://hitohata.github.io/ExogenesisEnsemble-Part3-Redemption/project/docs/technical-information/DynamoDB-Definition#version>
+
+use crate::audit::{self, AuditAction};
+use crate::dynamodb::attribute_json::item_to_json;
+use crate::dynamodb::client::{
+    collection_item_from_attributes, collection_item_to_attributes, get_now, DynamoDbClient,
+};
+use crate::dynamodb::entities::collection::{CollectionItem, Resolution};
+use crate::dynamodb::protection::{
+    with_unprotected_values, LEGAL_HOLD, RETAIN_UNTIL, UNPROTECTED_CONDITION,
+};
+use crate::dynamodb::storage::{StorageState, STORAGE_STATE, VAULT};
+use crate::dynamodb::trash::TRASHED_AT;
+use crate::error::aws_service_error;
+use aws_sdk_dynamodb::operation::put_item::PutItemError;
+use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue, ReturnValuesOnConditionCheckFailure};
+use shared::error::Error;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use time_file_name::file_datetime::PathDateTime;
+
+/// The attribute of the version
+pub(crate) const VERSION: &str = "Version";
+
+/// The error of a conditional write
+#[derive(Debug, PartialEq)]
+pub enum WriteError {
+    /// The item has been changed since it was read
+    Conflict {
+        key_name: String,
+        /// The version that the item was read with
+        expected: u64,
+        /// The version that is saved. None if the item doesn't exist.
+        actual: Option<u64>,
+    },
+    Other(Error),
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::Conflict {
+                key_name,
+                expected,
+                actual: Some(actual),
+            } => write!(
+                f,
+                "{key_name} has been changed: the version is {actual}, but {expected} is expected"
+            ),
+            WriteError::Conflict {
+                key_name,
+                expected,
+                actual: None,
+            } => write!(
+                f,
+                "{key_name} is not found, but the version {expected} is expected"
+            ),
+            WriteError::Other(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+impl From<Error> for WriteError {
+    fn from(error: Error) -> Self {
+        WriteError::Other(error)
+    }
+}
+
+/// The conflict is [ErrorKind::Conflict]
+impl From<WriteError> for Error {
+    fn from(error: WriteError) -> Self {
+        match error {
+            WriteError::Other(error) => error,
+            conflict => Error::conflict(conflict.to_string()),
+        }
+    }
+}
+
+/// The attributes to change
+/// The attributes that are None are kept as they are.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CollectionUpdate {
+    pub is_unzipped: Option<bool>,
+    /// The vault that the object is moved to. An empty string means the object is not archived.
+    pub vault: Option<String>,
+    pub size: Option<i64>,
+    pub duration: Option<i64>,
+    pub resolution: Option<Resolution>,
+    pub content_type: Option<String>,
+    pub checksum: Option<String>,
+    pub uploader: Option<String>,
+    pub device: Option<String>,
+}
+
+impl CollectionUpdate {
+    /// The collection item that this update is applied to
+    /// The version is not changed.
+    pub fn apply(&self, collection: &CollectionItem) -> CollectionItem {
+        let collection = collection.clone();
+
+        CollectionItem {
+            is_unzipped: self.is_unzipped.unwrap_or(collection.is_unzipped),
+            vault: self.vault.clone().unwrap_or(collection.vault),
+            size: self.size.or(collection.size),
+            duration: self.duration.or(collection.duration),
+            resolution: self.resolution.or(collection.resolution),
+            content_type: self.content_type.clone().or(collection.content_type),
+            checksum: self.checksum.clone().or(collection.checksum),
+            uploader: self.uploader.clone().or(collection.uploader),
+            device: self.device.clone().or(collection.device),
+            ..collection
+        }
+    }
+
+    /// (attribute name, value) to set
+    fn attributes(&self) -> Vec<(&'static str, AttributeValue)> {
+        let number = |n: Option<i64>| n.map(|n| AttributeValue::N(n.to_string()));
+        let string = |s: &Option<String>| s.as_ref().map(|s| AttributeValue::S(s.to_string()));
+        let vault = self.vault.as_ref().filter(|vault| !vault.is_empty());
+        let storage_state = self.vault.as_ref().map(|vault| match vault.is_empty() {
+            true => StorageState::Standard,
+            false => StorageState::Archived,
+        });
+
+        [
+            ("IsUnzipped", self.is_unzipped.map(AttributeValue::Bool)),
+            (
+                VAULT,
+                vault.map(|vault| AttributeValue::S(vault.to_string())),
+            ),
+            (
+                STORAGE_STATE,
+                storage_state.map(|state| AttributeValue::S(state.to_string())),
+            ),
+            ("Size", number(self.size)),
+            ("Duration", number(self.duration)),
+            (
+                "Width",
+                number(self.resolution.map(|resolution| resolution.width.into())),
+            ),
+            (
+                "Height",
+                number(self.resolution.map(|resolution| resolution.height.into())),
+            ),
+            ("ContentType", string(&self.content_type)),
+            ("Checksum", string(&self.checksum)),
+            ("Uploader", string(&self.uploader)),
+            ("Device", string(&self.device)),
+        ]
+        .into_iter()
+        .filter_map(|(name, attribute)| attribute.map(|attribute| (name, attribute)))
+        .collect()
+    }
+
+    /// attribute names to remove
+    fn removed_attributes(&self) -> Vec<&'static str> {
+        match self.vault.as_deref() {
+            Some("") => vec![VAULT],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl DynamoDbClient {
+    /// put a collection item if its version is the saved one
+    /// The version of a new item is 0, so it is saved only when the item doesn't exist.
+    /// The item in the trash cannot be replaced, and the active protection cannot be removed or shortened by the put.
+    /// Returns the saved item that has the incremented version.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = %collection.key_name),
+        err(level = "warn")
+    )]
+    pub async fn put_collection_item(
+        &self,
+        collection: &CollectionItem,
+    ) -> Result<CollectionItem, WriteError> {
+        let saved = CollectionItem {
+            version: collection.version + 1,
+            ..collection.clone()
+        };
+        let attributes = collection_item_to_attributes(&saved)?;
+        let now = get_now(None)?;
+
+        // the saved retention must not be active after the new one, and the hold must be kept
+        let mut condition = format!(
+            "({}) AND attribute_not_exists(#trashed_at) AND (attribute_not_exists(#retain_until) OR #retain_until <= :retain_until)",
+            version_condition(collection.version)
+        );
+        let retain_until = collection
+            .protection
+            .retain_until
+            .unwrap_or_default()
+            .max(now);
+        let mut request = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(attributes.clone()))
+            .expression_attribute_names("#version", VERSION)
+            .expression_attribute_names("#trashed_at", TRASHED_AT)
+            .expression_attribute_names("#retain_until", RETAIN_UNTIL)
+            .set_expression_attribute_values(version_values(collection.version))
+            .expression_attribute_values(
+                ":retain_until",
+                AttributeValue::N(retain_until.to_string()),
+            )
+            .return_values(ReturnValue::AllOld)
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld);
+        if !collection.protection.legal_hold {
+            condition.push_str(" AND attribute_not_exists(#legal_hold)");
+            request = request.expression_attribute_names("#legal_hold", LEGAL_HOLD);
+        }
+        let request = request.condition_expression(condition);
+
+        // not retried, since the replay of the applied write fails by the version
+        let old_attributes = match request.send().await {
+            Ok(output) => output.attributes,
+            Err(e) => {
+                return Err(match e.into_service_error() {
+                    PutItemError::ConditionalCheckFailedException(exception) => {
+                        put_condition_error(collection, exception.item, now)
+                    }
+                    service_error => WriteError::Other(aws_service_error(service_error)),
+                })
+            }
+        };
+
+        // the item in the trash is not counted in the statistics
+        let old_collection = match &old_attributes {
+            Some(old_attributes) if !old_attributes.contains_key(TRASHED_AT) => {
+                Some(collection_item_from_attributes(old_attributes)?)
+            }
+            _ => None,
+        };
+
+        self.update_stats(old_collection.as_ref(), Some(&saved))
+            .await?;
+
+        audit::record(
+            &self.auditor,
+            AuditAction::PutCollection,
+            &saved.key_name,
+            old_attributes
+                .as_ref()
+                .map(|old_attributes| item_to_json(old_attributes).map(|json| json.to_string()))
+                .transpose()?,
+            Some(item_to_json(&attributes)?.to_string()),
+        )
+        .await?;
+
+        Ok(saved)
+    }
+
+    /// Change the attributes of the collection item, and keep the others.
+    /// If the expected version is given, the item is changed only when it is the saved one.
+    /// The item in the trash cannot be changed, and the retained or held item cannot be moved to another vault.
+    /// Returns the changed item that has the incremented version.
+    #[tracing::instrument(
+        skip_all,
+        fields(table = %self.table_name, key = key_name, expected_version = ?expected_version),
+        err(level = "warn")
+    )]
+    pub async fn update_collection_item(
+        &self,
+        key_name: &str,
+        update: &CollectionUpdate,
+        expected_version: Option<u64>,
+    ) -> Result<CollectionItem, WriteError> {
+        let path_date_time = PathDateTime::parse(key_name).map_err(Error::invalid_input)?;
+
+        let mut set_expressions = vec!["UpdatedAt = :updated_at".to_string()];
+        let mut request = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(path_date_time.year.to_string()))
+            .key(
+                "SK",
+                AttributeValue::N(path_date_time.unix_time.to_string()),
+            )
+            .expression_attribute_names("#version", VERSION)
+            .expression_attribute_names("#trashed_at", TRASHED_AT)
+            .expression_attribute_values(
+                ":updated_at",
+                AttributeValue::N(get_now(None)?.to_string()),
+            )
+            .expression_attribute_values(":one", AttributeValue::N("1".to_string()));
+
+        for (name, attribute) in update.attributes() {
+            set_expressions.push(format!("#{name} = :{name}"));
+            request = request
+                .expression_attribute_names(format!("#{name}"), name)
+                .expression_attribute_values(format!(":{name}"), attribute);
+        }
+
+        let mut remove_expressions = Vec::new();
+        for name in update.removed_attributes() {
+            remove_expressions.push(format!("#{name}"));
+            request = request.expression_attribute_names(format!("#{name}"), name);
+        }
+        let remove_expression = match remove_expressions.is_empty() {
+            true => String::new(),
+            false => format!("REMOVE {}", remove_expressions.join(", ")),
+        };
+
+        let mut condition =
+            "attribute_exists(PK) AND attribute_not_exists(#trashed_at)".to_string();
+        // the protected item cannot be moved to another vault
+        let now = get_now(None)?;
+        if update.vault.is_some() {
+            condition = format!("{condition} AND {UNPROTECTED_CONDITION}");
+            request = with_unprotected_values(request, now);
+        }
+        if let Some(expected_version) = expected_version {
+            condition = format!("{condition} AND ({})", version_condition(expected_version));
+            for (name, value) in version_values(expected_version).unwrap_or_default() {
+                request = request.expression_attribute_values(name, value);
+            }
+        }
+
+        let request = request
+            .update_expression(format!(
+                "SET {} {remove_expression} ADD #version :one",
+                set_expressions.join(", ")
+            ))
+            .condition_expression(condition)
+            .return_values(ReturnValue::AllOld)
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld);
+
+        // not retried, since the replay of the applied update fails by the version
+        let old_attributes = match request.send().await {
+            Ok(output) => match output.attributes {
+                Some(old_attributes) => old_attributes,
+                None => {
+                    return Err(WriteError::Other(Error::internal(
+                        "The old item is not returned",
+                    )))
+                }
+            },
+            Err(e) => {
+                return Err(match e.into_service_error() {
+                    UpdateItemError::ConditionalCheckFailedException(exception) => {
+                        match exception.item {
+                            Some(item) if !item.contains_key(TRASHED_AT) => {
+                                match protection_error(key_name, &item, update, now) {
+                                    Some(error) => error,
+                                    None => conflict(
+                                        key_name,
+                                        expected_version.unwrap_or_default(),
+                                        Some(item),
+                                    ),
+                                }
+                            }
+                            _ => WriteError::Other(Error::not_found(format!(
+                                "{key_name} is not found or in the trash"
+                            ))),
+                        }
+                    }
+                    service_error => WriteError::Other(aws_service_error(service_error)),
+                })
+            }
+        };
+
+        let old_collection = collection_item_from_attributes(&old_attributes)?;
+        let collection = CollectionItem {
+            version: old_collection.version + 1,
+            ..update.apply(&old_collection)
+        };
+
+        self.update_stats(Some(&old_collection), Some(&collection))
+            .await?;
+
+        audit::record(
+            &self.auditor,
+            AuditAction::UpdateCollection,
+            &collection.key_name,
+            Some(item_to_json(&old_attributes)?.to_string()),
+            Some(item_to_json(&collection_item_to_attributes(&collection)?)?.to_string()),
+        )
+        .await?;
+
+        Ok(collection)
+    }
+}
+
+/// The error if the update moves the protected item
+fn protection_error(
+    key_name: &str,
+    item: &HashMap<String, AttributeValue>,
+    update: &CollectionUpdate,
+    now: u128,
+) -> Option<WriteError> {
+    // only the vault moves the item
+    update.vault.as_ref()?;
+
+    match collection_item_from_attributes(item) {
+        Ok(collection) => collection
+            .protection
+            .check(key_name, now)
+            .err()
+            .map(WriteError::Other),
+        Err(e) => Some(WriteError::Other(e)),
+    }
+}
+
+/// The error of the put whose condition failed
+/// The version is told first, and then the trash and the protection of the saved item.
+fn put_condition_error(
+    collection: &CollectionItem,
+    item: Option<HashMap<String, AttributeValue>>,
+    now: u128,
+) -> WriteError {
+    let key_name = &collection.key_name;
+    let item = match item {
+        Some(item) if saved_version(&item) == collection.version => item,
+        item => return conflict(key_name, collection.version, item),
+    };
+
+    if item.contains_key(TRASHED_AT) {
+        return WriteError::Other(Error::conflict(format!("{key_name} is in the trash")));
+    }
+
+    match collection_item_from_attributes(&item) {
+        Ok(saved) => WriteError::Other(match saved.protection.check(key_name, now) {
+            Err(e) => Error::conflict(format!(
+                "The protection can't be removed by the put: {}",
+                e.message()
+            )),
+            Ok(()) => Error::conflict(format!("{key_name} failed the condition of the put")),
+        }),
+        Err(e) => WriteError::Other(e),
+    }
+}
+
+/// The version of the saved item
+/// The item that doesn't have the version is considered as the version 0.
+fn saved_version(item: &HashMap<String, AttributeValue>) -> u64 {
+    match item.get(VERSION).map(|version| version.as_n()) {
+        Some(Ok(version)) => version.parse::<u64>().unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+/// The condition that the saved version is the expected one
+/// The item that doesn't have the version is considered as the version 0.
+fn version_condition(expected: u64) -> &'static str {
+    match expected {
+        0 => "attribute_not_exists(#version)",
+        _ => "#version = :expected_version",
+    }
+}
+
+fn version_values(expected: u64) -> Option<HashMap<String, AttributeValue>> {
+    match expected {
+        0 => None,
+        _ => Some(HashMap::from([(
+            ":expected_version".to_string(),
+            AttributeValue::N(expected.to_string()),
+        )])),
+    }
+}
+
+/// The conflict error from the item that failed the condition
+fn conflict(
+    key_name: &str,
+    expected: u64,
+    item: Option<HashMap<String, AttributeValue>>,
+) -> WriteError {
+    let actual = item.map(|item| saved_version(&item));
+
+    WriteError::Conflict {
+        key_name: key_name.to_string(),
+        expected,
+        actual,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dynamodb::client::DynamoClientTrait;
+    use crate::protection::Protection;
+    use shared::error::ErrorKind;
+
+    const KEY_NAME: &str = "1984/04/04/1984-04-04-12-34-50.MOV";
+
+    #[tokio::test]
+    async fn test_put_collection_item_increments_version() {
+        // Arrange
+        let client = DynamoDbClient::new("test_put_collection_item_increments_version").await;
+        let saved = client
+            .put_collection_item(&CollectionItem::dummy_object(KEY_NAME))
+            .await
+            .unwrap();
+
+        // Act
+        let result = client.put_collection_item(&saved).await.unwrap();
+
+        // Assert
+        assert_eq!(saved.version, 1);
+        assert_eq!(result.version, 2);
+        assert_eq!(
+            client.get_collection_item(KEY_NAME).await.unwrap(),
+            Some(result)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_put_stale_collection_item() {
+        // Arrange
+        let client = DynamoDbClient::new("test_put_stale_collection_item").await;
+        let saved = client
+            .put_collection_item(&CollectionItem::dummy_object(KEY_NAME))
+            .await
+            .unwrap();
+        client
+            .update_collection_item(
+                KEY_NAME,
+                &CollectionUpdate {
+                    is_unzipped: Some(true),
+                    ..CollectionUpdate::default()
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        // Act
+        let retried = client
+            .put_collection_item(&CollectionItem::dummy_object(KEY_NAME))
+            .await;
+        let stale = client.put_collection_item(&saved).await;
+
+        // Assert
+        let conflict = |expected| WriteError::Conflict {
+            key_name: KEY_NAME.to_string(),
+            expected,
+            actual: Some(2),
+        };
+        assert_eq!(retried, Err(conflict(0)));
+        assert_eq!(stale, Err(conflict(1)));
+        let result = client.get_collection_item(KEY_NAME).await.unwrap().unwrap();
+        assert!(result.is_unzipped);
+    }
+
+    #[tokio::test]
+    async fn test_put_collection_item_keeps_protection_and_trash() {
+        // Arrange
+        let client =
+            DynamoDbClient::new("test_put_collection_item_keeps_protection_and_trash").await;
+        let retained = "1984/04/04/1984-04-04-12-34-51.MOV";
+        client
+            .put_collection_items(&vec![
+                CollectionItem::dummy_object(KEY_NAME),
+                CollectionItem::dummy_object(retained),
+            ])
+            .await
+            .unwrap();
+        let held = client.set_legal_hold(KEY_NAME, true).await.unwrap();
+        let retained = client.set_retention(retained, u128::MAX / 2).await.unwrap();
+        let unprotected = |collection: &CollectionItem| CollectionItem {
+            protection: Protection::default(),
+            ..collection.clone()
+        };
+
+        // Act
+        let dropped_hold = client.put_collection_item(&unprotected(&held)).await;
+        let dropped_retention = client.put_collection_item(&unprotected(&retained)).await;
+        let kept = client.put_collection_item(&retained).await;
+        let released = client.set_legal_hold(KEY_NAME, false).await.unwrap();
+        client
+            .trash_collection_item(KEY_NAME, "user", None)
+            .await
+            .unwrap();
+        // the trash increments the version
+        let replaced_trash = client
+            .put_collection_item(&CollectionItem {
+                version: released.version + 1,
+                ..CollectionItem::dummy_object(KEY_NAME)
+            })
+            .await;
+
+        // Assert
+        for result in [dropped_hold, dropped_retention, replaced_trash] {
+            match result {
+                Err(WriteError::Other(e)) => assert_eq!(e.kind(), ErrorKind::Conflict),
+                result => panic!("the put is not rejected: {result:?}"),
+            }
+        }
+        assert_eq!(kept.unwrap().protection, retained.protection);
+        assert!(client
+            .get_collection_item(KEY_NAME)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_collection_item() {
+        // Arrange
+        let client = DynamoDbClient::new("test_update_collection_item").await;
+        let saved = client
+            .put_collection_item(&CollectionItem {
+                uploader: Some("grandma".to_string()),
+                ..CollectionItem::dummy_object(KEY_NAME)
+            })
+            .await
+            .unwrap();
+        let update = CollectionUpdate {
+            size: Some(1024),
+            device: Some("iPhone".to_string()),
+            ..CollectionUpdate::default()
+        };
+
+        // Act
+        let result = client
+            .update_collection_item(KEY_NAME, &update, Some(saved.version))
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(result.version, 2);
+        assert_eq!(result.size, Some(1024));
+        assert_eq!(result.device, Some("iPhone".to_string()));
+        assert_eq!(result.uploader, Some("grandma".to_string()));
+        assert_eq!(
+            client.get_collection_item(KEY_NAME).await.unwrap(),
+            Some(result)
+        );
+        assert_eq!(
+            client.get_year_stats(1984).await.unwrap().total.total_bytes,
+            1024
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_collection_item_with_stale_version() {
+        // Arrange
+        let client = DynamoDbClient::new("test_update_collection_item_with_stale_version").await;
+        client
+            .put_collection_items(&vec![CollectionItem::dummy_object(KEY_NAME)])
+            .await
+            .unwrap();
+
+        // Act
+        let stale = client
+            .update_collection_item(KEY_NAME, &CollectionUpdate::default(), Some(0))
+            .await;
+        let not_found = client
+            .update_collection_item(
+                "1985/04/04/1985-04-04-12-34-50.MOV",
+                &CollectionUpdate::default(),
+                None,
+            )
+            .await;
+
+        // Assert
+        assert_eq!(
+            stale,
+            Err(WriteError::Conflict {
+                key_name: KEY_NAME.to_string(),
+                expected: 0,
+                actual: Some(1),
+            })
+        );
+        assert!(matches!(not_found, Err(WriteError::Other(_))));
+    }
+
+    #[test]
+    fn test_apply_keeps_unset_attributes() {
+        // Arrange
+        let collection = CollectionItem {
+            checksum: Some("checksum".to_string()),
+            version: 3,
+            ..CollectionItem::dummy_object(KEY_NAME)
+        };
+        let update = CollectionUpdate {
+            is_unzipped: Some(true),
+            ..CollectionUpdate::default()
+        };
+
+        // Act
+        let result = update.apply(&collection);
+
+        // Assert
+        assert_eq!(
+            result,
+            CollectionItem {
+                is_unzipped: true,
+                ..collection
+            }
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! The classification of the AWS SDK errors into [shared::error::ErrorKind]
+
+use aws_smithy_runtime_api::client::result::SdkError;
+use aws_smithy_types::error::metadata::ProvideErrorMetadata;
+use shared::error::{Error, ErrorKind};
+
+/// The error codes that tell the request is throttled
+const THROTTLED_CODES: [&str; 5] = [
+    "ThrottlingException",
+    "Throttling",
+    "ProvisionedThroughputExceededException",
+    "RequestLimitExceeded",
+    "SlowDown",
+];
+
+/// The error codes that tell the service fails
+const UNAVAILABLE_CODES: [&str; 5] = [
+    "InternalServerError",
+    "InternalError",
+    "ServiceUnavailable",
+    "ServiceUnavailableException",
+    "RequestTimeout",
+];
+
+/// The error codes that tell the target doesn't exist
+/// `ResourceNotFoundException` is not here, since a missing table is a misconfiguration, not a missing item.
+const NOT_FOUND_CODES: [&str; 3] = ["NoSuchKey", "NoSuchBucket", "NotFound"];
+
+/// The error codes that tell the request is invalid
+const INVALID_INPUT_CODES: [&str; 2] = ["ValidationException", "InvalidRequest"];
+
+/// The error codes that tell the condition of the write is not met
+const CONFLICT_CODES: [&str; 3] = [
+    "ConditionalCheckFailedException",
+    "TransactionCanceledException",
+    "PreconditionFailed",
+];
+
+/// Classify the kind by the error code
+/// The error without a known code is not retried, e.g. a response that can't be parsed.
+fn kind_of(code: Option<&str>) -> ErrorKind {
+    match code {
+        None => ErrorKind::Internal,
+        Some(code) if THROTTLED_CODES.contains(&code) => ErrorKind::Throttled,
+        Some(code) if UNAVAILABLE_CODES.contains(&code) => ErrorKind::UpstreamUnavailable,
+        Some(code) if NOT_FOUND_CODES.contains(&code) => ErrorKind::NotFound,
+        Some(code) if INVALID_INPUT_CODES.contains(&code) => ErrorKind::InvalidInput,
+        Some(code) if CONFLICT_CODES.contains(&code) => ErrorKind::Conflict,
+        Some(_) => ErrorKind::Internal,
+    }
+}
+
+/// Convert the SDK error, keeping it as the source
+/// The timeout and the dispatch failure are the only errors without the response that are transient.
+pub(crate) fn aws_error<E, R>(error: SdkError<E, R>) -> Error
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    R: std::fmt::Debug + Send + Sync + 'static,
+{
+    let kind = match &error {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) => ErrorKind::UpstreamUnavailable,
+        _ => kind_of(error.code()),
+    };
+    error_of(kind, error)
+}
+
+/// Convert the service error, which is taken out of the SDK error, keeping it as the source
+#[cfg(feature = "db")]
+pub(crate) fn aws_service_error<E>(error: E) -> Error
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+{
+    error_of(kind_of(error.code()), error)
+}
+
+/// The code and the kind are recorded in the span of the call.
+fn error_of<E>(kind: ErrorKind, error: E) -> Error
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+{
+    let message = match error.message() {
+        Some(message) => format!("{}: {message}", error.code().unwrap_or_default()),
+        None => error.to_string(),
+    };
+    tracing::debug!(code = error.code(), error_kind = ?kind, "{message}");
+    Error::new(kind, message).with_source(error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aws_smithy_runtime_api::client::result::ConnectorError;
+    use aws_smithy_types::error::ErrorMetadata;
+
+    #[test]
+    fn test_kind_of() {
+        // Act
+        let result = [
+            kind_of(Some("ProvisionedThroughputExceededException")),
+            kind_of(Some("SlowDown")),
+            kind_of(Some("NoSuchKey")),
+            kind_of(Some("ConditionalCheckFailedException")),
+            kind_of(Some("ValidationException")),
+            kind_of(Some("ServiceUnavailable")),
+            kind_of(Some("ResourceNotFoundException")),
+            kind_of(None),
+            kind_of(Some("AccessDenied")),
+        ];
+
+        // Assert
+        assert_eq!(
+            result,
+            [
+                ErrorKind::Throttled,
+                ErrorKind::Throttled,
+                ErrorKind::NotFound,
+                ErrorKind::Conflict,
+                ErrorKind::InvalidInput,
+                ErrorKind::UpstreamUnavailable,
+                ErrorKind::Internal,
+                ErrorKind::Internal,
+                ErrorKind::Internal,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aws_error_without_response() {
+        // Act
+        let result = [
+            aws_error(SdkError::<ErrorMetadata, ()>::timeout_error("timed out")),
+            aws_error(SdkError::<ErrorMetadata, ()>::dispatch_failure(
+                ConnectorError::io("connection reset".into()),
+            )),
+            aws_error(SdkError::<ErrorMetadata, ()>::construction_failure(
+                "invalid request",
+            )),
+        ];
+
+        // Assert
+        assert_eq!(
+            result.map(|error| error.kind()),
+            [
+                ErrorKind::UpstreamUnavailable,
+                ErrorKind::UpstreamUnavailable,
+                ErrorKind::Internal,
+            ]
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! The in-process fakes of S3 and DynamoDB
+//! The fake is the HTTP connector of the AWS SDK, so the clients send the same requests as they do to AWS,
+//! and the tests don't need LocalStack or DynamoDB Local.
+//! It implements the operations that the clients of this crate call, and the others fail.
+//! The clients of the environment use [FakeAws::shared] in the tests of this crate and with the `fake` feature.
+
+#[cfg(feature = "db")]
+mod dynamodb;
+#[cfg(feature = "db")]
+mod expression;
+#[cfg(feature = "standard-storage")]
+mod s3;
+
+#[cfg(all(test, feature = "standard-storage"))]
+pub(crate) use s3::SEEDED_BODY;
+
+use aws_smithy_runtime_api::client::http::{
+    http_client_fn, HttpConnector, HttpConnectorFuture, SharedHttpClient, SharedHttpConnector,
+};
+use aws_smithy_runtime_api::client::orchestrator::{HttpRequest, HttpResponse};
+use aws_smithy_runtime_api::client::result::ConnectorError;
+use aws_smithy_runtime_api::http::StatusCode;
+use aws_smithy_types::body::SdkBody;
+use aws_smithy_types::byte_stream::ByteStream;
+use shared::lock_ignoring_poison;
+use std::sync::{Arc, Mutex, OnceLock};
+
+type FakeResponse = HttpResponse;
+
+/// The buckets and the tables of the fake
+/// The clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct FakeAws {
+    #[cfg(feature = "standard-storage")]
+    buckets: Arc<Mutex<s3::Buckets>>,
+    #[cfg(feature = "db")]
+    tables: Arc<Mutex<dynamodb::Tables>>,
+}
+
+impl FakeAws {
+    /// Create the fake that has no buckets and no tables
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The fake that the clients of the environment use
+    /// The bucket of the configuration, if it is set, has the objects of `storage/scripts/ready.d/s3.sh`.
+    pub fn shared() -> &'static Self {
+        static SHARED: OnceLock<FakeAws> = OnceLock::new();
+
+        SHARED.get_or_init(|| {
+            let fake = Self::new();
+            #[cfg(feature = "standard-storage")]
+            if let Ok(bucket_name) = crate::s3::environment_value::standard_bucked_name() {
+                fake.create_seeded_bucket(bucket_name);
+            }
+            fake
+        })
+    }
+
+    /// Create the bucket that has the objects of `storage/scripts/ready.d/s3.sh`
+    #[cfg(feature = "standard-storage")]
+    pub fn create_seeded_bucket(&self, bucket_name: &str) {
+        lock_ignoring_poison(&self.buckets).seed(bucket_name);
+    }
+
+    /// The HTTP client that sends the requests to this fake
+    pub fn http_client(&self) -> SharedHttpClient {
+        let connector = SharedHttpConnector::new(self.clone());
+        http_client_fn(move |_, _| connector.clone())
+    }
+
+    /// The DynamoDB requests have the operation in the `X-Amz-Target`, and the others are S3 requests.
+    fn respond(&self, request: &HttpRequest, body: &[u8]) -> FakeResponse {
+        #[cfg(feature = "db")]
+        if let Some(target) = request.headers().get("x-amz-target") {
+            return dynamodb::respond(&mut lock_ignoring_poison(&self.tables), target, body);
+        }
+
+        #[cfg(feature = "standard-storage")]
+        return s3::respond(&mut lock_ignoring_poison(&self.buckets), request, body);
+
+        #[cfg(not(feature = "standard-storage"))]
+        response(
+            501,
+            &[],
+            format!(
+                "{} {} is not implemented by the fake",
+                request.method(),
+                request.uri()
+            ),
+        )
+    }
+}
+
+impl HttpConnector for FakeAws {
+    fn call(&self, mut request: HttpRequest) -> HttpConnectorFuture {
+        let fake = self.clone();
+
+        HttpConnectorFuture::new(async move {
+            let body = ByteStream::new(request.take_body())
+                .collect()
+                .await
+                .map_err(|e| ConnectorError::io(e.into()))?
+                .into_bytes();
+
+            Ok(fake.respond(&request, &body))
+        })
+    }
+}
+
+fn response(status: u16, headers: &[(&str, String)], body: impl Into<SdkBody>) -> FakeResponse {
+    let status = StatusCode::try_from(status).expect("the status is valid");
+    let mut response = HttpResponse::new(status, body.into());
+    for (name, value) in headers {
+        response
+            .headers_mut()
+            .insert(name.to_string(), value.clone());
+    }
+    response.headers_mut().insert("x-amz-request-id", "fake");
+    response
+}
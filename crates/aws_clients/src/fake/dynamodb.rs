@@ -0,0 +1,972 @@
+//! The fake of the DynamoDB JSON API
+//! The operation is the `X-Amz-Target` header, and the request and the response are the JSON of the wire.
+//! A table is active as soon as it is created, the indexes project all the attributes,
+//! and the TTL is recorded but doesn't expire the items.
+
+use super::expression::{compare, parse_projection, Condition, Item, Placeholders, Update};
+use super::{response, FakeResponse};
+use serde_json::{json, Map, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TARGET_PREFIX: &str = "DynamoDB_20120810.";
+
+/// The items of a BatchWriteItem
+const BATCH_WRITE_LIMIT: usize = 25;
+
+/// The keys of a BatchGetItem
+const BATCH_GET_LIMIT: usize = 100;
+
+/// The items of a TransactWriteItems
+const TRANSACTION_LIMIT: usize = 100;
+
+/// The tables by the name
+#[derive(Debug, Default)]
+pub(super) struct Tables(BTreeMap<String, Table>);
+
+/// The error response
+#[derive(Debug)]
+struct Failure {
+    status: u16,
+    code: &'static str,
+    message: String,
+    details: Map<String, Value>,
+}
+
+impl Failure {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status: 400,
+            code,
+            message: message.into(),
+            details: Map::new(),
+        }
+    }
+
+    fn conditional_check_failed(item: Option<Item>) -> Self {
+        let mut failure = Self::new(
+            "ConditionalCheckFailedException",
+            "The conditional request failed",
+        );
+        if let Some(item) = item {
+            failure
+                .details
+                .insert("Item".to_string(), Value::Object(item));
+        }
+        failure
+    }
+
+    fn response(self) -> FakeResponse {
+        let mut body = self.details;
+        body.insert(
+            "__type".to_string(),
+            Value::String(format!("com.amazonaws.dynamodb.v20120810#{}", self.code)),
+        );
+        body.insert("message".to_string(), Value::String(self.message));
+
+        response(
+            self.status,
+            &json_headers(),
+            Value::Object(body).to_string(),
+        )
+    }
+}
+
+fn validation(message: impl Into<String>) -> Failure {
+    Failure::new("ValidationException", message)
+}
+
+fn json_headers() -> [(&'static str, String); 1] {
+    [("Content-Type", "application/x-amz-json-1.0".to_string())]
+}
+
+/// The position of an item in a table or an index, which orders the items as DynamoDB does
+#[derive(Debug, Clone)]
+struct Position(Vec<Value>);
+
+impl Ord for Position {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(left, right)| compare(left, right).unwrap_or(Ordering::Equal))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| self.0.len().cmp(&other.0.len()))
+    }
+}
+
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Position {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Position {}
+
+#[derive(Debug, Clone)]
+struct KeySchema {
+    partition_key: String,
+    sort_key: Option<String>,
+}
+
+impl KeySchema {
+    fn parse(key_schema: &Value) -> Result<Self, Failure> {
+        let mut partition_key = None;
+        let mut sort_key = None;
+        for element in key_schema.as_array().into_iter().flatten() {
+            let name = element["AttributeName"].as_str().map(str::to_string);
+            match element["KeyType"].as_str() {
+                Some("HASH") => partition_key = name,
+                Some("RANGE") => sort_key = name,
+                _ => return Err(validation(format!("Invalid key schema element: {element}"))),
+            }
+        }
+
+        match partition_key {
+            Some(partition_key) => Ok(Self {
+                partition_key,
+                sort_key,
+            }),
+            None => Err(validation("The key schema must have the HASH key")),
+        }
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.partition_key.as_str()).chain(self.sort_key.as_deref())
+    }
+
+    fn to_json(&self) -> Value {
+        let mut key_schema = vec![json!({"AttributeName": self.partition_key, "KeyType": "HASH"})];
+        if let Some(sort_key) = &self.sort_key {
+            key_schema.push(json!({"AttributeName": sort_key, "KeyType": "RANGE"}));
+        }
+        Value::Array(key_schema)
+    }
+
+    /// The key values of the item, or None if the item doesn't have them
+    fn values(&self, item: &Item) -> Option<Vec<Value>> {
+        self.names().map(|name| item.get(name).cloned()).collect()
+    }
+}
+
+#[derive(Debug)]
+struct Index {
+    name: String,
+    key_schema: KeySchema,
+    projection: Value,
+}
+
+impl Index {
+    fn parse(index: &Value) -> Result<Self, Failure> {
+        match index["IndexName"].as_str() {
+            Some(name) => Ok(Self {
+                name: name.to_string(),
+                key_schema: KeySchema::parse(&index["KeySchema"])?,
+                projection: index["Projection"].clone(),
+            }),
+            None => Err(validation("The index name is required")),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Table {
+    key_schema: KeySchema,
+    attribute_definitions: Vec<Value>,
+    indexes: Vec<Index>,
+    time_to_live: Option<String>,
+    created_at: f64,
+    items: BTreeMap<Position, Item>,
+}
+
+impl Table {
+    fn describe(&self, table_name: &str) -> Value {
+        let mut description = json!({
+            "TableName": table_name,
+            "TableArn": format!("arn:aws:dynamodb:us-west-2:000000000000:table/{table_name}"),
+            "TableStatus": "ACTIVE",
+            "KeySchema": self.key_schema.to_json(),
+            "AttributeDefinitions": self.attribute_definitions,
+            "ItemCount": self.items.len(),
+            "TableSizeBytes": 0,
+            "CreationDateTime": self.created_at,
+            "BillingModeSummary": {"BillingMode": "PAY_PER_REQUEST"},
+        });
+
+        if !self.indexes.is_empty() {
+            let indexes = self
+                .indexes
+                .iter()
+                .map(|index| {
+                    json!({
+                        "IndexName": index.name,
+                        "KeySchema": index.key_schema.to_json(),
+                        "Projection": index.projection,
+                        "IndexStatus": "ACTIVE",
+                        "ItemCount": self.sorted(Some(index)).len(),
+                    })
+                })
+                .collect();
+            description["GlobalSecondaryIndexes"] = Value::Array(indexes);
+        }
+
+        description
+    }
+
+    fn attribute_type(&self, name: &str) -> Option<&str> {
+        self.attribute_definitions
+            .iter()
+            .find(|definition| definition["AttributeName"] == name)
+            .and_then(|definition| definition["AttributeType"].as_str())
+    }
+
+    fn index(&self, index_name: Option<&str>) -> Result<Option<&Index>, Failure> {
+        match index_name {
+            Some(index_name) => match self.indexes.iter().find(|index| index.name == index_name) {
+                Some(index) => Ok(Some(index)),
+                None => Err(validation(format!(
+                    "The table does not have the specified index: {index_name}"
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Check the key attributes of the table and the indexes that the item has
+    fn check_keys(&self, item: &Item) -> Result<(), Failure> {
+        for name in self.key_schema.names() {
+            if !item.contains_key(name) {
+                return Err(validation(format!(
+                    "One of the required keys was not given a value: {name}"
+                )));
+            }
+        }
+
+        let index_keys = self
+            .indexes
+            .iter()
+            .flat_map(|index| index.key_schema.names());
+        for name in self.key_schema.names().chain(index_keys) {
+            let Some(value) = item.get(name) else {
+                continue;
+            };
+            let expected = self.attribute_type(name).unwrap_or_default();
+            let actual = value
+                .as_object()
+                .and_then(|value| value.keys().next())
+                .map(String::as_str)
+                .unwrap_or_default();
+            if expected != actual {
+                return Err(validation(format!(
+                    "One or more parameter values were invalid: Type mismatch for key {name} expected: {expected} actual: {actual}"
+                )));
+            }
+            if value[actual] == "" {
+                return Err(validation(format!(
+                    "One or more parameter values are not valid. The AttributeValue for a key attribute cannot contain an empty string value. Key: {name}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The position of the item to put
+    fn item_position(&self, item: &Item) -> Result<Position, Failure> {
+        self.check_keys(item)?;
+        match self.key_schema.values(item) {
+            Some(values) => Ok(Position(values)),
+            None => Err(validation("One of the required keys was not given a value")),
+        }
+    }
+
+    /// The position of the key, which must have only the key attributes
+    fn key_position(&self, key: &Item) -> Result<Position, Failure> {
+        if key.len() != self.key_schema.names().count() {
+            return Err(validation(
+                "The provided key element does not match the schema",
+            ));
+        }
+        self.item_position(key)
+    }
+
+    /// The position of the item in the table or the index
+    /// The item that doesn't have the keys of the index is not in the index.
+    fn position_in(&self, index: Option<&Index>, item: &Item) -> Option<Position> {
+        let mut values = match index {
+            Some(index) => index.key_schema.values(item)?,
+            None => vec![],
+        };
+        values.extend(self.key_schema.values(item)?);
+        Some(Position(values))
+    }
+
+    /// The key of the item in the table or the index, as the LastEvaluatedKey
+    fn key_of(&self, index: Option<&Index>, item: &Item) -> Item {
+        let index_keys = index.into_iter().flat_map(|index| index.key_schema.names());
+        self.key_schema
+            .names()
+            .chain(index_keys)
+            .filter_map(|name| Some((name.to_string(), item.get(name)?.clone())))
+            .collect()
+    }
+
+    /// The items of the table or the index in the order
+    fn sorted(&self, index: Option<&Index>) -> Vec<(Position, &Item)> {
+        let mut items: Vec<(Position, &Item)> = self
+            .items
+            .values()
+            .filter_map(|item| Some((self.position_in(index, item)?, item)))
+            .collect();
+        items.sort_by(|(left, _), (right, _)| left.cmp(right));
+        items
+    }
+}
+
+/// A write of an item
+/// It is a PutItem, an UpdateItem, a DeleteItem, or an element of a TransactWriteItems.
+struct Write {
+    table_name: String,
+    position: Position,
+    action: WriteAction,
+    condition: Option<Condition>,
+    returns_old_on_failure: bool,
+}
+
+enum WriteAction {
+    Put(Item),
+    Update(Item, Option<Update>),
+    Delete,
+    ConditionCheck,
+}
+
+impl Write {
+    /// The attributes of the update, which are returned by `UPDATED_OLD` and `UPDATED_NEW`
+    fn updated_attributes(&self, item: &Item) -> Item {
+        match &self.action {
+            WriteAction::Update(_, Some(update)) => update
+                .paths()
+                .filter_map(|path| Some((path.to_string(), item.get(path)?.clone())))
+                .collect(),
+            _ => Item::new(),
+        }
+    }
+}
+
+pub(super) fn respond(tables: &mut Tables, target: &str, body: &[u8]) -> FakeResponse {
+    let operation = target.strip_prefix(TARGET_PREFIX).unwrap_or(target);
+    let result = match serde_json::from_slice::<Value>(body) {
+        Ok(request) => tables.call(operation, &request),
+        Err(e) => Err(Failure::new("SerializationException", e.to_string())),
+    };
+
+    match result {
+        Ok(output) => response(200, &json_headers(), output.to_string()),
+        Err(failure) => failure.response(),
+    }
+}
+
+fn table_name(request: &Value) -> Result<&str, Failure> {
+    request["TableName"]
+        .as_str()
+        .ok_or_else(|| validation("The table name is required"))
+}
+
+fn object(request: &Value, field: &str) -> Result<Item, Failure> {
+    request[field]
+        .as_object()
+        .cloned()
+        .ok_or_else(|| validation(format!("{field} is required")))
+}
+
+fn condition(
+    request: &Value,
+    field: &str,
+    placeholders: &mut Placeholders,
+) -> Result<Option<Condition>, Failure> {
+    request[field]
+        .as_str()
+        .map(|expression| Condition::parse(expression, placeholders))
+        .transpose()
+        .map_err(validation)
+}
+
+fn projection(
+    request: &Value,
+    placeholders: &mut Placeholders,
+) -> Result<Option<Vec<String>>, Failure> {
+    request["ProjectionExpression"]
+        .as_str()
+        .map(|expression| parse_projection(expression, placeholders))
+        .transpose()
+        .map_err(validation)
+}
+
+fn project(item: &Item, projection: &Option<Vec<String>>) -> Value {
+    match projection {
+        Some(paths) => Value::Object(
+            item.iter()
+                .filter(|(name, _)| paths.contains(name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+        ),
+        None => Value::Object(item.clone()),
+    }
+}
+
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs_f64())
+        .unwrap_or_default()
+}
+
+impl Tables {
+    fn call(&mut self, operation: &str, request: &Value) -> Result<Value, Failure> {
+        match operation {
+            "CreateTable" => self.create_table(request),
+            "DeleteTable" => self.delete_table(request),
+            "DescribeTable" => {
+                let table_name = table_name(request)?;
+                Ok(json!({"Table": self.table(table_name)?.describe(table_name)}))
+            }
+            "ListTables" => Ok(json!({"TableNames": self.0.keys().collect::<Vec<_>>()})),
+            "UpdateTable" => self.update_table(request),
+            "DescribeTimeToLive" => {
+                let description = match &self.table(table_name(request)?)?.time_to_live {
+                    Some(attribute_name) => {
+                        json!({"TimeToLiveStatus": "ENABLED", "AttributeName": attribute_name})
+                    }
+                    None => json!({"TimeToLiveStatus": "DISABLED"}),
+                };
+                Ok(json!({"TimeToLiveDescription": description}))
+            }
+            "UpdateTimeToLive" => self.update_time_to_live(request),
+            "PutItem" | "UpdateItem" | "DeleteItem" => self.write_item(operation, request),
+            "GetItem" => self.get_item(request),
+            "Query" => self.query(request),
+            "Scan" => self.scan(request),
+            "BatchWriteItem" => self.batch_write_item(request),
+            "BatchGetItem" => self.batch_get_item(request),
+            "TransactWriteItems" => self.transact_write_items(request),
+            _ => Err(Failure::new(
+                "UnknownOperationException",
+                format!("{operation} is not implemented by the fake"),
+            )),
+        }
+    }
+
+    fn table(&self, table_name: &str) -> Result<&Table, Failure> {
+        self.0.get(table_name).ok_or_else(|| {
+            Failure::new(
+                "ResourceNotFoundException",
+                format!("Requested resource not found: Table: {table_name} not found"),
+            )
+        })
+    }
+
+    fn table_mut(&mut self, table_name: &str) -> Result<&mut Table, Failure> {
+        self.table(table_name)?;
+        Ok(self.0.get_mut(table_name).expect("the table exists"))
+    }
+
+    fn create_table(&mut self, request: &Value) -> Result<Value, Failure> {
+        let table_name = table_name(request)?;
+        if self.0.contains_key(table_name) {
+            return Err(Failure::new(
+                "ResourceInUseException",
+                format!("Table already exists: {table_name}"),
+            ));
+        }
+
+        let table = Table {
+            key_schema: KeySchema::parse(&request["KeySchema"])?,
+            attribute_definitions: request["AttributeDefinitions"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default(),
+            indexes: request["GlobalSecondaryIndexes"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(Index::parse)
+                .collect::<Result<_, _>>()?,
+            time_to_live: None,
+            created_at: now(),
+            items: BTreeMap::new(),
+        };
+
+        let key_names = table
+            .indexes
+            .iter()
+            .flat_map(|index| index.key_schema.names())
+            .chain(table.key_schema.names());
+        for name in key_names {
+            if table.attribute_type(name).is_none() {
+                return Err(validation(format!(
+                    "One or more parameter values were invalid: Some index key attributes are not defined in AttributeDefinitions: {name}"
+                )));
+            }
+        }
+
+        let description = table.describe(table_name);
+        self.0.insert(table_name.to_string(), table);
+
+        Ok(json!({"TableDescription": description}))
+    }
+
+    fn delete_table(&mut self, request: &Value) -> Result<Value, Failure> {
+        let table_name = table_name(request)?;
+        let description = self.table(table_name)?.describe(table_name);
+        self.0.remove(table_name);
+
+        Ok(json!({"TableDescription": description}))
+    }
+
+    fn update_table(&mut self, request: &Value) -> Result<Value, Failure> {
+        let table_name = table_name(request)?;
+        let table = self.table_mut(table_name)?;
+
+        for definition in request["AttributeDefinitions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let name = definition["AttributeName"].as_str().unwrap_or_default();
+            if table.attribute_type(name).is_none() {
+                table.attribute_definitions.push(definition.clone());
+            }
+        }
+
+        for index_update in request["GlobalSecondaryIndexUpdates"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            if let Some(create) = index_update.get("Create") {
+                let index = Index::parse(create)?;
+                if table
+                    .indexes
+                    .iter()
+                    .any(|existing| existing.name == index.name)
+                {
+                    return Err(validation(format!(
+                        "Attempting to create an index which already exists: {}",
+                        index.name
+                    )));
+                }
+                table.indexes.push(index);
+            }
+            if let Some(delete) = index_update.get("Delete") {
+                table
+                    .indexes
+                    .retain(|index| index.name != delete["IndexName"].as_str().unwrap_or_default());
+            }
+        }
+
+        Ok(json!({"TableDescription": table.describe(table_name)}))
+    }
+
+    fn update_time_to_live(&mut self, request: &Value) -> Result<Value, Failure> {
+        let table = self.table_mut(table_name(request)?)?;
+        let specification = &request["TimeToLiveSpecification"];
+
+        match (
+            specification["Enabled"].as_bool(),
+            specification["AttributeName"].as_str(),
+        ) {
+            (Some(true), Some(_)) if table.time_to_live.is_some() => {
+                return Err(validation("TimeToLive is already enabled"))
+            }
+            (Some(true), Some(attribute_name)) => {
+                table.time_to_live = Some(attribute_name.to_string())
+            }
+            (Some(false), _) => table.time_to_live = None,
+            _ => return Err(validation("Invalid TimeToLiveSpecification")),
+        }
+
+        Ok(json!({"TimeToLiveSpecification": specification}))
+    }
+
+    /// Parse the write of the action, which is `Put`, `Update`, `Delete`, or `ConditionCheck`
+    fn write(&self, action: &str, request: &Value) -> Result<Write, Failure> {
+        let mut placeholders = Placeholders::new(request);
+        let condition = condition(request, "ConditionExpression", &mut placeholders)?;
+        let table_name = table_name(request)?;
+        let table = self.table(table_name)?;
+
+        let (position, action) = match action {
+            "Put" => {
+                let item = object(request, "Item")?;
+                (table.item_position(&item)?, WriteAction::Put(item))
+            }
+            "Update" => {
+                let key = object(request, "Key")?;
+                let update = request["UpdateExpression"]
+                    .as_str()
+                    .map(|expression| Update::parse(expression, &mut placeholders))
+                    .transpose()
+                    .map_err(validation)?;
+                let key_path = update
+                    .iter()
+                    .flat_map(Update::paths)
+                    .find(|path| table.key_schema.names().any(|name| name == *path));
+                if let Some(key_path) = key_path {
+                    return Err(validation(format!(
+                        "One or more parameter values were invalid: Cannot update attribute {key_path}. This attribute is part of the key"
+                    )));
+                }
+                (table.key_position(&key)?, WriteAction::Update(key, update))
+            }
+            "Delete" => (
+                table.key_position(&object(request, "Key")?)?,
+                WriteAction::Delete,
+            ),
+            _ => {
+                if condition.is_none() {
+                    return Err(validation(
+                        "The condition check requires the ConditionExpression",
+                    ));
+                }
+                (
+                    table.key_position(&object(request, "Key")?)?,
+                    WriteAction::ConditionCheck,
+                )
+            }
+        };
+        placeholders.check_unused().map_err(validation)?;
+
+        Ok(Write {
+            table_name: table_name.to_string(),
+            position,
+            action,
+            condition,
+            returns_old_on_failure: request["ReturnValuesOnConditionCheckFailure"] == "ALL_OLD",
+        })
+    }
+
+    /// The item before and after the write
+    /// If the condition is not met, it fails with the item if it is requested.
+    fn evaluate(&self, write: &Write) -> Result<(Option<Item>, Option<Item>), Failure> {
+        let table = self.table(&write.table_name)?;
+        let old = table.items.get(&write.position).cloned();
+
+        if let Some(condition) = &write.condition {
+            if !condition.evaluate(old.as_ref().unwrap_or(&Item::new())) {
+                return Err(Failure::conditional_check_failed(
+                    old.filter(|_| write.returns_old_on_failure),
+                ));
+            }
+        }
+
+        let new = match &write.action {
+            WriteAction::Put(item) => Some(item.clone()),
+            WriteAction::Update(key, update) => {
+                let current = old.clone().unwrap_or_else(|| key.clone());
+                let updated = match update {
+                    Some(update) => update.apply(&current).map_err(validation)?,
+                    None => current,
+                };
+                table.check_keys(&updated)?;
+                Some(updated)
+            }
+            WriteAction::Delete => None,
+            WriteAction::ConditionCheck => old.clone(),
+        };
+
+        Ok((old, new))
+    }
+
+    fn commit(&mut self, write: &Write, item: Option<Item>) {
+        if let Some(table) = self.0.get_mut(&write.table_name) {
+            match item {
+                Some(item) => table.items.insert(write.position.clone(), item),
+                None => table.items.remove(&write.position),
+            };
+        }
+    }
+
+    /// PutItem, UpdateItem, or DeleteItem
+    fn write_item(&mut self, operation: &str, request: &Value) -> Result<Value, Failure> {
+        let write = self.write(operation.trim_end_matches("Item"), request)?;
+        let (old, new) = self.evaluate(&write)?;
+        self.commit(&write, new.clone());
+
+        let attributes = match (request["ReturnValues"].as_str(), &old, &new) {
+            (Some("ALL_OLD"), Some(old), _) => old.clone(),
+            (Some("ALL_NEW"), _, Some(new)) => new.clone(),
+            (Some("UPDATED_OLD"), Some(old), _) => write.updated_attributes(old),
+            (Some("UPDATED_NEW"), _, Some(new)) => write.updated_attributes(new),
+            _ => Item::new(),
+        };
+
+        match attributes.is_empty() {
+            true => Ok(json!({})),
+            false => Ok(json!({"Attributes": attributes})),
+        }
+    }
+
+    fn get_item(&self, request: &Value) -> Result<Value, Failure> {
+        let mut placeholders = Placeholders::new(request);
+        let projection = projection(request, &mut placeholders)?;
+        placeholders.check_unused().map_err(validation)?;
+
+        let table = self.table(table_name(request)?)?;
+        let position = table.key_position(&object(request, "Key")?)?;
+
+        match table.items.get(&position) {
+            Some(item) => Ok(json!({"Item": project(item, &projection)})),
+            None => Ok(json!({})),
+        }
+    }
+
+    fn query(&self, request: &Value) -> Result<Value, Failure> {
+        let mut placeholders = Placeholders::new(request);
+        let key_condition = condition(request, "KeyConditionExpression", &mut placeholders)?
+            .ok_or_else(|| validation("The KeyConditionExpression parameter must be specified"))?;
+        let filter = condition(request, "FilterExpression", &mut placeholders)?;
+        let projection = projection(request, &mut placeholders)?;
+        placeholders.check_unused().map_err(validation)?;
+
+        let table = self.table(table_name(request)?)?;
+        let index = table.index(request["IndexName"].as_str())?;
+        let mut items: Vec<(Position, &Item)> = table
+            .sorted(index)
+            .into_iter()
+            .filter(|(_, item)| key_condition.evaluate(item))
+            .collect();
+        let is_forward = request["ScanIndexForward"].as_bool().unwrap_or(true);
+        if !is_forward {
+            items.reverse();
+        }
+
+        page(
+            table,
+            index,
+            items,
+            is_forward,
+            request,
+            &filter,
+            &projection,
+        )
+    }
+
+    fn scan(&self, request: &Value) -> Result<Value, Failure> {
+        let mut placeholders = Placeholders::new(request);
+        let filter = condition(request, "FilterExpression", &mut placeholders)?;
+        let projection = projection(request, &mut placeholders)?;
+        placeholders.check_unused().map_err(validation)?;
+
+        let table = self.table(table_name(request)?)?;
+        let index = table.index(request["IndexName"].as_str())?;
+        let items = table.sorted(index);
+
+        page(table, index, items, true, request, &filter, &projection)
+    }
+
+    fn batch_write_item(&mut self, request: &Value) -> Result<Value, Failure> {
+        let mut writes = Vec::new();
+        for (table_name, requests) in request["RequestItems"].as_object().into_iter().flatten() {
+            for write_request in requests.as_array().into_iter().flatten() {
+                let (action, mut request) = match (
+                    write_request.get("PutRequest"),
+                    write_request.get("DeleteRequest"),
+                ) {
+                    (Some(put), None) => ("Put", put.clone()),
+                    (None, Some(delete)) => ("Delete", delete.clone()),
+                    _ => return Err(validation("A write request must be a put or a delete")),
+                };
+                request["TableName"] = Value::String(table_name.clone());
+                writes.push(self.write(action, &request)?);
+            }
+        }
+
+        if writes.is_empty() || writes.len() > BATCH_WRITE_LIMIT {
+            return Err(validation(format!(
+                "The number of the write requests must be 1 to {BATCH_WRITE_LIMIT}"
+            )));
+        }
+        check_duplicates(&writes, "Provided list of item keys contains duplicates")?;
+
+        for write in &writes {
+            let (_, new) = self.evaluate(write)?;
+            self.commit(write, new);
+        }
+
+        Ok(json!({"UnprocessedItems": {}}))
+    }
+
+    fn batch_get_item(&self, request: &Value) -> Result<Value, Failure> {
+        let mut responses = Map::new();
+        let mut key_count = 0;
+
+        for (table_name, keys_and_attributes) in
+            request["RequestItems"].as_object().into_iter().flatten()
+        {
+            let mut placeholders = Placeholders::new(keys_and_attributes);
+            let projection = projection(keys_and_attributes, &mut placeholders)?;
+            placeholders.check_unused().map_err(validation)?;
+
+            let table = self.table(table_name)?;
+            let mut items = Vec::new();
+            for key in keys_and_attributes["Keys"].as_array().into_iter().flatten() {
+                key_count += 1;
+                let key = key
+                    .as_object()
+                    .ok_or_else(|| validation("The key must be an object"))?;
+                if let Some(item) = table.items.get(&table.key_position(key)?) {
+                    items.push(project(item, &projection));
+                }
+            }
+            responses.insert(table_name.clone(), Value::Array(items));
+        }
+
+        if key_count == 0 || key_count > BATCH_GET_LIMIT {
+            return Err(validation(format!(
+                "The number of the keys must be 1 to {BATCH_GET_LIMIT}"
+            )));
+        }
+
+        Ok(json!({"Responses": responses, "UnprocessedKeys": {}}))
+    }
+
+    /// Write all or nothing
+    /// If a condition is not met, it fails with the reasons of all the writes in the order.
+    fn transact_write_items(&mut self, request: &Value) -> Result<Value, Failure> {
+        let mut writes = Vec::new();
+        for transact_item in request["TransactItems"].as_array().into_iter().flatten() {
+            let write = ["Put", "Update", "Delete", "ConditionCheck"]
+                .into_iter()
+                .find_map(|action| Some((action, transact_item.get(action)?)));
+            match write {
+                Some((action, request)) => writes.push(self.write(action, request)?),
+                None => {
+                    return Err(validation(format!(
+                        "Invalid transact item: {transact_item}"
+                    )))
+                }
+            }
+        }
+
+        if writes.is_empty() || writes.len() > TRANSACTION_LIMIT {
+            return Err(validation(format!(
+                "The number of the transact items must be 1 to {TRANSACTION_LIMIT}"
+            )));
+        }
+        check_duplicates(
+            &writes,
+            "Transaction request cannot include multiple operations on one item",
+        )?;
+
+        let mut reasons = Vec::new();
+        let mut updates = Vec::new();
+        for write in &writes {
+            match self.evaluate(write) {
+                Ok((_, new)) => {
+                    reasons.push(json!({"Code": "None"}));
+                    updates.push(new);
+                }
+                Err(failure) if failure.code == "ConditionalCheckFailedException" => {
+                    let mut reason = failure.details;
+                    reason.insert("Code".to_string(), json!("ConditionalCheckFailed"));
+                    reason.insert("Message".to_string(), json!(failure.message));
+                    reasons.push(Value::Object(reason));
+                }
+                Err(failure) => return Err(failure),
+            }
+        }
+
+        if updates.len() < writes.len() {
+            let codes = reasons
+                .iter()
+                .map(|reason| reason["Code"].as_str().unwrap_or_default())
+                .collect::<Vec<&str>>()
+                .join(", ");
+            let mut failure = Failure::new(
+                "TransactionCanceledException",
+                format!("Transaction cancelled, please refer cancellation reasons for specific reasons [{codes}]"),
+            );
+            failure
+                .details
+                .insert("CancellationReasons".to_string(), Value::Array(reasons));
+            return Err(failure);
+        }
+
+        for (write, new) in writes.iter().zip(updates) {
+            if !matches!(write.action, WriteAction::ConditionCheck) {
+                self.commit(write, new);
+            }
+        }
+
+        Ok(json!({}))
+    }
+}
+
+fn check_duplicates(writes: &[Write], message: &str) -> Result<(), Failure> {
+    let mut keys = BTreeSet::new();
+    for write in writes {
+        if !keys.insert((write.table_name.as_str(), &write.position)) {
+            return Err(validation(message));
+        }
+    }
+    Ok(())
+}
+
+/// The page of a Query or a Scan
+/// The limit is of the evaluated items before the filter, and the LastEvaluatedKey is only when items remain.
+fn page(
+    table: &Table,
+    index: Option<&Index>,
+    items: Vec<(Position, &Item)>,
+    is_forward: bool,
+    request: &Value,
+    filter: &Option<Condition>,
+    projection: &Option<Vec<String>>,
+) -> Result<Value, Failure> {
+    let start = match request["ExclusiveStartKey"].as_object() {
+        Some(start_key) => Some(
+            table
+                .position_in(index, start_key)
+                .ok_or_else(|| validation("The provided starting key is invalid"))?,
+        ),
+        None => None,
+    };
+    let items: Vec<(Position, &Item)> = items
+        .into_iter()
+        .filter(|(position, _)| match &start {
+            Some(start) if is_forward => position > start,
+            Some(start) => position < start,
+            None => true,
+        })
+        .collect();
+
+    let limit = match request["Limit"].as_u64() {
+        Some(0) => return Err(validation("The limit must be greater than or equal to 1")),
+        Some(limit) => (limit as usize).min(items.len()),
+        None => items.len(),
+    };
+    let evaluated = &items[..limit];
+
+    let matched: Vec<Value> = evaluated
+        .iter()
+        .filter(|(_, item)| filter.as_ref().is_none_or(|filter| filter.evaluate(item)))
+        .map(|(_, item)| project(item, projection))
+        .collect();
+
+    let mut output = json!({"Count": matched.len(), "ScannedCount": evaluated.len()});
+    if request["Select"] != "COUNT" {
+        output["Items"] = Value::Array(matched);
+    }
+    if let (true, Some((_, last))) = (limit < items.len(), evaluated.last()) {
+        output["LastEvaluatedKey"] = Value::Object(table.key_of(index, last));
+    }
+
+    Ok(output)
+}
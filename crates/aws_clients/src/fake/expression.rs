@@ -0,0 +1,994 @@
+//! The expressions of the fake DynamoDB
+//! The conditions, the updates, and the projections are parsed with the placeholders resolved,
+//! and evaluated on the items in the JSON of the wire, e.g. `{"PK": {"S": "1984"}}`.
+//! The paths are the top-level attributes, which are all that the clients of this crate use.
+
+use aws_smithy_types::base64;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// An item in the JSON of the wire
+pub(super) type Item = Map<String, Value>;
+
+/// The expression attribute names and values of a request
+/// As DynamoDB does, a name or a value that no expression uses is an error.
+pub(super) struct Placeholders<'a> {
+    names: Option<&'a Map<String, Value>>,
+    values: Option<&'a Map<String, Value>>,
+    used: HashSet<String>,
+}
+
+impl<'a> Placeholders<'a> {
+    pub(super) fn new(request: &'a Value) -> Self {
+        Self {
+            names: request["ExpressionAttributeNames"].as_object(),
+            values: request["ExpressionAttributeValues"].as_object(),
+            used: HashSet::new(),
+        }
+    }
+
+    /// Fail if a name or a value is not used
+    pub(super) fn check_unused(&self) -> Result<(), String> {
+        let provided = self
+            .names
+            .into_iter()
+            .chain(self.values)
+            .flat_map(|placeholders| placeholders.keys());
+
+        let mut unused = provided
+            .filter(|placeholder| !self.used.contains(*placeholder))
+            .map(String::as_str)
+            .collect::<Vec<&str>>();
+        unused.sort();
+
+        match unused.is_empty() {
+            true => Ok(()),
+            false => Err(format!(
+                "Value provided in ExpressionAttributeNames or ExpressionAttributeValues unused in expressions: {}",
+                unused.join(", ")
+            )),
+        }
+    }
+
+    fn name(&mut self, placeholder: &str) -> Result<String, String> {
+        match self.names.and_then(|names| names.get(placeholder)) {
+            Some(Value::String(name)) => {
+                self.used.insert(placeholder.to_string());
+                Ok(name.clone())
+            }
+            _ => Err(format!(
+                "An expression attribute name used in the document path is not defined; attribute name: {placeholder}"
+            )),
+        }
+    }
+
+    fn value(&mut self, placeholder: &str) -> Result<Value, String> {
+        match self.values.and_then(|values| values.get(placeholder)) {
+            Some(value) => {
+                self.used.insert(placeholder.to_string());
+                Ok(value.clone())
+            }
+            None => Err(format!(
+                "An expression attribute value used in expression is not defined; attribute value: {placeholder}"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A name that is a keyword, a function, or an attribute
+    Word(String),
+    /// The attribute name of a `#name`
+    Name(String),
+    /// The attribute value of a `:value`
+    Value(Value),
+    Comparator(Comparator),
+    OpenParenthesis,
+    CloseParenthesis,
+    Comma,
+    Plus,
+    Minus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+fn tokenize(expression: &str, placeholders: &mut Placeholders) -> Result<Vec<Token>, String> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut tokens = Vec::new();
+    let mut chars = expression.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::OpenParenthesis,
+            ')' => Token::CloseParenthesis,
+            ',' => Token::Comma,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '=' => Token::Comparator(Comparator::Equal),
+            '<' | '>' => {
+                let (comparator, is_two_characters) = match (c, chars.peek().map(|(_, next)| *next))
+                {
+                    ('<', Some('>')) => (Comparator::NotEqual, true),
+                    ('<', Some('=')) => (Comparator::LessOrEqual, true),
+                    ('>', Some('=')) => (Comparator::GreaterOrEqual, true),
+                    ('<', _) => (Comparator::Less, false),
+                    _ => (Comparator::Greater, false),
+                };
+                if is_two_characters {
+                    chars.next();
+                }
+                Token::Comparator(comparator)
+            }
+            '#' | ':' | 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((index, next)) = chars.peek().copied() {
+                    if !is_word(next) {
+                        break;
+                    }
+                    end = index + next.len_utf8();
+                    chars.next();
+                }
+                let word = &expression[start..end];
+                match c {
+                    '#' => Token::Name(placeholders.name(word)?),
+                    ':' => Token::Value(placeholders.value(word)?),
+                    _ => Token::Word(word.to_string()),
+                }
+            }
+            '.' | '[' => {
+                return Err(format!(
+                    "The nested paths are not supported by the fake: {expression}"
+                ))
+            }
+            _ => {
+                return Err(format!(
+                    "Invalid character {c} in the expression: {expression}"
+                ))
+            }
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    expression: String,
+}
+
+impl Parser {
+    fn new(expression: &str, placeholders: &mut Placeholders) -> Result<Self, String> {
+        Ok(Self {
+            tokens: tokenize(expression, placeholders)?,
+            position: 0,
+            expression: expression.to_string(),
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn is_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    fn error(&self) -> String {
+        match self.tokens.get(self.position) {
+            Some(token) => format!("Invalid syntax at {token:?} in {}", self.expression),
+            None => format!("Unexpected end of {}", self.expression),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.peek() == Some(&expected) {
+            true => {
+                self.position += 1;
+                Ok(())
+            }
+            false => Err(self.error()),
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        match self.is_keyword(keyword) {
+            true => {
+                self.position += 1;
+                Ok(())
+            }
+            false => Err(self.error()),
+        }
+    }
+
+    fn path(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Name(name)) => Ok(name),
+            Some(Token::Word(word)) if !is_reserved(&word) => Ok(word),
+            _ => {
+                self.position -= 1;
+                Err(self.error())
+            }
+        }
+    }
+
+    /// The name of the function if the next tokens are `name(`
+    fn function(&self) -> Option<String> {
+        match (self.peek(), self.tokens.get(self.position + 1)) {
+            (Some(Token::Word(word)), Some(Token::OpenParenthesis)) => Some(word.to_lowercase()),
+            _ => None,
+        }
+    }
+
+    fn condition(&mut self) -> Result<Node, String> {
+        let mut condition = self.and_condition()?;
+        while self.is_keyword("OR") {
+            self.position += 1;
+            condition = Node::Or(Box::new(condition), Box::new(self.and_condition()?));
+        }
+        Ok(condition)
+    }
+
+    fn and_condition(&mut self) -> Result<Node, String> {
+        let mut condition = self.not_condition()?;
+        while self.is_keyword("AND") {
+            self.position += 1;
+            condition = Node::And(Box::new(condition), Box::new(self.not_condition()?));
+        }
+        Ok(condition)
+    }
+
+    fn not_condition(&mut self) -> Result<Node, String> {
+        match self.is_keyword("NOT") {
+            true => {
+                self.position += 1;
+                Ok(Node::Not(Box::new(self.not_condition()?)))
+            }
+            false => self.primary_condition(),
+        }
+    }
+
+    fn primary_condition(&mut self) -> Result<Node, String> {
+        if self.peek() == Some(&Token::OpenParenthesis) {
+            self.position += 1;
+            let condition = self.condition()?;
+            self.expect(Token::CloseParenthesis)?;
+            return Ok(condition);
+        }
+
+        let condition = match self.function().as_deref() {
+            Some("attribute_exists") => {
+                self.position += 2;
+                Node::Exists(self.path()?)
+            }
+            Some("attribute_not_exists") => {
+                self.position += 2;
+                Node::Not(Box::new(Node::Exists(self.path()?)))
+            }
+            Some("begins_with") => {
+                self.position += 2;
+                let operand = self.operand()?;
+                self.expect(Token::Comma)?;
+                Node::BeginsWith(operand, self.operand()?)
+            }
+            Some("contains") => {
+                self.position += 2;
+                let operand = self.operand()?;
+                self.expect(Token::Comma)?;
+                Node::Contains(operand, self.operand()?)
+            }
+            Some("attribute_type") => {
+                self.position += 2;
+                let path = self.path()?;
+                self.expect(Token::Comma)?;
+                Node::AttributeType(path, self.operand()?)
+            }
+            _ => return self.comparison(),
+        };
+        self.expect(Token::CloseParenthesis)?;
+
+        Ok(condition)
+    }
+
+    fn comparison(&mut self) -> Result<Node, String> {
+        let operand = self.operand()?;
+
+        if self.is_keyword("BETWEEN") {
+            self.position += 1;
+            let lower = self.operand()?;
+            self.expect_keyword("AND")?;
+            return Ok(Node::Between(operand, lower, self.operand()?));
+        }
+
+        if self.is_keyword("IN") {
+            self.position += 1;
+            self.expect(Token::OpenParenthesis)?;
+            let mut candidates = vec![self.operand()?];
+            while self.peek() == Some(&Token::Comma) {
+                self.position += 1;
+                candidates.push(self.operand()?);
+            }
+            self.expect(Token::CloseParenthesis)?;
+            return Ok(Node::In(operand, candidates));
+        }
+
+        match self.next() {
+            Some(Token::Comparator(comparator)) => {
+                Ok(Node::Compare(operand, comparator, self.operand()?))
+            }
+            _ => {
+                self.position -= 1;
+                Err(self.error())
+            }
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        if self.function().as_deref() == Some("size") {
+            self.position += 2;
+            let path = self.path()?;
+            self.expect(Token::CloseParenthesis)?;
+            return Ok(Operand::Size(path));
+        }
+
+        match self.peek() {
+            Some(Token::Value(value)) => {
+                let value = value.clone();
+                self.position += 1;
+                Ok(Operand::Value(value))
+            }
+            _ => Ok(Operand::Path(self.path()?)),
+        }
+    }
+
+    fn update(&mut self) -> Result<Update, String> {
+        let mut actions = Vec::new();
+
+        while !self.is_end() {
+            let clause = match self.next() {
+                Some(Token::Word(word)) => word.to_uppercase(),
+                _ => {
+                    self.position -= 1;
+                    return Err(self.error());
+                }
+            };
+
+            loop {
+                let path = self.path()?;
+                let action = match clause.as_str() {
+                    "SET" => {
+                        self.expect(Token::Comparator(Comparator::Equal))?;
+                        Action::Set(path, self.set_value()?)
+                    }
+                    "REMOVE" => Action::Remove(path),
+                    "ADD" => Action::Add(path, self.value()?),
+                    "DELETE" => Action::Delete(path, self.value()?),
+                    _ => {
+                        self.position -= 2;
+                        return Err(self.error());
+                    }
+                };
+                actions.push(action);
+
+                match self.peek() == Some(&Token::Comma) {
+                    true => self.position += 1,
+                    false => break,
+                }
+            }
+        }
+
+        let mut paths = HashSet::new();
+        if let Some(path) = actions
+            .iter()
+            .map(Action::path)
+            .find(|path| !paths.insert(*path))
+        {
+            return Err(format!(
+                "Two document paths overlap with each other; path: {path} in {}",
+                self.expression
+            ));
+        }
+
+        Ok(Update { actions })
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.next() {
+            Some(Token::Value(value)) => Ok(value),
+            _ => {
+                self.position -= 1;
+                Err(self.error())
+            }
+        }
+    }
+
+    fn set_value(&mut self) -> Result<SetValue, String> {
+        let operand = self.set_operand()?;
+        match self.peek() {
+            Some(Token::Plus) => {
+                self.position += 1;
+                Ok(SetValue::Plus(operand, self.set_operand()?))
+            }
+            Some(Token::Minus) => {
+                self.position += 1;
+                Ok(SetValue::Minus(operand, self.set_operand()?))
+            }
+            _ => Ok(SetValue::Operand(operand)),
+        }
+    }
+
+    fn set_operand(&mut self) -> Result<SetOperand, String> {
+        let operand = match self.function().as_deref() {
+            Some("if_not_exists") => {
+                self.position += 2;
+                let path = self.path()?;
+                self.expect(Token::Comma)?;
+                SetOperand::IfNotExists(path, Box::new(self.set_operand()?))
+            }
+            Some("list_append") => {
+                self.position += 2;
+                let list = self.set_operand()?;
+                self.expect(Token::Comma)?;
+                SetOperand::ListAppend(Box::new(list), Box::new(self.set_operand()?))
+            }
+            _ => {
+                return match self.peek() {
+                    Some(Token::Value(_)) => Ok(SetOperand::Value(self.value()?)),
+                    _ => Ok(SetOperand::Path(self.path()?)),
+                }
+            }
+        };
+        self.expect(Token::CloseParenthesis)?;
+
+        Ok(operand)
+    }
+
+    fn finish<T>(&self, parsed: T) -> Result<T, String> {
+        match self.is_end() {
+            true => Ok(parsed),
+            false => Err(self.error()),
+        }
+    }
+}
+
+fn is_reserved(word: &str) -> bool {
+    [
+        "AND", "OR", "NOT", "BETWEEN", "IN", "SET", "REMOVE", "ADD", "DELETE",
+    ]
+    .iter()
+    .any(|reserved| word.eq_ignore_ascii_case(reserved))
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Path(String),
+    Value(Value),
+    Size(String),
+}
+
+impl Operand {
+    fn evaluate(&self, item: &Item) -> Option<Value> {
+        match self {
+            Operand::Path(path) => item.get(path).cloned(),
+            Operand::Value(value) => Some(value.clone()),
+            Operand::Size(path) => size(item.get(path)?).map(|size| number_value(size as f64)),
+        }
+    }
+}
+
+/// A condition, a key condition, or a filter
+#[derive(Debug, Clone)]
+pub(super) struct Condition(Node);
+
+#[derive(Debug, Clone)]
+enum Node {
+    Compare(Operand, Comparator, Operand),
+    Between(Operand, Operand, Operand),
+    In(Operand, Vec<Operand>),
+    Exists(String),
+    BeginsWith(Operand, Operand),
+    Contains(Operand, Operand),
+    AttributeType(String, Operand),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+}
+
+impl Condition {
+    pub(super) fn parse(expression: &str, placeholders: &mut Placeholders) -> Result<Self, String> {
+        let mut parser = Parser::new(expression, placeholders)?;
+        let condition = parser.condition()?;
+        parser.finish(Self(condition))
+    }
+
+    pub(super) fn evaluate(&self, item: &Item) -> bool {
+        self.0.evaluate(item)
+    }
+}
+
+impl Node {
+    fn evaluate(&self, item: &Item) -> bool {
+        match self {
+            Node::Compare(left, comparator, right) => {
+                let (left, right) = (left.evaluate(item), right.evaluate(item));
+                match comparator {
+                    Comparator::NotEqual => {
+                        !matches!((left, right), (Some(left), Some(right)) if equal(&left, &right))
+                    }
+                    _ => match (left, right) {
+                        (Some(left), Some(right)) => {
+                            let ordering = match comparator {
+                                Comparator::Equal => return equal(&left, &right),
+                                _ => compare(&left, &right),
+                            };
+                            match (comparator, ordering) {
+                                (_, None) => false,
+                                (Comparator::Less, Some(ordering)) => ordering.is_lt(),
+                                (Comparator::LessOrEqual, Some(ordering)) => ordering.is_le(),
+                                (Comparator::Greater, Some(ordering)) => ordering.is_gt(),
+                                (_, Some(ordering)) => ordering.is_ge(),
+                            }
+                        }
+                        _ => false,
+                    },
+                }
+            }
+            Node::Between(operand, lower, upper) => {
+                match (
+                    operand.evaluate(item),
+                    lower.evaluate(item),
+                    upper.evaluate(item),
+                ) {
+                    (Some(value), Some(lower), Some(upper)) => {
+                        compare(&value, &lower).is_some_and(Ordering::is_ge)
+                            && compare(&value, &upper).is_some_and(Ordering::is_le)
+                    }
+                    _ => false,
+                }
+            }
+            Node::In(operand, candidates) => match operand.evaluate(item) {
+                Some(value) => candidates
+                    .iter()
+                    .filter_map(|candidate| candidate.evaluate(item))
+                    .any(|candidate| equal(&value, &candidate)),
+                None => false,
+            },
+            Node::Exists(path) => item.contains_key(path),
+            Node::BeginsWith(operand, prefix) => {
+                match (operand.evaluate(item), prefix.evaluate(item)) {
+                    (Some(value), Some(prefix)) => match (scalar(&value), scalar(&prefix)) {
+                        (Some(("S", Value::String(value))), Some(("S", Value::String(prefix)))) => {
+                            value.starts_with(prefix.as_str())
+                        }
+                        (Some(("B", Value::String(value))), Some(("B", Value::String(prefix)))) => {
+                            match (base64::decode(value), base64::decode(prefix)) {
+                                (Ok(value), Ok(prefix)) => value.starts_with(&prefix),
+                                _ => false,
+                            }
+                        }
+                        _ => false,
+                    },
+                    _ => false,
+                }
+            }
+            Node::Contains(operand, element) => {
+                match (operand.evaluate(item), element.evaluate(item)) {
+                    (Some(value), Some(element)) => contains(&value, &element),
+                    _ => false,
+                }
+            }
+            Node::AttributeType(path, attribute_type) => {
+                match (item.get(path), attribute_type.evaluate(item)) {
+                    (Some(value), Some(attribute_type)) => match scalar(&attribute_type) {
+                        Some(("S", Value::String(attribute_type))) => {
+                            scalar(value).is_some_and(|(tag, _)| tag == attribute_type)
+                        }
+                        _ => false,
+                    },
+                    _ => false,
+                }
+            }
+            Node::And(left, right) => left.evaluate(item) && right.evaluate(item),
+            Node::Or(left, right) => left.evaluate(item) || right.evaluate(item),
+            Node::Not(condition) => !condition.evaluate(item),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SetOperand {
+    Path(String),
+    Value(Value),
+    IfNotExists(String, Box<SetOperand>),
+    ListAppend(Box<SetOperand>, Box<SetOperand>),
+}
+
+impl SetOperand {
+    fn evaluate(&self, item: &Item) -> Result<Value, String> {
+        match self {
+            SetOperand::Path(path) => item.get(path).cloned().ok_or_else(|| {
+                format!("The attribute {path} in the update expression does not exist")
+            }),
+            SetOperand::Value(value) => Ok(value.clone()),
+            SetOperand::IfNotExists(path, operand) => match item.get(path) {
+                Some(value) => Ok(value.clone()),
+                None => operand.evaluate(item),
+            },
+            SetOperand::ListAppend(list, other) => {
+                match (list.evaluate(item)?, other.evaluate(item)?) {
+                    (Value::Object(list), Value::Object(other)) => {
+                        match (list.get("L"), other.get("L")) {
+                            (Some(Value::Array(list)), Some(Value::Array(other))) => {
+                                let appended = list.iter().chain(other).cloned().collect();
+                                Ok(single("L", Value::Array(appended)))
+                            }
+                            _ => Err("An operand of list_append is not a list".to_string()),
+                        }
+                    }
+                    _ => Err("An operand of list_append is not a list".to_string()),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SetValue {
+    Operand(SetOperand),
+    Plus(SetOperand, SetOperand),
+    Minus(SetOperand, SetOperand),
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    Set(String, SetValue),
+    Remove(String),
+    Add(String, Value),
+    Delete(String, Value),
+}
+
+impl Action {
+    fn path(&self) -> &str {
+        match self {
+            Action::Set(path, _)
+            | Action::Remove(path)
+            | Action::Add(path, _)
+            | Action::Delete(path, _) => path,
+        }
+    }
+}
+
+/// An update expression
+#[derive(Debug, Clone)]
+pub(super) struct Update {
+    actions: Vec<Action>,
+}
+
+impl Update {
+    pub(super) fn parse(expression: &str, placeholders: &mut Placeholders) -> Result<Self, String> {
+        let mut parser = Parser::new(expression, placeholders)?;
+        let update = parser.update()?;
+        parser.finish(update)
+    }
+
+    /// The attributes that the update changes
+    pub(super) fn paths(&self) -> impl Iterator<Item = &str> {
+        self.actions.iter().map(Action::path)
+    }
+
+    /// Apply the actions
+    /// The operands are evaluated on the item before the update, as DynamoDB does.
+    pub(super) fn apply(&self, item: &Item) -> Result<Item, String> {
+        let mut updated = item.clone();
+
+        for action in &self.actions {
+            match action {
+                Action::Set(path, value) => {
+                    let value = match value {
+                        SetValue::Operand(operand) => operand.evaluate(item)?,
+                        SetValue::Plus(left, right) => {
+                            add_numbers(&left.evaluate(item)?, &right.evaluate(item)?, 1.0)?
+                        }
+                        SetValue::Minus(left, right) => {
+                            add_numbers(&left.evaluate(item)?, &right.evaluate(item)?, -1.0)?
+                        }
+                    };
+                    updated.insert(path.clone(), value);
+                }
+                Action::Remove(path) => {
+                    updated.remove(path);
+                }
+                Action::Add(path, value) => {
+                    let added = match item.get(path) {
+                        None => value.clone(),
+                        Some(current) => match scalar(current) {
+                            Some(("N", _)) => add_numbers(current, value, 1.0)?,
+                            Some((tag @ ("SS" | "NS" | "BS"), Value::Array(elements))) => {
+                                let mut elements = elements.clone();
+                                for element in set_elements(value, tag)? {
+                                    if !elements.contains(element) {
+                                        elements.push(element.clone());
+                                    }
+                                }
+                                single(tag, Value::Array(elements))
+                            }
+                            _ => {
+                                return Err(format!(
+                                    "An operand in the update expression has an incorrect data type; attribute: {path}"
+                                ))
+                            }
+                        },
+                    };
+                    updated.insert(path.clone(), added);
+                }
+                Action::Delete(path, value) => {
+                    if let Some(current) = item.get(path) {
+                        let Some((tag @ ("SS" | "NS" | "BS"), Value::Array(elements))) =
+                            scalar(current)
+                        else {
+                            return Err(format!(
+                                "An operand in the update expression has an incorrect data type; attribute: {path}"
+                            ));
+                        };
+                        let removed = set_elements(value, tag)?;
+                        let remaining: Vec<Value> = elements
+                            .iter()
+                            .filter(|element| !removed.contains(element))
+                            .cloned()
+                            .collect();
+                        match remaining.is_empty() {
+                            true => updated.remove(path),
+                            false => {
+                                updated.insert(path.clone(), single(tag, Value::Array(remaining)))
+                            }
+                        };
+                    }
+                }
+            }
+        }
+
+        Ok(updated)
+    }
+}
+
+/// Parse a projection expression into the attributes
+pub(super) fn parse_projection(
+    expression: &str,
+    placeholders: &mut Placeholders,
+) -> Result<Vec<String>, String> {
+    let mut parser = Parser::new(expression, placeholders)?;
+    let mut paths = vec![parser.path()?];
+    while parser.peek() == Some(&Token::Comma) {
+        parser.position += 1;
+        paths.push(parser.path()?);
+    }
+    parser.finish(paths)
+}
+
+/// The type and the content of an attribute value, e.g. `("S", "1984")`
+pub(super) fn scalar(value: &Value) -> Option<(&str, &Value)> {
+    match value.as_object() {
+        Some(object) if object.len() == 1 => object
+            .iter()
+            .next()
+            .map(|(tag, value)| (tag.as_str(), value)),
+        _ => None,
+    }
+}
+
+fn single(tag: &str, value: Value) -> Value {
+    Value::Object(Map::from_iter([(tag.to_string(), value)]))
+}
+
+/// Compare the values of the same scalar type
+/// The numbers are compared as the integers if they are, so the large ones keep the precision.
+pub(super) fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (scalar(left)?, scalar(right)?) {
+        (("N", Value::String(left)), ("N", Value::String(right))) => {
+            match (left.parse::<i128>(), right.parse::<i128>()) {
+                (Ok(left), Ok(right)) => Some(left.cmp(&right)),
+                _ => left
+                    .parse::<f64>()
+                    .ok()?
+                    .partial_cmp(&right.parse::<f64>().ok()?),
+            }
+        }
+        (("S", Value::String(left)), ("S", Value::String(right))) => Some(left.cmp(right)),
+        (("B", Value::String(left)), ("B", Value::String(right))) => {
+            Some(base64::decode(left).ok()?.cmp(&base64::decode(right).ok()?))
+        }
+        _ => None,
+    }
+}
+
+fn equal(left: &Value, right: &Value) -> bool {
+    if let Some(ordering) = compare(left, right) {
+        return ordering.is_eq();
+    }
+
+    match (scalar(left), scalar(right)) {
+        (
+            Some((tag @ ("SS" | "NS" | "BS"), Value::Array(left))),
+            Some((other, Value::Array(right))),
+        ) if tag == other => {
+            left.len() == right.len()
+                && left.iter().all(|element| {
+                    right.iter().any(|other| {
+                        equal(
+                            &single(&tag[..1], element.clone()),
+                            &single(&tag[..1], other.clone()),
+                        )
+                    })
+                })
+        }
+        _ => left == right,
+    }
+}
+
+fn contains(value: &Value, element: &Value) -> bool {
+    match (scalar(value), scalar(element)) {
+        (Some(("S", Value::String(value))), Some(("S", Value::String(element)))) => {
+            value.contains(element.as_str())
+        }
+        (
+            Some((tag @ ("SS" | "NS" | "BS"), Value::Array(elements))),
+            Some((element_tag, element)),
+        ) if tag[..1] == *element_tag => elements.contains(element),
+        (Some(("L", Value::Array(elements))), Some(_)) => {
+            elements.iter().any(|other| equal(other, element))
+        }
+        _ => false,
+    }
+}
+
+fn size(value: &Value) -> Option<usize> {
+    match scalar(value)? {
+        ("S", Value::String(string)) => Some(string.len()),
+        ("B", Value::String(binary)) => base64::decode(binary).ok().map(|binary| binary.len()),
+        (_, Value::Array(elements)) => Some(elements.len()),
+        ("M", Value::Object(map)) => Some(map.len()),
+        _ => None,
+    }
+}
+
+fn set_elements<'a>(value: &'a Value, tag: &str) -> Result<&'a Vec<Value>, String> {
+    match scalar(value) {
+        Some((other, Value::Array(elements))) if other == tag => Ok(elements),
+        _ => Err(format!("An operand of {tag} has an incorrect data type")),
+    }
+}
+
+fn number(value: &Value) -> Result<&str, String> {
+    match scalar(value) {
+        Some(("N", Value::String(number))) => Ok(number),
+        _ => Err("An operand in the update expression has an incorrect data type".to_string()),
+    }
+}
+
+fn number_value(number: f64) -> Value {
+    single("N", Value::String(number.to_string()))
+}
+
+/// Add the numbers, or subtract by the sign of -1
+fn add_numbers(left: &Value, right: &Value, sign: f64) -> Result<Value, String> {
+    let (left, right) = (number(left)?, number(right)?);
+
+    if let (Ok(left), Ok(right)) = (left.parse::<i128>(), right.parse::<i128>()) {
+        let sum = left + right * sign as i128;
+        return Ok(single("N", Value::String(sum.to_string())));
+    }
+
+    match (left.parse::<f64>(), right.parse::<f64>()) {
+        (Ok(left), Ok(right)) => Ok(number_value(left + right * sign)),
+        _ => Err("An operand in the update expression is not a number".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn item() -> Item {
+        json!({
+            "PK": {"S": "1984"},
+            "SK": {"N": "449930090000"},
+            "Version": {"N": "2"},
+            "Tags": {"SS": ["a", "b"]}
+        })
+        .as_object()
+        .unwrap()
+        .clone()
+    }
+
+    #[test]
+    fn test_condition() {
+        // Arrange
+        let request = json!({
+            "ExpressionAttributeNames": {"#version": "Version", "#trashed_at": "TrashedAt"},
+            "ExpressionAttributeValues": {
+                ":pk": {"S": "1984"},
+                ":from": {"N": "449930089999"},
+                ":to": {"N": "449930090000"},
+                ":version": {"N": "2"}
+            }
+        });
+        let mut placeholders = Placeholders::new(&request);
+
+        // Act
+        let condition = Condition::parse(
+            "PK = :pk AND SK BETWEEN :from AND :to AND (attribute_not_exists(#trashed_at) OR #version <> :version)",
+            &mut placeholders,
+        )
+        .unwrap();
+
+        // Assert
+        assert!(condition.evaluate(&item()));
+        assert!(placeholders.check_unused().is_ok());
+    }
+
+    #[test]
+    fn test_update() {
+        // Arrange
+        let request = json!({
+            "ExpressionAttributeNames": {"#version": "Version"},
+            "ExpressionAttributeValues": {":one": {"N": "1"}, ":name": {"S": "name"}, ":tag": {"SS": ["c"]}}
+        });
+        let mut placeholders = Placeholders::new(&request);
+
+        // Act
+        let undefined = Update::parse("SET #name = :name", &mut placeholders);
+        let update = Update::parse(
+            "SET NameAttribute = :name REMOVE SK ADD #version :one, Tags :tag",
+            &mut placeholders,
+        )
+        .unwrap()
+        .apply(&item())
+        .unwrap();
+
+        // Assert
+        assert!(undefined.is_err());
+        assert_eq!(update["NameAttribute"], json!({"S": "name"}));
+        assert_eq!(update["Version"], json!({"N": "3"}));
+        assert_eq!(update["Tags"], json!({"SS": ["a", "b", "c"]}));
+        assert!(!update.contains_key("SK"));
+    }
+
+    #[test]
+    fn test_unused_placeholder() {
+        // Arrange
+        let request = json!({
+            "ExpressionAttributeValues": {":pk": {"S": "1984"}, ":unused": {"S": "1984"}}
+        });
+        let mut placeholders = Placeholders::new(&request);
+
+        // Act
+        Condition::parse("PK = :pk", &mut placeholders).unwrap();
+        let result = placeholders.check_unused();
+
+        // Assert
+        assert!(result.unwrap_err().contains(":unused"));
+    }
+}
@@ -0,0 +1,508 @@
+//! The fake of the S3 REST API
+//! The bucket is the first segment of the path, as the clients of this crate force the path style.
+
+use super::{response, FakeResponse};
+use aws_smithy_runtime_api::client::orchestrator::HttpRequest;
+use aws_smithy_types::base64;
+use aws_smithy_types::date_time::{DateTime, Format};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::time::SystemTime;
+
+/// The objects of `storage/scripts/ready.d/s3.sh`, which the tests expect in the bucket
+const SEEDED_KEYS: [&str; 5] = [
+    "1984/04/04/1984-04-04-12-34-50.MOV",
+    "1984/04/04/1984-04-04-12-34-51.MOV",
+    "1984/04/05/1984-04-05-12-34-50.MOV",
+    "1984/05/04/1984-05-04-12-34-50.MOV",
+    "1985/04/04/1985-04-04-12-34-50.MOV",
+];
+
+/// The body of the seeded objects
+pub(crate) const SEEDED_BODY: &[u8] = b"test";
+
+/// The keys of a listing that is not limited
+const DEFAULT_MAX_KEYS: usize = 1000;
+
+/// The buckets by the name
+#[derive(Debug, Default)]
+pub(super) struct Buckets(BTreeMap<String, Bucket>);
+
+/// The objects by the key
+type Bucket = BTreeMap<String, Object>;
+
+#[derive(Debug, Clone)]
+struct Object {
+    body: Vec<u8>,
+    last_modified: DateTime,
+    retain_until: Option<DateTime>,
+    legal_hold: bool,
+}
+
+impl Object {
+    fn new(body: Vec<u8>) -> Self {
+        Self {
+            body,
+            last_modified: DateTime::from(SystemTime::now()),
+            retain_until: None,
+            legal_hold: false,
+        }
+    }
+
+    fn etag(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.body.hash(&mut hasher);
+        format!("\"{:016x}\"", hasher.finish())
+    }
+}
+
+impl Buckets {
+    /// Create the bucket that has the seeded objects
+    pub(super) fn seed(&mut self, bucket_name: &str) {
+        let bucket = self.0.entry(bucket_name.to_string()).or_default();
+        for key in SEEDED_KEYS {
+            bucket.insert(key.to_string(), Object::new(SEEDED_BODY.to_vec()));
+        }
+    }
+}
+
+/// The query parameters of the request
+struct Query(HashMap<String, String>);
+
+impl Query {
+    fn parse(query: &str) -> Self {
+        Self(
+            query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| match pair.split_once('=') {
+                    Some((name, value)) => (percent_decode(name), percent_decode(value)),
+                    None => (percent_decode(pair), String::new()),
+                })
+                .collect(),
+        )
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+}
+
+pub(super) fn respond(buckets: &mut Buckets, request: &HttpRequest, body: &[u8]) -> FakeResponse {
+    let uri = request.uri();
+    let path_and_query = match uri.find("://") {
+        Some(scheme_end) => match uri[scheme_end + 3..].find('/') {
+            Some(path_start) => &uri[scheme_end + 3 + path_start..],
+            None => "/",
+        },
+        None => uri,
+    };
+    let (path, query) = match path_and_query.split_once('?') {
+        Some((path, query)) => (path, Query::parse(query)),
+        None => (path_and_query, Query::parse("")),
+    };
+    let (bucket_name, key) = match path.trim_start_matches('/').split_once('/') {
+        Some((bucket_name, key)) => (percent_decode(bucket_name), percent_decode(key)),
+        None => (percent_decode(path.trim_start_matches('/')), String::new()),
+    };
+    let method = request.method();
+    let is_head = method == "HEAD";
+
+    if key.is_empty() {
+        return match method {
+            "PUT" => {
+                buckets.0.entry(bucket_name).or_default();
+                response(200, &[], Vec::new())
+            }
+            "HEAD" if buckets.0.contains_key(&bucket_name) => response(200, &[], Vec::new()),
+            "GET" if query.get("list-type") == Some("2") => match buckets.0.get(&bucket_name) {
+                Some(bucket) => list_objects(&bucket_name, bucket, &query),
+                None => no_such_bucket(&bucket_name, is_head),
+            },
+            _ if !buckets.0.contains_key(&bucket_name) => no_such_bucket(&bucket_name, is_head),
+            _ => not_implemented(method, path, is_head),
+        };
+    }
+
+    // the source may be in another bucket, so it is read before the target bucket is borrowed
+    let copy_source = match request.headers().get("x-amz-copy-source") {
+        Some(copy_source) if method == "PUT" => match source_object(buckets, copy_source) {
+            Ok(object) => Some(object),
+            Err(response) => return *response,
+        },
+        _ => None,
+    };
+
+    let Some(bucket) = buckets.0.get_mut(&bucket_name) else {
+        return no_such_bucket(&bucket_name, is_head);
+    };
+
+    match method {
+        "GET" | "HEAD" => match bucket.get(&key) {
+            Some(object) => {
+                let body = match is_head {
+                    true => Vec::new(),
+                    false => object.body.clone(),
+                };
+                response(200, &object_headers(object), body)
+            }
+            None => no_such_key(&key, is_head),
+        },
+        "PUT" if query.contains("retention") => match bucket.get_mut(&key) {
+            Some(object) => {
+                let body = String::from_utf8_lossy(body);
+                let retain_until = element(&body, "RetainUntilDate")
+                    .and_then(|date| DateTime::from_str(date, Format::DateTimeWithOffset).ok());
+                match retain_until {
+                    Some(retain_until) => {
+                        object.retain_until = Some(retain_until);
+                        response(200, &[], Vec::new())
+                    }
+                    None => error(400, "MalformedXML", "The retention is invalid", false),
+                }
+            }
+            None => no_such_key(&key, false),
+        },
+        "PUT" if query.contains("legal-hold") => match bucket.get_mut(&key) {
+            Some(object) => {
+                let body = String::from_utf8_lossy(body);
+                match element(&body, "Status") {
+                    Some("ON") => object.legal_hold = true,
+                    Some("OFF") => object.legal_hold = false,
+                    _ => return error(400, "MalformedXML", "The legal hold is invalid", false),
+                }
+                response(200, &[], Vec::new())
+            }
+            None => no_such_key(&key, false),
+        },
+        "PUT" => {
+            let object = match copy_source {
+                Some(source) => Object::new(source.body),
+                None => Object::new(payload(request, body)),
+            };
+            let etag = object.etag();
+            let last_modified = format_date(&object.last_modified, Format::DateTime);
+            bucket.insert(key, object);
+
+            match request.headers().contains_key("x-amz-copy-source") {
+                true => response(
+                    200,
+                    &[],
+                    format!(
+                        r#"<?xml version="1.0" encoding="UTF-8"?><CopyObjectResult><ETag>{}</ETag><LastModified>{last_modified}</LastModified></CopyObjectResult>"#,
+                        escape(&etag)
+                    ),
+                ),
+                false => response(200, &[("ETag", etag)], Vec::new()),
+            }
+        }
+        "DELETE" => {
+            bucket.remove(&key);
+            response(204, &[], Vec::new())
+        }
+        _ => not_implemented(method, path, is_head),
+    }
+}
+
+/// The object of the `x-amz-copy-source`, which is `{bucket}/{key}` in the percent encoding
+fn source_object(buckets: &Buckets, copy_source: &str) -> Result<Object, Box<FakeResponse>> {
+    let copy_source = percent_decode(copy_source.split('?').next().unwrap_or_default());
+    let Some((bucket_name, key)) = copy_source.trim_start_matches('/').split_once('/') else {
+        return Err(Box::new(error(
+            400,
+            "InvalidArgument",
+            "Invalid copy source",
+            false,
+        )));
+    };
+
+    match buckets.0.get(bucket_name) {
+        Some(bucket) => match bucket.get(key) {
+            Some(object) => Ok(object.clone()),
+            None => Err(Box::new(no_such_key(key, false))),
+        },
+        None => Err(Box::new(no_such_bucket(bucket_name, false))),
+    }
+}
+
+fn object_headers(object: &Object) -> Vec<(&'static str, String)> {
+    let mut headers = vec![
+        ("Content-Length", object.body.len().to_string()),
+        ("ETag", object.etag()),
+        (
+            "Last-Modified",
+            format_date(&object.last_modified, Format::HttpDate),
+        ),
+    ];
+    if let Some(retain_until) = &object.retain_until {
+        headers.push(("x-amz-object-lock-mode", "GOVERNANCE".to_string()));
+        headers.push((
+            "x-amz-object-lock-retain-until-date",
+            format_date(retain_until, Format::DateTime),
+        ));
+    }
+    if object.legal_hold {
+        headers.push(("x-amz-object-lock-legal-hold", "ON".to_string()));
+    }
+    headers
+}
+
+/// A common prefix or an object of a listing
+enum Entry<'a> {
+    Prefix(&'a str),
+    Object(&'a str, &'a Object),
+}
+
+impl Entry<'_> {
+    fn name(&self) -> &str {
+        match self {
+            Entry::Prefix(prefix) => prefix,
+            Entry::Object(key, _) => key,
+        }
+    }
+}
+
+/// ListObjectsV2
+/// The continuation token is the last key or the last common prefix of the previous page.
+fn list_objects(bucket_name: &str, bucket: &Bucket, query: &Query) -> FakeResponse {
+    let prefix = query.get("prefix").unwrap_or_default();
+    let delimiter = query
+        .get("delimiter")
+        .filter(|delimiter| !delimiter.is_empty());
+    let max_keys = match query.get("max-keys").map(str::parse::<usize>) {
+        Some(Ok(max_keys)) => max_keys,
+        Some(Err(_)) => return error(400, "InvalidArgument", "Invalid max-keys", false),
+        None => DEFAULT_MAX_KEYS,
+    };
+    let after = match query.get("continuation-token") {
+        Some(token) => match base64::decode(token).map(String::from_utf8) {
+            Ok(Ok(after)) => Some(after),
+            _ => return error(400, "InvalidArgument", "Invalid continuation token", false),
+        },
+        None => None,
+    };
+
+    let mut entries: Vec<Entry> = Vec::new();
+    for (key, object) in bucket.range(prefix.to_string()..) {
+        if !key.starts_with(prefix) {
+            break;
+        }
+
+        let common_prefix = delimiter.and_then(|delimiter| {
+            key[prefix.len()..]
+                .find(delimiter)
+                .map(|index| &key[..prefix.len() + index + delimiter.len()])
+        });
+        let entry = match common_prefix {
+            Some(common_prefix) => Entry::Prefix(common_prefix),
+            None => Entry::Object(key, object),
+        };
+
+        if after.as_deref().is_some_and(|after| entry.name() <= after) {
+            continue;
+        }
+        if entries
+            .last()
+            .is_some_and(|last| last.name() == entry.name())
+        {
+            continue;
+        }
+        entries.push(entry);
+    }
+
+    let is_truncated = entries.len() > max_keys;
+    entries.truncate(max_keys);
+
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><Name>{}</Name><Prefix>{}</Prefix><KeyCount>{}</KeyCount><MaxKeys>{max_keys}</MaxKeys><IsTruncated>{is_truncated}</IsTruncated>"#,
+        escape(bucket_name),
+        escape(prefix),
+        entries.len()
+    );
+    if let Some(delimiter) = delimiter {
+        xml.push_str(&format!("<Delimiter>{}</Delimiter>", escape(delimiter)));
+    }
+    if let Some(token) = query.get("continuation-token") {
+        xml.push_str(&format!(
+            "<ContinuationToken>{}</ContinuationToken>",
+            escape(token)
+        ));
+    }
+    if let (true, Some(last)) = (is_truncated, entries.last()) {
+        xml.push_str(&format!(
+            "<NextContinuationToken>{}</NextContinuationToken>",
+            base64::encode(last.name())
+        ));
+    }
+    for entry in &entries {
+        match entry {
+            Entry::Object(key, object) => xml.push_str(&format!(
+                "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                escape(key),
+                format_date(&object.last_modified, Format::DateTime),
+                escape(&object.etag()),
+                object.body.len()
+            )),
+            Entry::Prefix(prefix) => xml.push_str(&format!(
+                "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+                escape(prefix)
+            )),
+        }
+    }
+    xml.push_str("</ListBucketResult>");
+
+    response(200, &[("Content-Type", "application/xml".to_string())], xml)
+}
+
+/// The body of the object
+/// The SDK may send it in the `aws-chunked` encoding with the checksum in the trailer.
+fn payload(request: &HttpRequest, body: &[u8]) -> Vec<u8> {
+    let is_chunked = request
+        .headers()
+        .get("content-encoding")
+        .is_some_and(|encoding| encoding.contains("aws-chunked"));
+
+    match is_chunked {
+        true => decode_aws_chunked(body).unwrap_or_else(|| body.to_vec()),
+        false => body.to_vec(),
+    }
+}
+
+/// Decode `{size in hex}[;extensions]\r\n{data}\r\n ... 0\r\n{trailers}`
+fn decode_aws_chunked(body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    let mut position = 0;
+
+    loop {
+        let line_end = position + body[position..].windows(2).position(|w| w == b"\r\n")?;
+        let header = std::str::from_utf8(&body[position..line_end]).ok()?;
+        let size = usize::from_str_radix(header.split(';').next()?.trim(), 16).ok()?;
+        position = line_end + 2;
+
+        if size == 0 {
+            return Some(decoded);
+        }
+
+        decoded.extend_from_slice(body.get(position..position + size)?);
+        position += size + 2;
+    }
+}
+
+/// The text of the first element of the name
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{name}>"))?;
+    Some(xml[start..end].trim())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn format_date(date_time: &DateTime, format: Format) -> String {
+    date_time.fmt(format).unwrap_or_default()
+}
+
+/// The error response
+/// The response of HEAD has no body, and the SDK tells the error by the status.
+fn error(status: u16, code: &str, message: &str, is_head: bool) -> FakeResponse {
+    let body = match is_head {
+        true => String::new(),
+        false => format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>{code}</Code><Message>{}</Message><RequestId>fake</RequestId></Error>"#,
+            escape(message)
+        ),
+    };
+    response(
+        status,
+        &[("Content-Type", "application/xml".to_string())],
+        body,
+    )
+}
+
+fn no_such_bucket(bucket_name: &str, is_head: bool) -> FakeResponse {
+    error(
+        404,
+        "NoSuchBucket",
+        &format!("The bucket {bucket_name} does not exist"),
+        is_head,
+    )
+}
+
+fn no_such_key(key: &str, is_head: bool) -> FakeResponse {
+    error(
+        404,
+        "NoSuchKey",
+        &format!("The key {key} does not exist"),
+        is_head,
+    )
+}
+
+fn not_implemented(method: &str, path: &str, is_head: bool) -> FakeResponse {
+    error(
+        501,
+        "NotImplemented",
+        &format!("{method} {path} is not implemented by the fake"),
+        is_head,
+    )
+}
+
+/// Decode the `%XX` of the URI
+fn percent_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_aws_chunked() {
+        // Arrange
+        let body = b"5;chunk-signature=abc\r\nhello\r\n6\r\n world\r\n0\r\nx-amz-checksum-crc32:AAAA\r\n\r\n";
+
+        // Act
+        let result = decode_aws_chunked(body);
+
+        // Assert
+        assert_eq!(result.unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        // Act
+        let result = percent_decode("1984/04/04/a%20b%2Fc%");
+
+        // Assert
+        assert_eq!(result, "1984/04/04/a b/c%");
+    }
+}
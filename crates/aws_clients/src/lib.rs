@@ -1,6 +1,26 @@
-//! To run tests, it required to run docker compose beforehand
+//! The tests run against the in-process fakes of S3 and DynamoDB in `fake`, so they don't need docker compose.
+//! The public calls of the clients are in the `tracing` spans that have the table or the bucket, and the key,
+//! so the calls of a request are correlated by its span. The failed calls are recorded as the warnings.
 
+#[cfg(any(feature = "db", feature = "standard-storage"))]
+pub mod audit;
+pub mod concurrency;
+#[cfg(any(feature = "db", feature = "standard-storage"))]
+mod config;
 #[cfg(feature = "db")]
 pub mod dynamodb;
+#[cfg(any(feature = "db", feature = "standard-storage"))]
+mod error;
+#[cfg(all(
+    any(test, feature = "fake"),
+    any(feature = "db", feature = "standard-storage")
+))]
+pub mod fake;
+#[cfg(any(feature = "db", feature = "standard-storage"))]
+pub mod protection;
+pub mod reconcile;
+pub mod retry;
 #[cfg(feature = "standard-storage")]
 pub mod s3;
+#[cfg(all(feature = "db", feature = "standard-storage"))]
+pub mod trash_cleanup;
@@ -0,0 +1,72 @@
+//! The protection of the media against the deletion
+//! A protected media, e.g. the video of a birth, cannot be trashed, moved, or purged.
+//! The DynamoDB client keeps it on the collection item, and the S3 client maps it to the S3 Object Lock.
+
+use shared::error::Error;
+/// The retention and the legal hold of a media
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Protection {
+    /// Epoch time (ms) until that the media is retained
+    pub retain_until: Option<u128>,
+    /// The media is held until the hold is removed
+    pub legal_hold: bool,
+}
+
+impl Protection {
+    /// If true, the media cannot be deleted or moved at the time.
+    /// time is mill sec
+    pub fn is_protected(&self, now: u128) -> bool {
+        self.legal_hold
+            || self
+                .retain_until
+                .is_some_and(|retain_until| now < retain_until)
+    }
+
+    /// Returns Err that tells why the media is protected
+    pub(crate) fn check(&self, key_name: &str, now: u128) -> Result<(), Error> {
+        if self.legal_hold {
+            return Err(Error::conflict(format!(
+                "{key_name} is under the legal hold"
+            )));
+        }
+
+        match self.retain_until {
+            Some(retain_until) if now < retain_until => Err(Error::conflict(format!(
+                "{key_name} is retained until {retain_until}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_protected() {
+        // Arrange
+        let retained = Protection {
+            retain_until: Some(1_000),
+            legal_hold: false,
+        };
+        let held = Protection {
+            retain_until: None,
+            legal_hold: true,
+        };
+
+        // Act
+        let result = [
+            retained.is_protected(999),
+            retained.is_protected(1_000),
+            held.is_protected(u128::MAX),
+            Protection::default().is_protected(0),
+        ];
+
+        // Assert
+        assert_eq!(result, [true, false, true, false]);
+        assert!(retained.check("key", 999).is_err());
+        assert!(held.check("key", 0).is_err());
+        assert!(retained.check("key", 1_000).is_ok());
+    }
+}
@@ -0,0 +1,474 @@
+//! The reconciliation of two sources of the file list, e.g. the bucket and the table
+//! They can drift: an object is uploaded but not ingested, or an item remains after its object is deleted.
+//! The sources are walked from the years to the objects, and the key names are compared after the normalization,
+//! so the unpadded and the padded keys are the same.
+
+use crate::concurrency::Concurrency;
+use shared::error::Error;
+use shared::traits::GetFileListTrait;
+use std::collections::BTreeSet;
+use time_file_name::file_datetime::PathDateTime;
+
+/// The result of the comparison of the sources A and B
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReconciliationReport {
+    /// The key names that only A has
+    pub only_in_a: Vec<String>,
+    /// The key names that only B has
+    pub only_in_b: Vec<String>,
+    /// The key names that both have
+    pub in_both: Vec<String>,
+    /// The entries that don't follow the key name convention
+    pub invalid: Vec<String>,
+}
+
+impl ReconciliationReport {
+    /// true if the sources have the same key names
+    pub fn is_consistent(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty()
+    }
+
+    fn merge(&mut self, other: ReconciliationReport) {
+        self.only_in_a.extend(other.only_in_a);
+        self.only_in_b.extend(other.only_in_b);
+        self.in_both.extend(other.in_both);
+        self.invalid.extend(other.invalid);
+    }
+}
+
+/// Compare the key names of the sources
+/// The years, the months, and the days are walked concurrently by [Concurrency::default].
+/// The key names in the report are sorted.
+pub async fn reconcile(
+    a: &impl GetFileListTrait,
+    b: &impl GetFileListTrait,
+) -> Result<ReconciliationReport, Error> {
+    reconcile_with(a, b, &Concurrency::default()).await
+}
+
+/// Compare the key names of the sources by the concurrency
+/// The limit applies to the years, the months of a year, and the days of a month each.
+#[tracing::instrument(skip_all, err(level = "warn"))]
+pub async fn reconcile_with(
+    a: &impl GetFileListTrait,
+    b: &impl GetFileListTrait,
+    concurrency: &Concurrency,
+) -> Result<ReconciliationReport, Error> {
+    let mut report = ReconciliationReport::default();
+
+    let (years_a, years_b) = futures::try_join!(a.get_years(), b.get_years())?;
+    let years = numbers(years_a, years_b, &mut report);
+
+    for year_report in concurrency
+        .run(
+            years
+                .into_iter()
+                .map(|year| reconcile_year(a, b, year, concurrency)),
+        )
+        .await?
+    {
+        report.merge(year_report);
+    }
+
+    report.only_in_a.sort();
+    report.only_in_b.sort();
+    report.in_both.sort();
+    report.invalid.sort();
+
+    Ok(report)
+}
+
+async fn reconcile_year(
+    a: &impl GetFileListTrait,
+    b: &impl GetFileListTrait,
+    year: usize,
+    concurrency: &Concurrency,
+) -> Result<ReconciliationReport, Error> {
+    let mut report = ReconciliationReport::default();
+
+    let (months_a, months_b) = futures::try_join!(a.get_months(year), b.get_months(year))?;
+    let months = numbers(months_a, months_b, &mut report);
+
+    for month_report in concurrency
+        .run(
+            months
+                .into_iter()
+                .map(|month| reconcile_month(a, b, year, month, concurrency)),
+        )
+        .await?
+    {
+        report.merge(month_report);
+    }
+
+    Ok(report)
+}
+
+async fn reconcile_month(
+    a: &impl GetFileListTrait,
+    b: &impl GetFileListTrait,
+    year: usize,
+    month: usize,
+    concurrency: &Concurrency,
+) -> Result<ReconciliationReport, Error> {
+    let mut report = ReconciliationReport::default();
+
+    let (days_a, days_b) = futures::try_join!(a.get_days(year, month), b.get_days(year, month))?;
+    let days = numbers(days_a, days_b, &mut report);
+
+    for day_report in concurrency
+        .run(
+            days.into_iter()
+                .map(|day| reconcile_day(a, b, year, month, day)),
+        )
+        .await?
+    {
+        report.merge(day_report);
+    }
+
+    Ok(report)
+}
+
+async fn reconcile_day(
+    a: &impl GetFileListTrait,
+    b: &impl GetFileListTrait,
+    year: usize,
+    month: usize,
+    day: usize,
+) -> Result<ReconciliationReport, Error> {
+    let mut report = ReconciliationReport::default();
+
+    let (objects_a, objects_b) = futures::try_join!(
+        a.get_objects(year, month, day),
+        b.get_objects(year, month, day)
+    )?;
+    let key_names_a = key_names(objects_a, (year, month, day), &mut report);
+    let key_names_b = key_names(objects_b, (year, month, day), &mut report);
+
+    report.only_in_a = key_names_a.difference(&key_names_b).cloned().collect();
+    report.only_in_b = key_names_b.difference(&key_names_a).cloned().collect();
+    report.in_both = key_names_a.intersection(&key_names_b).cloned().collect();
+
+    Ok(report)
+}
+
+/// The union of the years, the months, or the days of the sources
+/// The bucket pads them, but the table doesn't, so they are compared as numbers.
+fn numbers(a: Vec<String>, b: Vec<String>, report: &mut ReconciliationReport) -> BTreeSet<usize> {
+    let mut numbers = BTreeSet::new();
+
+    for entry in a.into_iter().chain(b) {
+        match entry.parse::<usize>() {
+            Ok(number) => {
+                numbers.insert(number);
+            }
+            Err(_) => report.invalid.push(entry),
+        }
+    }
+
+    numbers
+}
+
+/// The normalized key names of the objects of the day
+/// The bucket lists the file names, so the date is added to them.
+fn key_names(
+    objects: Vec<String>,
+    (year, month, day): (usize, usize, usize),
+    report: &mut ReconciliationReport,
+) -> BTreeSet<String> {
+    let mut key_names = BTreeSet::new();
+
+    for object in objects {
+        let path = match object.contains('/') {
+            true => object.clone(),
+            false => format!("{year}/{month}/{day}/{object}"),
+        };
+
+        match PathDateTime::parse(&path) {
+            Ok(path_date_time) => {
+                key_names.insert(path_date_time.key_name());
+            }
+            Err(_) => report.invalid.push(object),
+        }
+    }
+
+    key_names
+}
+
+#[cfg(feature = "db")]
+mod repair {
+    use super::ReconciliationReport;
+    use crate::dynamodb::client::{DynamoClientTrait, DynamoDbClient};
+    use crate::dynamodb::entities::collection::CollectionItem;
+    use shared::error::Error;
+
+    /// What the repair has done
+    #[derive(Debug, Default, PartialEq)]
+    pub struct RepairSummary {
+        /// The objects that are saved as new items
+        pub ingested: Vec<String>,
+        /// The items whose date lookups are added again
+        pub relinked: Vec<String>,
+        /// The items that are moved to the trash
+        pub trashed: Vec<String>,
+        /// The date lookups that are removed because they don't have the items
+        pub unlinked: Vec<String>,
+        /// The objects whose items are in the trash. They are removed when the trash is purged.
+        pub skipped: Vec<String>,
+        /// The key names that couldn't be repaired, e.g. the protected items
+        pub failed: Vec<(String, Error)>,
+    }
+
+    impl DynamoDbClient {
+        /// Apply the fixes of the report whose A is the bucket and B is this table.
+        /// The object only in the bucket is saved as a new item, and the item only in the table is moved to the trash.
+        /// A failure of a key doesn't stop the others.
+        #[tracing::instrument(
+            skip_all,
+            fields(
+                table = %self.table_name,
+                only_in_a = report.only_in_a.len(),
+                only_in_b = report.only_in_b.len()
+            ),
+            err(level = "warn")
+        )]
+        pub async fn repair(
+            &self,
+            report: &ReconciliationReport,
+            trashed_by: &str,
+        ) -> Result<RepairSummary, Error> {
+            let mut summary = RepairSummary::default();
+
+            for key_name in &report.only_in_a {
+                match self.repair_missing_item(key_name, &mut summary).await {
+                    Ok(()) => {}
+                    Err(e) => summary.failed.push((key_name.to_string(), e)),
+                }
+            }
+
+            for key_name in &report.only_in_b {
+                match self
+                    .repair_orphan_item(key_name, trashed_by, &mut summary)
+                    .await
+                {
+                    Ok(()) => {}
+                    Err(e) => summary.failed.push((key_name.to_string(), e)),
+                }
+            }
+
+            Ok(summary)
+        }
+
+        /// The object is in the bucket, but the table doesn't list it.
+        async fn repair_missing_item(
+            &self,
+            key_name: &str,
+            summary: &mut RepairSummary,
+        ) -> Result<(), Error> {
+            if let Some(collection) = self.get_collection_item(key_name).await? {
+                self.put_lookups(&[collection]).await?;
+                summary.relinked.push(key_name.to_string());
+                return Ok(());
+            }
+
+            // the object of the trashed item is kept until it is purged
+            if self.collection_item_exists(key_name).await? {
+                summary.skipped.push(key_name.to_string());
+                return Ok(());
+            }
+
+            self.put_collection_items(&vec![CollectionItem::new_object(key_name, "")?])
+                .await?;
+            summary.ingested.push(key_name.to_string());
+            Ok(())
+        }
+
+        /// The table lists the key, but the bucket doesn't have the object.
+        async fn repair_orphan_item(
+            &self,
+            key_name: &str,
+            trashed_by: &str,
+            summary: &mut RepairSummary,
+        ) -> Result<(), Error> {
+            match self.get_collection_item(key_name).await? {
+                Some(_) => {
+                    self.trash_collection_item(key_name, trashed_by, None)
+                        .await?;
+                    summary.trashed.push(key_name.to_string());
+                }
+                None => {
+                    self.remove_lookup(key_name).await?;
+                    summary.unlinked.push(key_name.to_string());
+                }
+            }
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "db")]
+pub use repair::RepairSummary;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::concurrency::ErrorMode;
+
+    /// The source that lists the key names in the memory
+    struct KeyList(Vec<&'static str>);
+
+    impl KeyList {
+        fn parts(&self) -> Vec<Vec<&'static str>> {
+            self.0.iter().map(|key| key.split('/').collect()).collect()
+        }
+
+        fn entries(&self, prefix: &[&str], level: usize) -> Vec<String> {
+            let entries = self
+                .parts()
+                .into_iter()
+                .filter(|parts| parts[..level] == *prefix)
+                .map(|parts| parts[level].to_string())
+                .collect::<BTreeSet<String>>();
+            entries.into_iter().collect()
+        }
+    }
+
+    impl GetFileListTrait for KeyList {
+        async fn get_years(&self) -> Result<Vec<String>, Error> {
+            Ok(self.entries(&[], 0))
+        }
+
+        async fn get_months(&self, year: usize) -> Result<Vec<String>, Error> {
+            let year = year.to_string();
+            Ok(self.entries(&[&year], 1))
+        }
+
+        async fn get_days(&self, year: usize, month: usize) -> Result<Vec<String>, Error> {
+            let (year, month) = (year.to_string(), format!("{month:02}"));
+            Ok(self.entries(&[&year, &month], 2))
+        }
+
+        async fn get_objects(
+            &self,
+            year: usize,
+            month: usize,
+            day: usize,
+        ) -> Result<Vec<String>, Error> {
+            let (year, month, day) = (year.to_string(), format!("{month:02}"), format!("{day:02}"));
+            Ok(self.entries(&[&year, &month, &day], 3))
+        }
+
+        async fn get_objects_page(
+            &self,
+            year: usize,
+            month: usize,
+            day: usize,
+            _limit: i32,
+            _cursor: Option<String>,
+        ) -> Result<shared::traits::Page, Error> {
+            Ok(shared::traits::Page {
+                items: self.get_objects(year, month, day).await?,
+                next_cursor: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconcile() {
+        // Arrange
+        let bucket = KeyList(vec![
+            "1984/04/04/1984-04-04-12-34-50.MOV",
+            "1984/04/04/1984-04-04-12-34-51.MOV",
+            "1985/01/01/1985-01-01-00-00-00.MOV",
+            "1985/01/01/thumbnail.png",
+        ]);
+        let table = KeyList(vec![
+            "1984/04/04/1984-04-04-12-34-50.MOV",
+            "1984/04/05/1984-04-05-12-34-50.MOV",
+        ]);
+
+        // Act
+        let result = reconcile(&bucket, &table).await.unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            ReconciliationReport {
+                only_in_a: vec![
+                    "1984/04/04/1984-04-04-12-34-51.MOV".to_string(),
+                    "1985/01/01/1985-01-01-00-00-00.MOV".to_string()
+                ],
+                only_in_b: vec!["1984/04/05/1984-04-05-12-34-50.MOV".to_string()],
+                in_both: vec!["1984/04/04/1984-04-04-12-34-50.MOV".to_string()],
+                invalid: vec!["thumbnail.png".to_string()],
+            }
+        );
+        assert!(!result.is_consistent());
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_one_at_a_time() {
+        // Arrange
+        let bucket = KeyList(vec![
+            "1984/04/04/1984-04-04-12-34-50.MOV",
+            "1985/01/01/1985-01-01-00-00-00.MOV",
+        ]);
+        let table = KeyList(vec!["1984/04/05/1984-04-05-12-34-50.MOV"]);
+        let concurrency = Concurrency::new(1, ErrorMode::CollectAll);
+
+        // Act
+        let result = reconcile_with(&bucket, &table, &concurrency).await;
+
+        // Assert
+        assert_eq!(result.unwrap(), reconcile(&bucket, &table).await.unwrap());
+    }
+
+    #[test]
+    fn test_key_names_are_normalized() {
+        // Arrange
+        let mut report = ReconciliationReport::default();
+
+        // Act
+        let file_names = key_names(
+            vec!["1984-04-04-12-34-50.MOV".to_string()],
+            (1984, 4, 4),
+            &mut report,
+        );
+        let unpadded = key_names(
+            vec!["1984/4/4/1984-4-4-12-34-50.MOV".to_string()],
+            (1984, 4, 4),
+            &mut report,
+        );
+
+        // Assert
+        assert_eq!(file_names, unpadded);
+        assert!(report.invalid.is_empty());
+    }
+
+    #[cfg(feature = "db")]
+    #[tokio::test]
+    async fn test_repair() {
+        use crate::dynamodb::client::{DynamoClientTrait, DynamoDbClient};
+        use crate::dynamodb::entities::collection::CollectionItem;
+
+        // Arrange
+        let client = DynamoDbClient::new("test_repair").await;
+        client
+            .put_collection_items(&vec![CollectionItem::dummy_object(
+                "1984/04/05/1984-04-05-12-34-50.MOV",
+            )])
+            .await
+            .unwrap();
+        let bucket = KeyList(vec!["1984/04/04/1984-04-04-12-34-50.MOV"]);
+        let report = reconcile(&bucket, &client).await.unwrap();
+
+        // Act
+        let result = client.repair(&report, "reconciler").await.unwrap();
+
+        // Assert
+        assert_eq!(result.ingested, ["1984/04/04/1984-04-04-12-34-50.MOV"]);
+        assert_eq!(result.trashed, ["1984/04/05/1984-04-05-12-34-50.MOV"]);
+        assert!(result.failed.is_empty());
+        assert!(reconcile(&bucket, &client).await.unwrap().is_consistent());
+    }
+}
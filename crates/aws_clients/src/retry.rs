@@ -0,0 +1,415 @@
+//! The retry policy of the AWS calls
+//! A failed call is retried by the kind of its error. The SDK clients don't retry, so the policy makes all the attempts.
+//! The throttled calls wait longer, and the calls that can't succeed by retrying are not retried.
+
+use shared::error::{Error, ErrorKind};
+use shared::metrics::{MetricSet, Unit};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+/// How the failed call is retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retryability {
+    /// The service throttles the requests
+    Throttling,
+    /// The service or the network fails for a while
+    Transient,
+    /// Retrying doesn't change the result
+    Permanent,
+}
+
+impl Retryability {
+    pub fn of(error: &Error) -> Self {
+        match error.kind() {
+            ErrorKind::Throttled => Self::Throttling,
+            ErrorKind::UpstreamUnavailable => Self::Transient,
+            _ => Self::Permanent,
+        }
+    }
+}
+
+/// The policy of the retries
+/// The backoff is the exponential one with the full jitter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The number of the attempts including the first one, so 1 means no retry
+    pub max_attempts: u32,
+    /// The base of the backoff of the transient errors
+    pub base_delay: Duration,
+    /// The base of the backoff of the throttled calls
+    pub throttling_base_delay: Duration,
+    /// The cap of a backoff
+    pub max_delay: Duration,
+    /// The time limit of all the attempts and the backoffs
+    pub deadline: Option<Duration>,
+    /// The counts of the calls, which the default policies share in the process
+    counters: Arc<Counters>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(50),
+            throttling_base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(5),
+            deadline: Some(Duration::from_secs(20)),
+            counters: PROCESS_COUNTERS.clone(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The policy that calls only once
+    pub fn no_retry() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Call until it succeeds, the error is permanent, the attempts run out, or the deadline passes
+    /// The operation is the name of the call in the logs.
+    pub async fn run<T, F, Fut>(&self, operation: &str, mut call: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let started_at = Instant::now();
+        let mut attempt = 1;
+
+        loop {
+            self.counters.attempts.fetch_add(1, Ordering::Relaxed);
+
+            let result = match self.remaining(started_at) {
+                Some(remaining) => match tokio::time::timeout(remaining, call()).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        self.counters
+                            .deadline_exceeded
+                            .fetch_add(1, Ordering::Relaxed);
+                        return Err(Error::upstream_unavailable(format!(
+                            "{operation} didn't finish before the deadline"
+                        )));
+                    }
+                },
+                None => call().await,
+            };
+
+            let error = match result {
+                Ok(output) => return Ok(output),
+                Err(e) => e,
+            };
+
+            let retryability = Retryability::of(&error);
+            if retryability == Retryability::Permanent {
+                return Err(error);
+            }
+            if attempt >= self.max_attempts {
+                self.counters.exhausted.fetch_add(1, Ordering::Relaxed);
+                return Err(error);
+            }
+
+            let delay = self.backoff(attempt, retryability);
+            if self
+                .remaining(started_at)
+                .is_some_and(|remaining| remaining <= delay)
+            {
+                self.counters
+                    .deadline_exceeded
+                    .fetch_add(1, Ordering::Relaxed);
+                return Err(error);
+            }
+
+            self.counters.retries.fetch_add(1, Ordering::Relaxed);
+            if retryability == Retryability::Throttling {
+                self.counters
+                    .throttled_retries
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+            tracing::warn!(
+                operation,
+                attempt,
+                max_attempts = self.max_attempts,
+                ?delay,
+                error_kind = ?error.kind(),
+                "The call failed, and is retried: {error}"
+            );
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// The backoff after the attempt, which starts from 1
+    /// It is a random duration up to the exponential one.
+    pub fn backoff(&self, attempt: u32, retryability: Retryability) -> Duration {
+        let base = match retryability {
+            Retryability::Throttling => self.throttling_base_delay,
+            _ => self.base_delay,
+        };
+        let exponential = base
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        exponential.mul_f64(fastrand::f64())
+    }
+
+    /// The counts of the calls of this policy
+    pub fn stats(&self) -> RetryStats {
+        self.counters.stats()
+    }
+
+    fn remaining(&self, started_at: Instant) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_sub(started_at.elapsed()))
+    }
+}
+
+/// The counts of the retries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RetryStats {
+    /// The calls including the retries
+    pub attempts: u64,
+    pub retries: u64,
+    /// The retries of the throttled calls, which are also counted in the retries
+    pub throttled_retries: u64,
+    /// The calls that failed after all the attempts
+    pub exhausted: u64,
+    /// The calls that failed by the deadline
+    pub deadline_exceeded: u64,
+}
+
+impl RetryStats {
+    /// The counts after the earlier ones, e.g. of an invocation of a lambda
+    pub fn since(&self, earlier: &RetryStats) -> RetryStats {
+        RetryStats {
+            attempts: self.attempts.saturating_sub(earlier.attempts),
+            retries: self.retries.saturating_sub(earlier.retries),
+            throttled_retries: self
+                .throttled_retries
+                .saturating_sub(earlier.throttled_retries),
+            exhausted: self.exhausted.saturating_sub(earlier.exhausted),
+            deadline_exceeded: self
+                .deadline_exceeded
+                .saturating_sub(earlier.deadline_exceeded),
+        }
+    }
+
+    /// Add the counts to the metrics
+    pub fn add_to(&self, metric_set: MetricSet) -> MetricSet {
+        metric_set
+            .metric("AwsCalls", self.attempts as f64, Unit::Count)
+            .metric("Retries", self.retries as f64, Unit::Count)
+            .metric(
+                "ThrottledRetries",
+                self.throttled_retries as f64,
+                Unit::Count,
+            )
+            .metric("RetriesExhausted", self.exhausted as f64, Unit::Count)
+            .metric(
+                "DeadlineExceeded",
+                self.deadline_exceeded as f64,
+                Unit::Count,
+            )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    attempts: AtomicU64,
+    retries: AtomicU64,
+    throttled_retries: AtomicU64,
+    exhausted: AtomicU64,
+    deadline_exceeded: AtomicU64,
+}
+
+impl Counters {
+    fn stats(&self) -> RetryStats {
+        RetryStats {
+            attempts: self.attempts.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            throttled_retries: self.throttled_retries.load(Ordering::Relaxed),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+            deadline_exceeded: self.deadline_exceeded.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The policies are compared by the settings, not by the counts
+impl PartialEq for Counters {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+static PROCESS_COUNTERS: LazyLock<Arc<Counters>> = LazyLock::new(Arc::default);
+
+/// The counts of the retries of the default policies since the process started
+pub fn retry_stats() -> RetryStats {
+    PROCESS_COUNTERS.stats()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    /// The policy that has its own counters, so the tests that run at the same time don't share them
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            throttling_base_delay: Duration::from_millis(2),
+            max_delay: Duration::from_millis(10),
+            deadline: Some(Duration::from_secs(1)),
+            counters: Arc::default(),
+        }
+    }
+
+    /// The call that fails with the error until the attempt
+    async fn fail_until(calls: &AtomicU32, succeeds_at: u32, error: Error) -> Result<u32, Error> {
+        let attempt = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        match attempt < succeeds_at {
+            true => Err(error),
+            false => Ok(attempt),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transient_error_is_retried() {
+        // Arrange
+        let calls = AtomicU32::new(0);
+        let policy = policy();
+
+        // Act
+        let result = policy
+            .run("Test", || {
+                fail_until(&calls, 3, Error::upstream_unavailable("timeout"))
+            })
+            .await;
+
+        // Assert
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(
+            policy.stats(),
+            RetryStats {
+                attempts: 3,
+                retries: 2,
+                ..RetryStats::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_permanent_error_is_not_retried() {
+        // Arrange
+        let calls = AtomicU32::new(0);
+
+        // Act
+        let result = policy()
+            .run("Test", || {
+                fail_until(&calls, 3, Error::not_found("NoSuchKey"))
+            })
+            .await;
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_attempts_run_out() {
+        // Arrange
+        let calls = AtomicU32::new(0);
+        let policy = policy();
+
+        // Act
+        let result = policy
+            .run("Test", || {
+                fail_until(&calls, 10, Error::throttled("SlowDown"))
+            })
+            .await;
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Throttled);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            policy.stats(),
+            RetryStats {
+                attempts: 3,
+                retries: 2,
+                throttled_retries: 2,
+                exhausted: 1,
+                deadline_exceeded: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deadline() {
+        // Arrange
+        let policy = RetryPolicy {
+            deadline: Some(Duration::from_millis(10)),
+            ..policy()
+        };
+
+        // Act
+        let result = policy
+            .run("Test", || async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Ok(())
+            })
+            .await;
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::UpstreamUnavailable);
+        assert_eq!(policy.stats().deadline_exceeded, 1);
+    }
+
+    #[test]
+    fn test_stats_since() {
+        // Arrange
+        let earlier = RetryStats {
+            attempts: 3,
+            retries: 1,
+            ..RetryStats::default()
+        };
+        let later = RetryStats {
+            attempts: 7,
+            retries: 2,
+            exhausted: 1,
+            ..RetryStats::default()
+        };
+
+        // Act
+        let result = later.since(&earlier);
+
+        // Assert
+        assert_eq!(
+            result,
+            RetryStats {
+                attempts: 4,
+                retries: 1,
+                exhausted: 1,
+                ..RetryStats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        // Arrange
+        let policy = policy();
+
+        // Act
+        let first = policy.backoff(1, Retryability::Transient);
+        let capped = policy.backoff(30, Retryability::Throttling);
+
+        // Assert
+        assert!(first <= Duration::from_millis(1));
+        assert!(capped <= Duration::from_millis(10));
+    }
+}
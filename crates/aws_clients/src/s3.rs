@@ -1,4 +1,4 @@
 pub mod client;
 pub(crate) mod environment_value;
 #[cfg(test)]
-mod test_utils;
+pub(crate) mod test_utils;
@@ -1,66 +1,242 @@
+use crate::audit::{self, AuditAction, Auditor};
+use crate::error::aws_error;
+use crate::protection::Protection;
+use crate::retry::RetryPolicy;
 use crate::s3::environment_value::{s3_client, standard_bucked_name};
-use aws_sdk_dynamodb::error::ProvideErrorMetadata;
 use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Output;
 use aws_sdk_s3::presigning::PresigningConfig;
-use shared::traits::GetFileListTrait;
+use aws_sdk_s3::primitives::DateTime;
+use aws_sdk_s3::types::{
+    ObjectLockLegalHold, ObjectLockLegalHoldStatus, ObjectLockRetention, ObjectLockRetentionMode,
+};
+use futures::TryFutureExt;
+use shared::error::{Error, ErrorKind};
+use shared::traits::{GetFileListTrait, Page};
 use std::future::Future;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use time_file_name::file_path::FilePath;
 
 /// The expiring time for the s3 pre-signed URL
-static PRE_SIGN_EXPIRING_TIME: Duration = Duration::from_secs(5 * 60);
+pub static PRE_SIGN_EXPIRING_TIME: Duration = Duration::from_secs(5 * 60);
 
 /// The client for the standard bucket
 pub struct StandardS3Client {
     pub(crate) client: &'static aws_sdk_s3::Client,
+    /// the bucket that this client reads and writes
+    pub(crate) bucket_name: &'static str,
+    /// emits the audit records of the mutating calls if it's set
+    pub(crate) auditor: Option<Auditor>,
+    /// retries the listings and the head calls
+    pub(crate) retry: RetryPolicy,
 }
 
 impl StandardS3Client {
-    pub async fn new() -> Self {
-        Self {
+    /// Create a client from the environment.
+    /// The bucket name is read from `STANDARD_BUCKET_NAME`, and if it is not set, returns Err.
+    pub async fn new() -> Result<Self, Error> {
+        Ok(Self {
             client: s3_client().await,
-        }
+            bucket_name: standard_bucked_name()?,
+            auditor: None,
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    /// audit the mutating calls with the auditor
+    pub fn with_auditor(mut self, auditor: Auditor) -> Self {
+        self.auditor = Some(auditor);
+        self
+    }
+
+    /// retry the listings and the head calls by the policy
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
     }
 
     /// check if a key provided exists
-    pub async fn exists(&self, key: impl Into<String>) -> Result<bool, String> {
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = self.bucket_name, key = tracing::field::Empty),
+        err(level = "warn")
+    )]
+    pub async fn exists(&self, key: impl Into<String>) -> Result<bool, Error> {
+        let key = key.into();
+        tracing::Span::current().record("key", key.as_str());
+
+        let request = self.client.head_object().bucket(self.bucket_name).key(key);
+
         let result = self
-            .client
-            .head_object()
-            .bucket(standard_bucked_name())
-            .key(key.into())
-            .send()
+            .retry
+            .run("HeadObject", || request.clone().send().map_err(aws_error))
             .await;
 
         match result {
             Ok(_) => Ok(true),
-            Err(e) => match &e.code() {
-                None => Err(e.to_string()),
-                Some(code) => {
-                    if *code == "NotFound" {
-                        Ok(false)
-                    } else {
-                        Err(e.to_string())
-                    }
-                }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// get the protection of the object from the S3 Object Lock
+    /// The object that doesn't exist is not protected.
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = self.bucket_name, key = %key),
+        err(level = "warn")
+    )]
+    pub async fn object_protection(&self, key: &str) -> Result<Protection, Error> {
+        let request = self.client.head_object().bucket(self.bucket_name).key(key);
+
+        let result = self
+            .retry
+            .run("HeadObject", || request.clone().send().map_err(aws_error))
+            .await;
+
+        let output = match result {
+            Ok(output) => output,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Protection::default()),
+            Err(e) => return Err(e),
+        };
+
+        let retain_until = match output.object_lock_retain_until_date() {
+            Some(date_time) => match date_time.to_millis() {
+                Ok(millis) => Some(millis.max(0) as u128),
+                Err(e) => return Err(Error::from_source(ErrorKind::Internal, e)),
             },
+            None => None,
+        };
+
+        Ok(Protection {
+            retain_until,
+            legal_hold: output.object_lock_legal_hold_status()
+                == Some(&ObjectLockLegalHoldStatus::On),
+        })
+    }
+
+    /// apply the protection to the object as the S3 Object Lock
+    /// The bucket must have the Object Lock enabled. The retention is in the governance mode,
+    /// and it is not removed when the protection doesn't have it.
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = self.bucket_name, key = %key),
+        err(level = "warn")
+    )]
+    pub async fn apply_object_lock(&self, key: &str, protection: &Protection) -> Result<(), Error> {
+        if let Some(retain_until) = protection.retain_until {
+            let retention = ObjectLockRetention::builder()
+                .mode(ObjectLockRetentionMode::Governance)
+                .retain_until_date(DateTime::from_millis(retain_until as i64))
+                .build();
+
+            let request = self
+                .client
+                .put_object_retention()
+                .bucket(self.bucket_name)
+                .key(key)
+                .retention(retention);
+
+            self.retry
+                .run("PutObjectRetention", || {
+                    request.clone().send().map_err(aws_error)
+                })
+                .await?;
         }
+
+        let legal_hold_status = match protection.legal_hold {
+            true => ObjectLockLegalHoldStatus::On,
+            false => ObjectLockLegalHoldStatus::Off,
+        };
+        let request = self
+            .client
+            .put_object_legal_hold()
+            .bucket(self.bucket_name)
+            .key(key)
+            .legal_hold(
+                ObjectLockLegalHold::builder()
+                    .status(legal_hold_status)
+                    .build(),
+            );
+
+        self.retry
+            .run("PutObjectLegalHold", || {
+                request.clone().send().map_err(aws_error)
+            })
+            .await
+            .map(|_| ())
     }
 
     /// remove an object
-    pub async fn remove_object(&self, key: impl Into<&str>) -> Result<(), String> {
-        let result = self
+    /// The object that is retained or held cannot be removed.
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = self.bucket_name, key = tracing::field::Empty),
+        err(level = "warn")
+    )]
+    pub async fn remove_object(&self, key: impl Into<&str>) -> Result<(), Error> {
+        let key = key.into();
+        tracing::Span::current().record("key", key);
+
+        self.check_unprotected(key).await?;
+        self.delete_object(key).await?;
+
+        audit::record(&self.auditor, AuditAction::DeleteObject, key, None, None).await
+    }
+
+    /// move an object to another key
+    /// The object is copied, and then the original one is removed.
+    /// The object that is retained or held cannot be moved.
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = self.bucket_name, from = %from, to = %to),
+        err(level = "warn")
+    )]
+    pub async fn move_object(&self, from: &str, to: &str) -> Result<(), Error> {
+        self.check_unprotected(from).await?;
+
+        let request = self
+            .client
+            .copy_object()
+            .bucket(self.bucket_name)
+            .copy_source(format!("{}/{from}", self.bucket_name))
+            .key(to);
+
+        self.retry
+            .run("CopyObject", || request.clone().send().map_err(aws_error))
+            .await?;
+
+        self.delete_object(from).await?;
+
+        audit::record(
+            &self.auditor,
+            AuditAction::MoveObject,
+            from,
+            None,
+            Some(format!(r#"{{"KeyName":"{to}"}}"#)),
+        )
+        .await
+    }
+
+    async fn check_unprotected(&self, key: &str) -> Result<(), Error> {
+        let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(now) => now.as_millis(),
+            Err(_) => return Err(Error::internal("Failed to get current time")),
+        };
+
+        self.object_protection(key).await?.check(key, now)
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), Error> {
+        let request = self
             .client
             .delete_object()
-            .bucket(standard_bucked_name())
-            .key(key.into())
-            .send()
-            .await;
+            .bucket(self.bucket_name)
+            .key(key);
 
-        match result {
-            Ok(_) => Ok(()),
-            Err(e) => Err(format!("{}", e)),
-        }
+        self.retry
+            .run("DeleteObject", || request.clone().send().map_err(aws_error))
+            .await
+            .map(|_| ())
     }
 }
 
@@ -69,43 +245,47 @@ pub trait StandardS3ClientTrait {
     fn generate_pre_signed_url_for_video(
         date_time: &str,
         extension: &str,
-    ) -> impl Future<Output = Result<String, String>> + Send;
+    ) -> impl Future<Output = Result<String, Error>> + Send;
 }
 
 impl GetFileListTrait for StandardS3Client {
-    async fn get_years(&self) -> Result<Vec<String>, String> {
-        let result = self
+    #[tracing::instrument(skip_all, fields(bucket = self.bucket_name), err(level = "warn"))]
+    async fn get_years(&self) -> Result<Vec<String>, Error> {
+        let request = self
             .client
             .list_objects_v2()
-            .bucket(standard_bucked_name())
-            .delimiter("/")
-            .send()
-            .await;
+            .bucket(self.bucket_name)
+            .delimiter("/");
 
-        let output = match result {
-            Ok(out) => out,
-            Err(e) => return Err(format!("Failed to get objects: {}", e)),
-        };
+        let output = self
+            .retry
+            .run("ListObjectsV2", || {
+                request.clone().send().map_err(aws_error)
+            })
+            .await?;
 
         Ok(retrieve_prefixes(&output))
     }
 
-    async fn get_months(&self, years: usize) -> Result<Vec<String>, String> {
-        let result = self
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = self.bucket_name, year = years),
+        err(level = "warn")
+    )]
+    async fn get_months(&self, years: usize) -> Result<Vec<String>, Error> {
+        let request = self
             .client
             .list_objects_v2()
-            .bucket(standard_bucked_name())
+            .bucket(self.bucket_name)
             .prefix(format!("{years}/"))
-            .delimiter("/")
-            .send()
-            .await;
+            .delimiter("/");
 
-        let output = match result {
-            Ok(out) => out,
-            Err(e) => {
-                return Err(format!("Failed to get objects: {}", e));
-            }
-        };
+        let output = self
+            .retry
+            .run("ListObjectsV2", || {
+                request.clone().send().map_err(aws_error)
+            })
+            .await?;
 
         let removed_delimiter: Vec<String> = retrieve_prefixes(&output);
         let months: Vec<String> = removed_delimiter
@@ -116,22 +296,25 @@ impl GetFileListTrait for StandardS3Client {
         Ok(months)
     }
 
-    async fn get_days(&self, year: usize, month: usize) -> Result<Vec<String>, String> {
-        let result = self
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = self.bucket_name, year = %year, month = %month),
+        err(level = "warn")
+    )]
+    async fn get_days(&self, year: usize, month: usize) -> Result<Vec<String>, Error> {
+        let request = self
             .client
             .list_objects_v2()
-            .bucket(standard_bucked_name())
+            .bucket(self.bucket_name)
             .prefix(format!("{year}/{}/", zero_adder(month)))
-            .delimiter("/")
-            .send()
-            .await;
+            .delimiter("/");
 
-        let output = match result {
-            Ok(out) => out,
-            Err(e) => {
-                return Err(format!("Failed to get objects: {}", e));
-            }
-        };
+        let output = self
+            .retry
+            .run("ListObjectsV2", || {
+                request.clone().send().map_err(aws_error)
+            })
+            .await?;
 
         let removed_delimiter: Vec<String> = retrieve_prefixes(&output);
         let days: Vec<String> = removed_delimiter
@@ -142,41 +325,80 @@ impl GetFileListTrait for StandardS3Client {
         Ok(days)
     }
 
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = self.bucket_name, year = %year, month = %month, day = %day),
+        err(level = "warn")
+    )]
     async fn get_objects(
         &self,
         year: usize,
         month: usize,
         day: usize,
-    ) -> Result<Vec<String>, String> {
-        let result = self
+    ) -> Result<Vec<String>, Error> {
+        let mut objects = Vec::new();
+        let mut cursor = None;
+
+        // a listing returns up to 1000 keys
+        loop {
+            let page = self
+                .get_objects_page(year, month, day, 1000, cursor)
+                .await?;
+            objects.extend(page.items);
+
+            match page.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => break,
+            }
+        }
+
+        Ok(objects)
+    }
+
+    /// The cursor is the continuation token of the listing.
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = self.bucket_name, year = %year, month = %month, day = %day, limit = %limit),
+        err(level = "warn")
+    )]
+    async fn get_objects_page(
+        &self,
+        year: usize,
+        month: usize,
+        day: usize,
+        limit: i32,
+        cursor: Option<String>,
+    ) -> Result<Page, Error> {
+        let request = self
             .client
             .list_objects_v2()
-            .bucket(standard_bucked_name())
+            .bucket(self.bucket_name)
             .prefix(format!("{year}/{}/{}", zero_adder(month), zero_adder(day)))
-            .send()
-            .await;
-
-        let output = match result {
-            Ok(out) => out,
-            Err(e) => {
-                return Err(format!("Failed to get objects: {}", e));
-            }
-        };
+            .max_keys(limit)
+            .set_continuation_token(cursor);
 
-        let saved_objects = output.contents();
+        let output = self
+            .retry
+            .run("ListObjectsV2", || {
+                request.clone().send().map_err(aws_error)
+            })
+            .await?;
 
-        let mut objects: Vec<String> = Vec::new();
+        let mut items: Vec<String> = Vec::new();
 
-        for object in saved_objects {
+        for object in output.contents() {
             if let Some(key) = &object.key {
                 let key_vec = key.split('/').collect::<Vec<&str>>();
                 if key_vec.len() == 4 {
-                    objects.push(key_vec[3].to_owned())
+                    items.push(key_vec[3].to_owned())
                 }
             }
         }
 
-        Ok(objects)
+        Ok(Page {
+            items,
+            next_cursor: output.next_continuation_token,
+        })
     }
 }
 
@@ -184,23 +406,30 @@ impl StandardS3ClientTrait for StandardS3Client {
     /// get a date time as an argument and return the [s3 pre-signed URL](https://docs.aws.amazon.com/AmazonS3/latest/userguide/ShareObjectPreSignedURL.html)
     /// The expiring time is 3600 sec
     /// The date time in the argument must be ISO
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = tracing::field::Empty, date_time = %date_time, extension = %extension),
+        err(level = "warn")
+    )]
     async fn generate_pre_signed_url_for_video(
         date_time: &str,
         extension: &str,
-    ) -> Result<String, String> {
+    ) -> Result<String, Error> {
         let config = match PresigningConfig::expires_in(PRE_SIGN_EXPIRING_TIME) {
             Ok(config) => config,
-            Err(_) => return Err("Too long expiring is provided".to_string()),
+            Err(_) => return Err(Error::internal("Too long expiring is provided")),
         };
 
         let s3_client = s3_client().await;
+        let bucket_name = standard_bucked_name()?;
+        tracing::Span::current().record("bucket", bucket_name);
 
         let file_path = match FilePath::new().generate_file_path(date_time, extension) {
             Ok(file_path) => file_path,
-            Err(e) => return Err(e),
+            Err(e) => return Err(Error::invalid_input(e)),
         };
 
-        get_pre_signed_url(s3_client, config, file_path.as_str()).await
+        get_pre_signed_url(s3_client, bucket_name, config, file_path.as_str()).await
     }
 }
 
@@ -209,19 +438,20 @@ impl StandardS3ClientTrait for StandardS3Client {
 /// [errors](https://docs.aws.amazon.com/AmazonS3/latest/API/ErrorResponses.html)
 async fn get_pre_signed_url(
     client: &aws_sdk_s3::Client,
+    bucket_name: &str,
     config: PresigningConfig,
     file_path: &str,
-) -> Result<String, String> {
+) -> Result<String, Error> {
     let pre_signed_request_result = client
         .put_object()
-        .bucket(standard_bucked_name())
+        .bucket(bucket_name)
         .key(file_path)
         .presigned(config)
         .await;
 
     match pre_signed_request_result {
         Ok(result) => Ok(result.uri().into()),
-        Err(e) => Err(format!("{}", e.to_string())),
+        Err(e) => Err(aws_error(e)),
     }
 }
 
@@ -307,6 +537,7 @@ mod client_test {
         async fn test_exists() {
             let result = StandardS3Client::new()
                 .await
+                .unwrap()
                 .exists("1984/04/04/1984-04-04-12-34-50.MOV")
                 .await
                 .unwrap();
@@ -318,6 +549,7 @@ mod client_test {
         async fn test_not_exists() {
             let result = StandardS3Client::new()
                 .await
+                .unwrap()
                 .exists("no-key")
                 .await
                 .unwrap();
@@ -332,7 +564,12 @@ mod client_test {
         #[tokio::test]
         async fn test_get_years() {
             // Assert
-            let result = StandardS3Client::new().await.get_years().await.unwrap();
+            let result = StandardS3Client::new()
+                .await
+                .unwrap()
+                .get_years()
+                .await
+                .unwrap();
 
             assert_eq!(result, ["1984", "1985"])
         }
@@ -346,6 +583,7 @@ mod client_test {
             // Assert
             let result = StandardS3Client::new()
                 .await
+                .unwrap()
                 .get_months(1984)
                 .await
                 .unwrap();
@@ -362,6 +600,7 @@ mod client_test {
             // Assert
             let result = StandardS3Client::new()
                 .await
+                .unwrap()
                 .get_days(1984, 4)
                 .await
                 .unwrap();
@@ -378,6 +617,7 @@ mod client_test {
             // Assert
             let result = StandardS3Client::new()
                 .await
+                .unwrap()
                 .get_objects(1984, 4, 4)
                 .await
                 .unwrap();
@@ -387,6 +627,25 @@ mod client_test {
                 ["1984-04-04-12-34-50.MOV", "1984-04-04-12-34-51.MOV"]
             )
         }
+
+        #[tokio::test]
+        async fn test_get_objects_page() {
+            // Arrange
+            let client = StandardS3Client::new().await.unwrap();
+
+            // Act
+            let first = client.get_objects_page(1984, 4, 4, 1, None).await.unwrap();
+            let second = client
+                .get_objects_page(1984, 4, 4, 1, first.next_cursor.clone())
+                .await
+                .unwrap();
+
+            // Assert
+            assert_eq!(first.items, ["1984-04-04-12-34-50.MOV"]);
+            assert!(first.next_cursor.is_some());
+            assert_eq!(second.items, ["1984-04-04-12-34-51.MOV"]);
+            assert_eq!(second.next_cursor, None);
+        }
     }
 
     mod test_remove_object {
@@ -398,10 +657,10 @@ mod client_test {
             // Arrange
             let key_name = "key";
             let _ = put_test_object(key_name).await;
-            let client = StandardS3Client::new();
+            let client = StandardS3Client::new().await.unwrap();
 
             // Act
-            let result = client.await.remove_object(key_name).await;
+            let result = client.remove_object(key_name).await;
 
             // Assert
             assert!(result.is_ok());
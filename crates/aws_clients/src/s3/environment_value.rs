@@ -1,52 +1,62 @@
 //! S3 client
 //! to see the data check the storage/ready.d
 
-use std::env::var;
-use std::sync::OnceLock;
+use crate::config::config;
+use shared::error::Error;
 use tokio::sync::OnceCell;
 
-/// read data from environment value
-pub(crate) fn standard_bucked_name() -> &'static str {
-    static STANDARD_BUCKET: OnceLock<String> = OnceLock::new();
-    STANDARD_BUCKET.get_or_init(|| {
-        var("STANDARD_BUCKET_NAME").unwrap_or_else(|_| {
-            if cfg!(test) {
-                "test-bucket".to_string()
-            } else {
-                log::error!("{}", String::from("Bucket is not found"));
-                panic!("variable not found")
-            }
-        })
-    })
+/// read the bucket name from the configuration
+/// If it is not set, returns Err.
+pub(crate) fn standard_bucked_name() -> Result<&'static str, Error> {
+    config()?.bucket_name()
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "fake"))]
 fn bucket_url() -> &'static str {
-    static HOST_NAME: OnceLock<String> = OnceLock::new();
-    HOST_NAME.get_or_init(|| {
-        if let Ok(host) = var("HOST_NAME") {
-            format!("http://{host}")
-        } else {
-            "http://localhost:4566".to_string()
-        }
-    })
+    config()
+        .ok()
+        .and_then(|config| config.s3_endpoint_url.as_deref())
+        .unwrap_or("http://localhost:4566")
 }
 
 static S3_CLIENT: OnceCell<aws_sdk_s3::Client> = OnceCell::const_new();
 
 /// The s3 client
-#[cfg(not(test))]
+/// The endpoint and the region of the configuration are used if they are set.
+/// The SDK doesn't retry, since [RetryPolicy](crate::retry::RetryPolicy) does.
+#[cfg(not(any(test, feature = "fake")))]
 pub async fn s3_client() -> &'static aws_sdk_s3::Client {
+    use aws_config::retry::RetryConfig;
+    use aws_config::{BehaviorVersion, Region};
+
     S3_CLIENT
         .get_or_init(|| async {
-            let config = aws_config::load_from_env().await;
-            aws_sdk_s3::Client::new(&config)
+            let mut loader = aws_config::defaults(BehaviorVersion::latest())
+                .retry_config(RetryConfig::disabled());
+            let mut force_path_style = false;
+            if let Ok(config) = config() {
+                if let Some(endpoint_url) = &config.s3_endpoint_url {
+                    loader = loader.endpoint_url(endpoint_url);
+                    // LocalStack doesn't resolve the bucket in the host name
+                    force_path_style = true;
+                }
+                if let Some(region) = &config.region {
+                    loader = loader.region(Region::new(region.to_string()));
+                }
+            }
+
+            let mut config_builder = aws_sdk_s3::config::Builder::from(&loader.load().await);
+            config_builder.set_force_path_style(Some(force_path_style));
+            aws_sdk_s3::Client::from_conf(config_builder.build())
         })
         .await
 }
 
-#[cfg(test)]
+/// The s3 client that sends the requests to [FakeAws::shared](crate::fake::FakeAws::shared)
+#[cfg(any(test, feature = "fake"))]
 pub async fn s3_client() -> &'static aws_sdk_s3::Client {
+    use crate::fake::FakeAws;
+    use aws_config::retry::RetryConfig;
     use aws_config::BehaviorVersion;
     use aws_config::Region;
     use aws_sdk_s3::config::Credentials;
@@ -56,7 +66,9 @@ pub async fn s3_client() -> &'static aws_sdk_s3::Client {
             let config = aws_config::defaults(BehaviorVersion::latest())
                 .endpoint_url(bucket_url())
                 .region(Some(Region::new("us-west-2")))
+                .retry_config(RetryConfig::disabled())
                 .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
+                .http_client(FakeAws::shared().http_client())
                 .load()
                 .await;
 
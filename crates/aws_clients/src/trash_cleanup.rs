@@ -0,0 +1,167 @@
+//! Removes the objects of the purged items from the standard bucket
+//! The object is removed only after DynamoDB has actually deleted the expired item by TTL,
+//! so an item that is restored just before the expiry never loses its object.
+
+use crate::dynamodb::client::{get_now, DynamoDbClient};
+use crate::s3::client::StandardS3Client;
+use shared::error::Error;
+
+/// Remove the objects of the trashed items that have expired and have been purged from the table.
+/// The media is also removed from the albums, and its annotation is deleted.
+/// The object that is protected by the S3 Object Lock is skipped.
+/// The trash entry is deleted after the object is removed, so a failed run can be retried.
+/// Returns the key names of the removed objects.
+/// time is mill sec
+#[tracing::instrument(skip_all, fields(table = %dynamodb_client.table_name), err(level = "warn"))]
+pub async fn clean_up_purged_objects(
+    dynamodb_client: &DynamoDbClient,
+    s3_client: &StandardS3Client,
+    time: Option<u128>,
+) -> Result<Vec<String>, Error> {
+    let now = get_now(time)?;
+    let mut removed_keys = Vec::new();
+
+    for trashed_item in dynamodb_client.get_trashed_items().await? {
+        if !trashed_item.is_expired(now) {
+            continue;
+        }
+
+        // TTL deletes the item within a few days after the expiry
+        if dynamodb_client
+            .collection_item_exists(&trashed_item.key_name)
+            .await?
+        {
+            continue;
+        }
+
+        // the object that is locked in the bucket is kept with its trash entry
+        if s3_client
+            .object_protection(&trashed_item.key_name)
+            .await?
+            .is_protected(now)
+        {
+            tracing::warn!(
+                key = trashed_item.key_name,
+                "The object is protected, so it is not removed"
+            );
+            continue;
+        }
+
+        s3_client
+            .remove_object(trashed_item.key_name.as_str())
+            .await?;
+        dynamodb_client
+            .remove_from_all_albums(&trashed_item.key_name)
+            .await?;
+        dynamodb_client
+            .delete_annotation(&trashed_item.key_name)
+            .await?;
+        dynamodb_client
+            .delete_trash_entry(&trashed_item.key_name)
+            .await?;
+
+        removed_keys.push(trashed_item.key_name);
+    }
+
+    Ok(removed_keys)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dynamodb::client::DynamoClientTrait;
+    use crate::dynamodb::entities::collection::CollectionItem;
+    use crate::dynamodb::trash::RETENTION_PERIOD;
+    use crate::s3::test_utils::put_test_object;
+    use aws_sdk_dynamodb::types::AttributeValue;
+    use time_file_name::file_datetime::PathDateTime;
+
+    const KEY_NAME: &str = "1986/04/04/1986-04-04-12-34-50.MOV";
+
+    #[tokio::test]
+    async fn test_clean_up_purged_objects() {
+        // Arrange
+        let dynamodb_client = DynamoDbClient::new("test_clean_up_purged_objects").await;
+        let s3_client = StandardS3Client::new().await.unwrap();
+        put_test_object(KEY_NAME).await;
+        dynamodb_client
+            .put_collection_items(&vec![CollectionItem::dummy_object(KEY_NAME)])
+            .await
+            .unwrap();
+        let album = dynamodb_client.create_album("album", None).await.unwrap();
+        dynamodb_client
+            .add_to_album(album.id, KEY_NAME)
+            .await
+            .unwrap();
+        dynamodb_client
+            .trash_collection_item(KEY_NAME, "user", Some(0))
+            .await
+            .unwrap();
+        let after_expiry = Some(RETENTION_PERIOD.as_millis() + 1);
+
+        // Act
+        // the item is expired, but it is not purged by TTL yet
+        let not_purged = clean_up_purged_objects(&dynamodb_client, &s3_client, after_expiry)
+            .await
+            .unwrap();
+
+        // Assert
+        assert!(not_purged.is_empty());
+        assert!(s3_client.exists(KEY_NAME).await.unwrap());
+
+        // Arrange
+        // TTL of DynamoDB Local doesn't delete items, so the item is deleted here
+        let path_date_time = PathDateTime::parse(KEY_NAME).unwrap();
+        dynamodb_client
+            .client
+            .delete_item()
+            .table_name(dynamodb_client.table_name())
+            .key("PK", AttributeValue::S(path_date_time.year.to_string()))
+            .key(
+                "SK",
+                AttributeValue::N(path_date_time.unix_time.to_string()),
+            )
+            .send()
+            .await
+            .unwrap();
+
+        // Act
+        let purged = clean_up_purged_objects(&dynamodb_client, &s3_client, after_expiry)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(purged, [KEY_NAME]);
+        assert_eq!(dynamodb_client.get_albums().await.unwrap()[0].item_count, 0);
+        assert!(!s3_client.exists(KEY_NAME).await.unwrap());
+        assert!(dynamodb_client
+            .get_trashed_items()
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_clean_up_before_expiry() {
+        // Arrange
+        let dynamodb_client = DynamoDbClient::new("test_clean_up_before_expiry").await;
+        let s3_client = StandardS3Client::new().await.unwrap();
+        dynamodb_client
+            .put_collection_items(&vec![CollectionItem::dummy_object(KEY_NAME)])
+            .await
+            .unwrap();
+        dynamodb_client
+            .trash_collection_item(KEY_NAME, "user", Some(0))
+            .await
+            .unwrap();
+
+        // Act
+        let result = clean_up_purged_objects(&dynamodb_client, &s3_client, Some(1))
+            .await
+            .unwrap();
+
+        // Assert
+        assert!(result.is_empty());
+        assert_eq!(dynamodb_client.get_trashed_items().await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,363 @@
+//! The cache of the file lists
+//! [CachedFileList] wraps any [GetFileListTrait], so browsing a month doesn't list the same prefixes again and again.
+//! Each level has its own TTL, and the number of the cached lists is bounded.
+//! The lists of a date are invalidated when an object of the date is changed in the same process.
+//! A change in another process, e.g. an upload that the S3 hook handles, shows up when the TTL of the list expires,
+//! so the TTLs are short.
+
+use crate::error::Error;
+use crate::lock_ignoring_poison;
+use crate::traits::{GetFileListTrait, Page};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The TTLs of the levels and the bound of the cache
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheConfig {
+    pub years_ttl: Duration,
+    pub months_ttl: Duration,
+    pub days_ttl: Duration,
+    pub objects_ttl: Duration,
+    /// The number of the lists that are kept. The least recently used one is evicted.
+    pub max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            years_ttl: Duration::from_secs(60),
+            months_ttl: Duration::from_secs(60),
+            days_ttl: Duration::from_secs(30),
+            objects_ttl: Duration::from_secs(10),
+            max_entries: 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CacheKey {
+    Years,
+    Months(usize),
+    Days(usize, usize),
+    Objects(usize, usize, usize),
+}
+
+struct CacheEntry {
+    list: Vec<String>,
+    expires_at: Instant,
+    /// The order of the use
+    last_used: u64,
+}
+
+/// The file list that caches the lists of the inner one
+/// The pages of the objects are not cached, since the cursor is only valid for the inner one.
+pub struct CachedFileList<T> {
+    inner: T,
+    config: CacheConfig,
+    entries: Mutex<HashMap<CacheKey, CacheEntry>>,
+    uses: AtomicU64,
+}
+
+impl<T> CachedFileList<T> {
+    pub fn new(inner: T, config: CacheConfig) -> Self {
+        Self {
+            inner,
+            config,
+            entries: Mutex::new(HashMap::new()),
+            uses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Invalidate the lists that an upload or a deletion of an object of the date changes
+    /// The objects of the day and the lists of its ancestors are dropped.
+    pub fn invalidate_date(&self, year: usize, month: usize, day: usize) {
+        let mut entries = self.lock();
+        for key in [
+            CacheKey::Years,
+            CacheKey::Months(year),
+            CacheKey::Days(year, month),
+            CacheKey::Objects(year, month, day),
+        ] {
+            entries.remove(&key);
+        }
+    }
+
+    /// Drop all the lists
+    pub fn invalidate_all(&self) {
+        self.lock().clear();
+    }
+
+    /// The number of the cached lists including the expired ones
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn ttl(&self, key: CacheKey) -> Duration {
+        match key {
+            CacheKey::Years => self.config.years_ttl,
+            CacheKey::Months(_) => self.config.months_ttl,
+            CacheKey::Days(_, _) => self.config.days_ttl,
+            CacheKey::Objects(_, _, _) => self.config.objects_ttl,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<CacheKey, CacheEntry>> {
+        lock_ignoring_poison(&self.entries)
+    }
+
+    fn get(&self, key: CacheKey) -> Option<Vec<String>> {
+        let now = Instant::now();
+        let mut entries = self.lock();
+
+        match entries.get_mut(&key) {
+            Some(entry) if now < entry.expires_at => {
+                entry.last_used = self.uses.fetch_add(1, Ordering::Relaxed);
+                Some(entry.list.clone())
+            }
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn put(&self, key: CacheKey, list: &[String]) {
+        if self.config.max_entries == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut entries = self.lock();
+
+        if !entries.contains_key(&key) && entries.len() >= self.config.max_entries {
+            entries.retain(|_, entry| now < entry.expires_at);
+        }
+        while !entries.contains_key(&key) && entries.len() >= self.config.max_entries {
+            let least_recently_used = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+            match least_recently_used {
+                Some(least_recently_used) => entries.remove(&least_recently_used),
+                None => break,
+            };
+        }
+
+        entries.insert(
+            key,
+            CacheEntry {
+                list: list.to_vec(),
+                expires_at: now + self.ttl(key),
+                last_used: self.uses.fetch_add(1, Ordering::Relaxed),
+            },
+        );
+    }
+
+    /// Read the list from the cache, or from the inner one if it is not cached
+    /// The errors are not cached.
+    async fn get_or_list(
+        &self,
+        key: CacheKey,
+        list: impl std::future::Future<Output = Result<Vec<String>, Error>>,
+    ) -> Result<Vec<String>, Error> {
+        if let Some(list) = self.get(key) {
+            return Ok(list);
+        }
+
+        let list = list.await?;
+        self.put(key, &list);
+        Ok(list)
+    }
+}
+
+impl<T: GetFileListTrait + Sync> GetFileListTrait for CachedFileList<T> {
+    async fn get_years(&self) -> Result<Vec<String>, Error> {
+        self.get_or_list(CacheKey::Years, self.inner.get_years())
+            .await
+    }
+
+    async fn get_months(&self, year: usize) -> Result<Vec<String>, Error> {
+        self.get_or_list(CacheKey::Months(year), self.inner.get_months(year))
+            .await
+    }
+
+    async fn get_days(&self, year: usize, month: usize) -> Result<Vec<String>, Error> {
+        self.get_or_list(
+            CacheKey::Days(year, month),
+            self.inner.get_days(year, month),
+        )
+        .await
+    }
+
+    async fn get_objects(
+        &self,
+        year: usize,
+        month: usize,
+        day: usize,
+    ) -> Result<Vec<String>, Error> {
+        self.get_or_list(
+            CacheKey::Objects(year, month, day),
+            self.inner.get_objects(year, month, day),
+        )
+        .await
+    }
+
+    async fn get_objects_page(
+        &self,
+        year: usize,
+        month: usize,
+        day: usize,
+        limit: i32,
+        cursor: Option<String>,
+    ) -> Result<Page, Error> {
+        self.inner
+            .get_objects_page(year, month, day, limit, cursor)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    /// The file list that counts the calls
+    #[derive(Default)]
+    struct CountingFileList {
+        calls: AtomicUsize,
+    }
+
+    impl CountingFileList {
+        fn list(&self) -> Result<Vec<String>, Error> {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(vec![calls.to_string()])
+        }
+    }
+
+    impl GetFileListTrait for CountingFileList {
+        async fn get_years(&self) -> Result<Vec<String>, Error> {
+            self.list()
+        }
+
+        async fn get_months(&self, _year: usize) -> Result<Vec<String>, Error> {
+            self.list()
+        }
+
+        async fn get_days(&self, _year: usize, _month: usize) -> Result<Vec<String>, Error> {
+            self.list()
+        }
+
+        async fn get_objects(
+            &self,
+            _year: usize,
+            _month: usize,
+            _day: usize,
+        ) -> Result<Vec<String>, Error> {
+            self.list()
+        }
+
+        async fn get_objects_page(
+            &self,
+            _year: usize,
+            _month: usize,
+            _day: usize,
+            _limit: i32,
+            _cursor: Option<String>,
+        ) -> Result<Page, Error> {
+            Ok(Page {
+                items: self.list()?,
+                next_cursor: None,
+            })
+        }
+    }
+
+    fn cached(config: CacheConfig) -> CachedFileList<CountingFileList> {
+        CachedFileList::new(CountingFileList::default(), config)
+    }
+
+    #[tokio::test]
+    async fn test_cached_list() {
+        // Arrange
+        let client = cached(CacheConfig::default());
+
+        // Act
+        let first = client.get_objects(1984, 4, 4).await.unwrap();
+        let second = client.get_objects(1984, 4, 4).await.unwrap();
+        let another_day = client.get_objects(1984, 4, 5).await.unwrap();
+
+        // Assert
+        assert_eq!(first, ["1"]);
+        assert_eq!(second, ["1"]);
+        assert_eq!(another_day, ["2"]);
+    }
+
+    #[tokio::test]
+    async fn test_expired_list() {
+        // Arrange
+        let client = cached(CacheConfig {
+            objects_ttl: Duration::ZERO,
+            ..CacheConfig::default()
+        });
+
+        // Act
+        let first = client.get_objects(1984, 4, 4).await.unwrap();
+        let second = client.get_objects(1984, 4, 4).await.unwrap();
+        let years = client.get_years().await.unwrap();
+        let cached_years = client.get_years().await.unwrap();
+
+        // Assert
+        assert_eq!(first, ["1"]);
+        assert_eq!(second, ["2"]);
+        assert_eq!(years, cached_years);
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_date() {
+        // Arrange
+        let client = cached(CacheConfig::default());
+        client.get_years().await.unwrap();
+        client.get_months(1984).await.unwrap();
+        client.get_months(1985).await.unwrap();
+        client.get_objects(1984, 4, 4).await.unwrap();
+
+        // Act
+        client.invalidate_date(1984, 4, 4);
+
+        // Assert
+        assert_eq!(client.len(), 1);
+        assert_eq!(client.get_months(1985).await.unwrap(), ["3"]);
+        assert_eq!(client.get_objects(1984, 4, 4).await.unwrap(), ["5"]);
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_is_evicted() {
+        // Arrange
+        let client = cached(CacheConfig {
+            max_entries: 2,
+            ..CacheConfig::default()
+        });
+        client.get_months(1984).await.unwrap();
+        client.get_months(1985).await.unwrap();
+        // 1984 is used after 1985
+        client.get_months(1984).await.unwrap();
+
+        // Act
+        client.get_months(1986).await.unwrap();
+
+        // Assert
+        assert_eq!(client.len(), 2);
+        assert_eq!(client.get_months(1984).await.unwrap(), ["1"]);
+        assert_eq!(client.get_months(1985).await.unwrap(), ["4"]);
+    }
+}
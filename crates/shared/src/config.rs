@@ -0,0 +1,463 @@
+//! The configuration of the binaries
+//! The settings are read from the environment and an optional file, which is given by `APP_CONFIG_FILE`.
+//! The file has a `KEY=value` per line, and the environment overrides it.
+//! Everything is validated when it is loaded, and all the problems are reported at once.
+
+use crate::error::Error;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The key of the file of the settings
+pub const CONFIG_FILE: &str = "APP_CONFIG_FILE";
+
+/// The environment the binary runs in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// The local DynamoDB and LocalStack, which are the defaults of the endpoints
+    Local,
+    Dev,
+    /// The endpoints can't be overridden
+    Prod,
+}
+
+impl Profile {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Profile::Local => "local",
+            Profile::Dev => "dev",
+            Profile::Prod => "prod",
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Profile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(Profile::Local),
+            "dev" => Ok(Profile::Dev),
+            "prod" => Ok(Profile::Prod),
+            _ => Err(Error::invalid_input(format!(
+                "{} must be local, dev, or prod: {s}",
+                Setting::Profile.key()
+            ))),
+        }
+    }
+}
+
+/// A setting of the configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Setting {
+    Profile,
+    TableName,
+    BucketName,
+    S3EndpointUrl,
+    DynamoDbEndpointUrl,
+    Region,
+    LibraryDir,
+    IgnoreStageInPath,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 8] = [
+        Setting::Profile,
+        Setting::TableName,
+        Setting::BucketName,
+        Setting::S3EndpointUrl,
+        Setting::DynamoDbEndpointUrl,
+        Setting::Region,
+        Setting::LibraryDir,
+        Setting::IgnoreStageInPath,
+    ];
+
+    /// The key in the environment and the file
+    pub fn key(&self) -> &'static str {
+        match self {
+            Setting::Profile => "APP_PROFILE",
+            Setting::TableName => "TABLE_NAME",
+            Setting::BucketName => "STANDARD_BUCKET_NAME",
+            Setting::S3EndpointUrl => "S3_ENDPOINT_URL",
+            Setting::DynamoDbEndpointUrl => "DYNAMODB_ENDPOINT_URL",
+            Setting::Region => "AWS_REGION",
+            Setting::LibraryDir => "LIBRARY_DIR",
+            Setting::IgnoreStageInPath => "AWS_LAMBDA_HTTP_IGNORE_STAGE_IN_PATH",
+        }
+    }
+
+    /// The host names that the tests have used for the endpoints, which are read only in local
+    fn legacy_host_key(&self) -> Option<&'static str> {
+        match self {
+            Setting::S3EndpointUrl => Some("HOST_NAME"),
+            Setting::DynamoDbEndpointUrl => Some("DYNAMO_HOST"),
+            _ => None,
+        }
+    }
+}
+
+/// The validated configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub profile: Profile,
+    pub table_name: Option<String>,
+    pub bucket_name: Option<String>,
+    pub s3_endpoint_url: Option<String>,
+    pub dynamodb_endpoint_url: Option<String>,
+    pub region: Option<String>,
+    /// The root of the local library of the desktop app
+    pub library_dir: Option<PathBuf>,
+    /// The web API ignores the stage of the API Gateway in the path
+    pub ignore_stage_in_path: bool,
+}
+
+impl Config {
+    /// The table name, which is required by [ConfigLoader::require] to be sure
+    pub fn table_name(&self) -> Result<&str, Error> {
+        required(&self.table_name, Setting::TableName)
+    }
+
+    /// The bucket name, which is required by [ConfigLoader::require] to be sure
+    pub fn bucket_name(&self) -> Result<&str, Error> {
+        required(&self.bucket_name, Setting::BucketName)
+    }
+}
+
+fn required(value: &Option<String>, setting: Setting) -> Result<&str, Error> {
+    match value {
+        Some(value) => Ok(value),
+        None => Err(Error::invalid_input(format!(
+            "{} is not set",
+            setting.key()
+        ))),
+    }
+}
+
+/// The loader of the [Config]
+/// The profile is `prod` if it is not set, unless the default profile is given.
+///
+/// # Example
+/// ```rust
+/// # use shared::config::{ConfigLoader, Profile, Setting};
+/// let config = ConfigLoader::new()
+///     .vars([("APP_PROFILE", "dev"), ("TABLE_NAME", "table")])
+///     .require(Setting::TableName)
+///     .load()
+///     .unwrap();
+/// assert_eq!(config.profile, Profile::Dev);
+/// assert_eq!(config.table_name().unwrap(), "table");
+/// ```
+pub struct ConfigLoader {
+    default_profile: Profile,
+    required: Vec<Setting>,
+    file: Option<PathBuf>,
+    vars: Option<HashMap<String, String>>,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self {
+            default_profile: Profile::Prod,
+            required: vec![],
+            file: None,
+            vars: None,
+        }
+    }
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The profile when `APP_PROFILE` is not set
+    pub fn default_profile(mut self, profile: Profile) -> Self {
+        self.default_profile = profile;
+        self
+    }
+
+    /// The setting that the binary can't run without
+    pub fn require(mut self, setting: Setting) -> Self {
+        self.required.push(setting);
+        self
+    }
+
+    /// The file of the settings instead of `APP_CONFIG_FILE`
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// The variables instead of the environment of the process
+    pub fn vars<K: Into<String>, V: Into<String>>(
+        mut self,
+        vars: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        self.vars = Some(
+            vars.into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        );
+        self
+    }
+
+    pub fn load(self) -> Result<Config, Error> {
+        let vars = match self.vars {
+            Some(vars) => vars,
+            None => std::env::vars().collect(),
+        };
+        let mut problems = Vec::new();
+
+        let mut values = HashMap::new();
+        if let Some(file) = self
+            .file
+            .or_else(|| vars.get(CONFIG_FILE).map(PathBuf::from))
+        {
+            match std::fs::read_to_string(&file) {
+                Ok(content) => values = parse_file(&content, &file, &mut problems),
+                Err(e) => problems.push(format!("{} can't be read: {e}", file.display())),
+            }
+        }
+        for setting in Setting::ALL {
+            if let Some(value) = vars.get(setting.key()) {
+                values.insert(setting, value.to_string());
+            }
+        }
+
+        for (setting, value) in &values {
+            if value.trim().is_empty() {
+                problems.push(format!("{} is empty", setting.key()));
+            }
+        }
+        values.retain(|_, value| !value.trim().is_empty());
+
+        let profile = match values.remove(&Setting::Profile) {
+            Some(profile) => profile.parse().unwrap_or_else(|e: Error| {
+                problems.push(e.message().to_string());
+                self.default_profile
+            }),
+            None => self.default_profile,
+        };
+
+        if profile == Profile::Local {
+            for setting in [Setting::S3EndpointUrl, Setting::DynamoDbEndpointUrl] {
+                if let Some(host) = setting.legacy_host_key().and_then(|key| vars.get(key)) {
+                    values
+                        .entry(setting)
+                        .or_insert_with(|| format!("http://{host}"));
+                }
+            }
+            for (setting, default) in [
+                (Setting::S3EndpointUrl, "http://localhost:4566"),
+                (Setting::DynamoDbEndpointUrl, "http://localhost:8000"),
+                (Setting::Region, "us-west-2"),
+            ] {
+                values.entry(setting).or_insert_with(|| default.to_string());
+            }
+        }
+
+        for setting in [Setting::S3EndpointUrl, Setting::DynamoDbEndpointUrl] {
+            let Some(url) = values.get(&setting) else {
+                continue;
+            };
+            if profile == Profile::Prod {
+                problems.push(format!("{} can't be set in prod", setting.key()));
+            } else if !url.starts_with("http://") && !url.starts_with("https://") {
+                problems.push(format!("{} must be an HTTP URL: {url}", setting.key()));
+            }
+        }
+
+        let ignore_stage_in_path = match values.get(&Setting::IgnoreStageInPath).map(|v| v.as_str())
+        {
+            None | Some("true") | Some("1") => true,
+            Some("false") | Some("0") => false,
+            Some(value) => {
+                problems.push(format!(
+                    "{} must be true or false: {value}",
+                    Setting::IgnoreStageInPath.key()
+                ));
+                true
+            }
+        };
+
+        for setting in &self.required {
+            if !values.contains_key(setting) {
+                problems.push(format!("{} is not set", setting.key()));
+            }
+        }
+
+        if !problems.is_empty() {
+            problems.sort();
+            problems.dedup();
+            return Err(Error::invalid_input(format!(
+                "Invalid configuration of the {profile} profile: {}",
+                problems.join(", ")
+            )));
+        }
+
+        Ok(Config {
+            profile,
+            table_name: values.remove(&Setting::TableName),
+            bucket_name: values.remove(&Setting::BucketName),
+            s3_endpoint_url: values.remove(&Setting::S3EndpointUrl),
+            dynamodb_endpoint_url: values.remove(&Setting::DynamoDbEndpointUrl),
+            region: values.remove(&Setting::Region),
+            library_dir: values.remove(&Setting::LibraryDir).map(PathBuf::from),
+            ignore_stage_in_path,
+        })
+    }
+}
+
+/// Read the `KEY=value` lines
+/// The empty lines and the lines that start with `#` are skipped, and the value may be quoted.
+fn parse_file(
+    content: &str,
+    file: &std::path::Path,
+    problems: &mut Vec<String>,
+) -> HashMap<Setting, String> {
+    let mut values = HashMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let position = format!("{}:{}", file.display(), index + 1);
+        let Some((key, value)) = line.split_once('=') else {
+            problems.push(format!("{position} is not KEY=value"));
+            continue;
+        };
+
+        let key = key.trim();
+        let Some(setting) = Setting::ALL.into_iter().find(|s| s.key() == key) else {
+            problems.push(format!("{position} has an unknown setting {key}"));
+            continue;
+        };
+
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value);
+        values.insert(setting, value.to_string());
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    #[test]
+    fn test_local_defaults() {
+        // Arrange
+        let loader = ConfigLoader::new()
+            .default_profile(Profile::Local)
+            .vars([("DYNAMO_HOST", "ensemble-dynamo-db:8000")]);
+
+        // Act
+        let config = loader.load().unwrap();
+
+        // Assert
+        assert_eq!(config.profile, Profile::Local);
+        assert_eq!(
+            config.s3_endpoint_url.as_deref(),
+            Some("http://localhost:4566")
+        );
+        assert_eq!(
+            config.dynamodb_endpoint_url.as_deref(),
+            Some("http://ensemble-dynamo-db:8000")
+        );
+        assert!(config.ignore_stage_in_path);
+        assert_eq!(
+            config.table_name().unwrap_err().message(),
+            "TABLE_NAME is not set"
+        );
+        assert_eq!(
+            config.bucket_name().unwrap_err().message(),
+            "STANDARD_BUCKET_NAME is not set"
+        );
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        // Arrange
+        let loader = ConfigLoader::new()
+            .vars([
+                ("S3_ENDPOINT_URL", "http://localhost:4566"),
+                ("AWS_LAMBDA_HTTP_IGNORE_STAGE_IN_PATH", "yes"),
+            ])
+            .require(Setting::TableName)
+            .require(Setting::BucketName);
+
+        // Act
+        let error = loader.load().unwrap_err();
+
+        // Assert
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            error.message(),
+            "Invalid configuration of the prod profile: \
+             AWS_LAMBDA_HTTP_IGNORE_STAGE_IN_PATH must be true or false: yes, \
+             S3_ENDPOINT_URL can't be set in prod, \
+             STANDARD_BUCKET_NAME is not set, \
+             TABLE_NAME is not set"
+        );
+    }
+
+    #[test]
+    fn test_environment_overrides_file() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.env");
+        std::fs::write(
+            &file,
+            "# the dev table\nAPP_PROFILE=dev\nTABLE_NAME=\"file-table\"\nSTANDARD_BUCKET_NAME=bucket\n",
+        )
+        .unwrap();
+        let loader = ConfigLoader::new()
+            .vars([
+                (CONFIG_FILE, file.to_str().unwrap()),
+                ("TABLE_NAME", "env-table"),
+            ])
+            .require(Setting::TableName);
+
+        // Act
+        let config = loader.load().unwrap();
+
+        // Assert
+        assert_eq!(config.profile, Profile::Dev);
+        assert_eq!(config.table_name().unwrap(), "env-table");
+        assert_eq!(config.bucket_name().unwrap(), "bucket");
+    }
+
+    #[test]
+    fn test_invalid_file() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.env");
+        std::fs::write(&file, "TABLE=table\nAPP_PROFILE\n").unwrap();
+
+        // Act
+        let result = ConfigLoader::new()
+            .file(&file)
+            .vars([("APP_PROFILE", "staging")])
+            .load();
+
+        // Assert
+        let message = result.unwrap_err().message().to_string();
+        assert!(message.contains("has an unknown setting TABLE"));
+        assert!(message.contains(":2 is not KEY=value"));
+        assert!(message.contains("APP_PROFILE must be local, dev, or prod: staging"));
+    }
+}
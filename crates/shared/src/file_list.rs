@@ -0,0 +1,63 @@
+//! The file lists that don't need the cloud
+//! [memory::InMemoryFileList] is for the tests, and [filesystem::LocalFileList] reads the local library.
+//! They list the same as the date lookups of the DynamoDB.
+//! The years, the months, and the days are in the numeric order, and the objects are the key names in the order of the time.
+
+pub mod filesystem;
+pub mod memory;
+
+use crate::error::Error;
+use crate::traits::Page;
+use time_file_name::file_datetime::PathDateTime;
+
+/// An object of the list
+/// The objects are ordered by the time, and by the key name if the times are the same.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct ObjectKey {
+    unix_time: i64,
+    key_name: String,
+    /// The year, the month, and the day
+    date: (usize, usize, usize),
+}
+
+impl ObjectKey {
+    fn parse(key_name: &str) -> Result<Self, Error> {
+        let time = PathDateTime::parse(key_name).map_err(Error::invalid_input)?;
+
+        Ok(Self {
+            unix_time: time.unix_time,
+            key_name: time.key_name(),
+            date: (time.year as usize, time.month as usize, time.day as usize),
+        })
+    }
+}
+
+/// Cut the page out of the sorted objects
+/// The cursor is the key name of the last object of the previous page.
+fn page_of(objects: &[ObjectKey], limit: i32, cursor: Option<String>) -> Result<Page, Error> {
+    if limit < 1 {
+        return Err(Error::invalid_input(format!("Invalid limit: {limit}")));
+    }
+
+    let start = match cursor {
+        Some(cursor) => {
+            let cursor = ObjectKey::parse(&cursor)
+                .map_err(|_| Error::invalid_input(format!("Invalid cursor: {cursor}")))?;
+            objects.partition_point(|object| object <= &cursor)
+        }
+        None => 0,
+    };
+
+    let end = objects.len().min(start + limit as usize);
+    let items: Vec<String> = objects[start..end]
+        .iter()
+        .map(|object| object.key_name.clone())
+        .collect();
+
+    let next_cursor = match end < objects.len() {
+        true => items.last().cloned(),
+        false => None,
+    };
+
+    Ok(Page { items, next_cursor })
+}
@@ -0,0 +1,257 @@
+//! The file list of the local library
+//! The library is `{root}/yyyy/M/d/yyyy-M-d-h-m-s.{extension}`, which the desktop app creates.
+
+use super::{page_of, ObjectKey};
+use crate::error::Error;
+use crate::traits::{GetFileListTrait, Page};
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The file list that walks the library directory
+/// The directory is read every time, so the list is always the current one.
+/// The directories that are not numbers and the files that are not named by the time are not listed.
+pub struct LocalFileList {
+    root: PathBuf,
+}
+
+impl LocalFileList {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The directories of the date level, which may be zero-padded
+    /// A level that doesn't exist is empty, as the prefix that has no objects in the bucket.
+    fn date_directories(&self, parents: &[usize]) -> Result<Vec<(usize, PathBuf)>, Error> {
+        let mut directories = vec![self.root.clone()];
+        for parent in parents {
+            directories = directories
+                .into_iter()
+                .map(|directory| read_directory(&directory))
+                .collect::<Result<Vec<_>, Error>>()?
+                .into_iter()
+                .flatten()
+                .filter(|(number, path)| number == parent && path.is_dir())
+                .map(|(_, path)| path)
+                .collect();
+        }
+
+        Ok(directories
+            .into_iter()
+            .map(|directory| read_directory(&directory))
+            .collect::<Result<Vec<_>, Error>>()?
+            .into_iter()
+            .flatten()
+            .filter(|(_, path)| path.is_dir())
+            .collect())
+    }
+
+    fn numbers(&self, parents: &[usize]) -> Result<Vec<String>, Error> {
+        Ok(self
+            .date_directories(parents)?
+            .into_iter()
+            .map(|(number, _)| number)
+            .collect::<BTreeSet<usize>>()
+            .into_iter()
+            .map(|number| number.to_string())
+            .collect())
+    }
+
+    fn objects_of(&self, year: usize, month: usize, day: usize) -> Result<Vec<ObjectKey>, Error> {
+        let mut objects = BTreeSet::new();
+
+        for (number, directory) in self.date_directories(&[year, month])? {
+            if number != day {
+                continue;
+            }
+
+            for entry in entries(&directory)? {
+                let path = entry.path();
+                let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if !path.is_file() {
+                    continue;
+                }
+                if let Ok(object) = ObjectKey::parse(&format!("{year}/{month}/{day}/{file_name}")) {
+                    objects.insert(object);
+                }
+            }
+        }
+
+        Ok(objects.into_iter().collect())
+    }
+}
+
+/// The entries of the directory
+/// The directory that doesn't exist has no entries.
+fn entries(directory: &Path) -> Result<Vec<std::fs::DirEntry>, Error> {
+    let read_dir = match std::fs::read_dir(directory) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(io_error(directory, e)),
+    };
+
+    read_dir
+        .collect::<Result<Vec<_>, io::Error>>()
+        .map_err(|e| io_error(directory, e))
+}
+
+/// The entries of the directory that are named by the numbers
+fn read_directory(directory: &Path) -> Result<Vec<(usize, PathBuf)>, Error> {
+    Ok(entries(directory)?
+        .into_iter()
+        .filter_map(|entry| {
+            let number = entry.file_name().to_str()?.parse::<usize>().ok()?;
+            Some((number, entry.path()))
+        })
+        .collect())
+}
+
+fn io_error(directory: &Path, e: io::Error) -> Error {
+    Error::internal(format!("Failed to read {}: {e}", directory.display())).with_source(e)
+}
+
+impl GetFileListTrait for LocalFileList {
+    async fn get_years(&self) -> Result<Vec<String>, Error> {
+        self.numbers(&[])
+    }
+
+    async fn get_months(&self, year: usize) -> Result<Vec<String>, Error> {
+        self.numbers(&[year])
+    }
+
+    async fn get_days(&self, year: usize, month: usize) -> Result<Vec<String>, Error> {
+        self.numbers(&[year, month])
+    }
+
+    async fn get_objects(
+        &self,
+        year: usize,
+        month: usize,
+        day: usize,
+    ) -> Result<Vec<String>, Error> {
+        Ok(self
+            .objects_of(year, month, day)?
+            .into_iter()
+            .map(|object| object.key_name)
+            .collect())
+    }
+
+    /// The cursor is the key name of the last object of the previous page.
+    async fn get_objects_page(
+        &self,
+        year: usize,
+        month: usize,
+        day: usize,
+        limit: i32,
+        cursor: Option<String>,
+    ) -> Result<Page, Error> {
+        page_of(&self.objects_of(year, month, day)?, limit, cursor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    /// The library of the desktop app
+    fn library() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        for path in [
+            "1984/4/4/1984-4-4-12-34-50.mp4",
+            "1984/4/4/1984-4-4-1-2-3.mov",
+            "1984/04/4/1984-4-4-1-2-4.mov",
+            "1984/10/1/1984-10-1-0-0-0.mp4",
+            "1984/9/30/1984-9-30-0-0-0.mp4",
+            "2001/1/1/2001-1-1-0-0-0.mp4",
+        ] {
+            let path = root.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"video").unwrap();
+        }
+        fs::create_dir_all(root.path().join("thumbnails")).unwrap();
+        fs::write(root.path().join("1984/4/4/.DS_Store"), b"").unwrap();
+
+        root
+    }
+
+    #[tokio::test]
+    async fn test_numeric_order() {
+        // Arrange
+        let root = library();
+        let list = LocalFileList::new(root.path());
+
+        // Act
+        let years = list.get_years().await.unwrap();
+        let months = list.get_months(1984).await.unwrap();
+        let days = list.get_days(1984, 4).await.unwrap();
+        let no_months = list.get_months(1985).await.unwrap();
+
+        // Assert
+        assert_eq!(years, ["1984", "2001"]);
+        assert_eq!(months, ["4", "9", "10"]);
+        assert_eq!(days, ["4"]);
+        assert!(no_months.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_objects_are_key_names_in_time_order() {
+        // Arrange
+        let root = library();
+        let list = LocalFileList::new(root.path());
+
+        // Act
+        let objects = list.get_objects(1984, 4, 4).await.unwrap();
+        let page = list
+            .get_objects_page(1984, 4, 4, 2, Some(objects[0].clone()))
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(
+            objects,
+            [
+                "1984/04/04/1984-04-04-01-02-03.mov",
+                "1984/04/04/1984-04-04-01-02-04.mov",
+                "1984/04/04/1984-04-04-12-34-50.mp4"
+            ]
+        );
+        assert_eq!(page.items, objects[1..]);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn test_same_as_in_memory() {
+        // Arrange
+        let root = library();
+        let local = LocalFileList::new(root.path());
+        let memory = crate::file_list::memory::InMemoryFileList::with_keys([
+            "/1984/4/4/1984-4-4-12-34-50.mp4",
+            "/1984/4/4/1984-4-4-1-2-3.mov",
+            "/1984/4/4/1984-4-4-1-2-4.mov",
+            "/1984/10/1/1984-10-1-0-0-0.mp4",
+            "/1984/9/30/1984-9-30-0-0-0.mp4",
+            "/2001/1/1/2001-1-1-0-0-0.mp4",
+        ])
+        .unwrap();
+
+        // Act
+        let local_page = local.get_objects_page(1984, 4, 4, 1, None).await.unwrap();
+        let memory_page = memory.get_objects_page(1984, 4, 4, 1, None).await.unwrap();
+
+        // Assert
+        assert_eq!(local.get_years().await, memory.get_years().await);
+        assert_eq!(local.get_months(1984).await, memory.get_months(1984).await);
+        assert_eq!(
+            local.get_objects(1984, 4, 4).await,
+            memory.get_objects(1984, 4, 4).await
+        );
+        assert_eq!(local_page, memory_page);
+    }
+}
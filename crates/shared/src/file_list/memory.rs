@@ -0,0 +1,220 @@
+//! The file list in the memory
+
+use super::{page_of, ObjectKey};
+use crate::error::Error;
+use crate::lock_ignoring_poison;
+use crate::traits::{GetFileListTrait, Page};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, MutexGuard};
+
+type Dates = BTreeMap<(usize, usize, usize), BTreeSet<ObjectKey>>;
+
+/// The file list that keeps the key names in the memory
+/// The date that has no objects is not listed.
+#[derive(Default)]
+pub struct InMemoryFileList {
+    objects: Mutex<Dates>,
+}
+
+impl InMemoryFileList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the list of the key names
+    pub fn with_keys<'a>(key_names: impl IntoIterator<Item = &'a str>) -> Result<Self, Error> {
+        let list = Self::new();
+        for key_name in key_names {
+            list.insert(key_name)?;
+        }
+        Ok(list)
+    }
+
+    /// Add the object
+    /// The key name is normalized, so `/1984/4/4/1984-4-4-12-34-5.mp4` is `1984/04/04/1984-04-04-12-34-05.mp4`.
+    pub fn insert(&self, key_name: &str) -> Result<(), Error> {
+        let object = ObjectKey::parse(key_name)?;
+        self.lock().entry(object.date).or_default().insert(object);
+        Ok(())
+    }
+
+    /// Remove the object, and returns if it existed
+    pub fn remove(&self, key_name: &str) -> Result<bool, Error> {
+        let object = ObjectKey::parse(key_name)?;
+        let date = object.date;
+        let mut dates = self.lock();
+
+        let Some(objects) = dates.get_mut(&date) else {
+            return Ok(false);
+        };
+        let removed = objects.remove(&object);
+        if objects.is_empty() {
+            dates.remove(&date);
+        }
+
+        Ok(removed)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Dates> {
+        lock_ignoring_poison(&self.objects)
+    }
+
+    fn objects_of(&self, year: usize, month: usize, day: usize) -> Vec<ObjectKey> {
+        match self.lock().get(&(year, month, day)) {
+            Some(objects) => objects.iter().cloned().collect(),
+            None => vec![],
+        }
+    }
+}
+
+/// The numbers in the numeric order without the duplicates
+fn numbers(numbers: impl Iterator<Item = usize>) -> Vec<String> {
+    numbers
+        .collect::<BTreeSet<usize>>()
+        .into_iter()
+        .map(|number| number.to_string())
+        .collect()
+}
+
+impl GetFileListTrait for InMemoryFileList {
+    async fn get_years(&self) -> Result<Vec<String>, Error> {
+        Ok(numbers(self.lock().keys().map(|(year, _, _)| *year)))
+    }
+
+    async fn get_months(&self, year: usize) -> Result<Vec<String>, Error> {
+        Ok(numbers(
+            self.lock()
+                .keys()
+                .filter(|(y, _, _)| *y == year)
+                .map(|(_, month, _)| *month),
+        ))
+    }
+
+    async fn get_days(&self, year: usize, month: usize) -> Result<Vec<String>, Error> {
+        Ok(numbers(
+            self.lock()
+                .keys()
+                .filter(|(y, m, _)| *y == year && *m == month)
+                .map(|(_, _, day)| *day),
+        ))
+    }
+
+    async fn get_objects(
+        &self,
+        year: usize,
+        month: usize,
+        day: usize,
+    ) -> Result<Vec<String>, Error> {
+        Ok(self
+            .objects_of(year, month, day)
+            .into_iter()
+            .map(|object| object.key_name)
+            .collect())
+    }
+
+    /// The cursor is the key name of the last object of the previous page.
+    async fn get_objects_page(
+        &self,
+        year: usize,
+        month: usize,
+        day: usize,
+        limit: i32,
+        cursor: Option<String>,
+    ) -> Result<Page, Error> {
+        page_of(&self.objects_of(year, month, day), limit, cursor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    fn file_list() -> InMemoryFileList {
+        InMemoryFileList::with_keys([
+            "/1984/4/4/1984-4-4-12-34-50.mp4",
+            "1984/04/04/1984-04-04-01-02-03.mov",
+            "/1984/10/1/1984-10-1-0-0-0.mp4",
+            "/1984/9/30/1984-9-30-0-0-0.mp4",
+            "/2001/1/1/2001-1-1-0-0-0.mp4",
+        ])
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_numeric_order() {
+        // Arrange
+        let list = file_list();
+
+        // Act
+        let years = list.get_years().await.unwrap();
+        let months = list.get_months(1984).await.unwrap();
+        let days = list.get_days(1984, 4).await.unwrap();
+        let no_days = list.get_days(1985, 4).await.unwrap();
+
+        // Assert
+        assert_eq!(years, ["1984", "2001"]);
+        assert_eq!(months, ["4", "9", "10"]);
+        assert_eq!(days, ["4"]);
+        assert!(no_days.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_objects_are_key_names_in_time_order() {
+        // Arrange
+        let list = file_list();
+
+        // Act
+        let objects = list.get_objects(1984, 4, 4).await.unwrap();
+
+        // Assert
+        assert_eq!(
+            objects,
+            [
+                "1984/04/04/1984-04-04-01-02-03.mov",
+                "1984/04/04/1984-04-04-12-34-50.mp4"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_objects_page() {
+        // Arrange
+        let list = file_list();
+
+        // Act
+        let first = list.get_objects_page(1984, 4, 4, 1, None).await.unwrap();
+        let second = list
+            .get_objects_page(1984, 4, 4, 1, first.next_cursor.clone())
+            .await
+            .unwrap();
+        let invalid = list
+            .get_objects_page(1984, 4, 4, 1, Some("cursor".to_string()))
+            .await;
+
+        // Assert
+        assert_eq!(first.items, ["1984/04/04/1984-04-04-01-02-03.mov"]);
+        assert_eq!(
+            first.next_cursor.as_deref(),
+            Some("1984/04/04/1984-04-04-01-02-03.mov")
+        );
+        assert_eq!(second.items, ["1984/04/04/1984-04-04-12-34-50.mp4"]);
+        assert_eq!(second.next_cursor, None);
+        assert_eq!(invalid.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn test_remove() {
+        // Arrange
+        let list = file_list();
+
+        // Act
+        let removed = list.remove("/2001/1/1/2001-1-1-0-0-0.mp4").unwrap();
+        let removed_again = list.remove("/2001/1/1/2001-1-1-0-0-0.mp4").unwrap();
+
+        // Assert
+        assert!(removed);
+        assert!(!removed_again);
+        assert_eq!(list.get_years().await.unwrap(), ["1984"]);
+    }
+}
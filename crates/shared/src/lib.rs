@@ -1,32 +1,216 @@
 //! This is a shared crate.
 //! This crate contains general things that are used in the whole system
 
+pub mod cache;
+
+pub mod config;
+
+pub mod file_list;
+
+pub mod metrics;
+
+use std::sync::{Mutex, MutexGuard};
+
+/// Lock the mutex even if a panic happened while it was held
+/// It is for the values that a panic while the lock is held doesn't leave broken,
+/// e.g. the maps that are changed by the single inserts and removals.
+pub fn lock_ignoring_poison<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub mod error {
+    use std::fmt::{Display, Formatter};
+
+    /// The category of the error
+    /// The web API maps it to the HTTP status code.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ErrorKind {
+        /// The target doesn't exist
+        NotFound,
+        /// The input is invalid, e.g. the key name doesn't follow the convention
+        InvalidInput,
+        /// The target has been changed, or it is in a state that doesn't allow the operation
+        Conflict,
+        /// The upstream service limits the requests. It can be retried later.
+        Throttled,
+        /// The upstream service cannot be reached or fails
+        UpstreamUnavailable,
+        Internal,
+    }
+
+    /// The source of the error
+    pub type Source = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+    /// The error of the whole system
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+        message: String,
+        source: Option<Source>,
+    }
+
+    impl Error {
+        pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+            Self {
+                kind,
+                message: message.into(),
+                source: None,
+            }
+        }
+
+        /// The error whose message is the one of the source
+        pub fn from_source(kind: ErrorKind, source: impl Into<Source>) -> Self {
+            let source = source.into();
+            Self {
+                kind,
+                message: source.to_string(),
+                source: Some(source),
+            }
+        }
+
+        pub fn not_found(message: impl Into<String>) -> Self {
+            Self::new(ErrorKind::NotFound, message)
+        }
+
+        pub fn invalid_input(message: impl Into<String>) -> Self {
+            Self::new(ErrorKind::InvalidInput, message)
+        }
+
+        pub fn conflict(message: impl Into<String>) -> Self {
+            Self::new(ErrorKind::Conflict, message)
+        }
+
+        pub fn throttled(message: impl Into<String>) -> Self {
+            Self::new(ErrorKind::Throttled, message)
+        }
+
+        pub fn upstream_unavailable(message: impl Into<String>) -> Self {
+            Self::new(ErrorKind::UpstreamUnavailable, message)
+        }
+
+        pub fn internal(message: impl Into<String>) -> Self {
+            Self::new(ErrorKind::Internal, message)
+        }
+
+        /// Attach the error that caused this one
+        pub fn with_source(mut self, source: impl Into<Source>) -> Self {
+            self.source = Some(source.into());
+            self
+        }
+
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+
+        pub fn message(&self) -> &str {
+            &self.message
+        }
+    }
+
+    impl Display for Error {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.message)
+        }
+    }
+
+    /// The errors are equal if the kinds and the messages are. The sources are not compared.
+    impl PartialEq for Error {
+        fn eq(&self, other: &Self) -> bool {
+            self.kind == other.kind && self.message == other.message
+        }
+    }
+
+    impl std::error::Error for Error {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            self.source
+                .as_ref()
+                .map(|source| source.as_ref() as &(dyn std::error::Error + 'static))
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use std::error::Error as _;
+
+        #[test]
+        fn test_error_with_source() {
+            // Arrange
+            let source = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+
+            // Act
+            let error =
+                Error::upstream_unavailable("The table cannot be reached").with_source(source);
+
+            // Assert
+            assert_eq!(error.kind(), ErrorKind::UpstreamUnavailable);
+            assert_eq!(error.to_string(), "The table cannot be reached");
+            assert_eq!(error.source().unwrap().to_string(), "timed out");
+        }
+
+        #[test]
+        fn test_error_from_source() {
+            // Arrange
+            let source = std::io::Error::other("broken");
+
+            // Act
+            let error = Error::from_source(ErrorKind::Internal, source);
+
+            // Assert
+            assert_eq!(error.message(), "broken");
+            assert!(error.source().is_some());
+        }
+    }
+}
+
 pub mod traits {
+    use crate::error::Error;
     use std::future::Future;
 
+    /// A page of the list
+    #[derive(Debug, Clone, PartialEq, Default)]
+    pub struct Page {
+        pub items: Vec<String>,
+        /// Pass it to get the next page. If None, this is the last page.
+        /// The cursor is opaque, and it is valid only for the implementation that returns it.
+        pub next_cursor: Option<String>,
+    }
+
     /// The searching is shared in the DB and bucket
     /// This trait defines basic access patterns
     #[cfg_attr(feature = "mock", mockall::automock)]
     pub trait GetFileListTrait {
         /// get years list
-        fn get_years(&self) -> impl Future<Output = Result<Vec<String>, String>> + Send;
+        fn get_years(&self) -> impl Future<Output = Result<Vec<String>, Error>> + Send;
         /// get months list
         fn get_months(
             &self,
             years: usize,
-        ) -> impl Future<Output = Result<Vec<String>, String>> + Send;
+        ) -> impl Future<Output = Result<Vec<String>, Error>> + Send;
         /// get days list
         fn get_days(
             &self,
             year: usize,
             month: usize,
-        ) -> impl Future<Output = Result<Vec<String>, String>> + Send;
+        ) -> impl Future<Output = Result<Vec<String>, Error>> + Send;
         /// get objects list
         fn get_objects(
             &self,
             year: usize,
             month: usize,
             day: usize,
-        ) -> impl Future<Output = Result<Vec<String>, String>> + Send;
+        ) -> impl Future<Output = Result<Vec<String>, Error>> + Send;
+        /// get a page of objects list
+        /// The page has up to `limit` objects. The cursor is the one that is returned by the previous page.
+        fn get_objects_page(
+            &self,
+            year: usize,
+            month: usize,
+            day: usize,
+            limit: i32,
+            cursor: Option<String>,
+        ) -> impl Future<Output = Result<Page, Error>> + Send;
     }
 }
@@ -0,0 +1,180 @@
+//! The metrics in the CloudWatch Embedded Metric Format
+//! A [MetricSet] is written to stdout as a JSON line, and CloudWatch Logs extracts the metrics from it,
+//! so the lambdas don't call the CloudWatch API. Locally, the lines are read in the output as they are.
+//! See https://docs.aws.amazon.com/AmazonCloudWatch/latest/monitoring/CloudWatch_Embedded_Metric_Format_Specification.html
+
+use serde_json::{json, Map, Value};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The namespace of the metrics of this system
+pub const NAMESPACE: &str = "VideoStorage";
+
+/// The unit of a metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Count,
+    Milliseconds,
+    Bytes,
+}
+
+impl Unit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Unit::Count => "Count",
+            Unit::Milliseconds => "Milliseconds",
+            Unit::Bytes => "Bytes",
+        }
+    }
+}
+
+/// The metrics that share the namespace and the dimensions
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricSet {
+    namespace: String,
+    dimensions: Vec<(String, String)>,
+    metrics: Vec<(String, f64, Unit)>,
+    properties: Vec<(String, String)>,
+}
+
+impl MetricSet {
+    pub fn new(namespace: impl Into<String>) -> Self {
+        Self {
+            namespace: namespace.into(),
+            dimensions: Vec::new(),
+            metrics: Vec::new(),
+            properties: Vec::new(),
+        }
+    }
+
+    /// Add the dimension that the metrics are aggregated by, e.g. the function name
+    pub fn dimension(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.dimensions.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn metric(mut self, name: impl Into<String>, value: f64, unit: Unit) -> Self {
+        self.metrics.push((name.into(), value, unit));
+        self
+    }
+
+    /// Add the value that is searched in the logs but is not aggregated, e.g. the request ID
+    pub fn property(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.properties.push((name.into(), value.into()));
+        self
+    }
+
+    /// The JSON line of the metrics
+    /// timestamp is mill sec
+    pub fn to_json(&self, timestamp: u128) -> String {
+        let mut root = Map::new();
+
+        for (name, value) in &self.properties {
+            root.insert(name.to_string(), json!(value));
+        }
+        for (name, value) in &self.dimensions {
+            root.insert(name.to_string(), json!(value));
+        }
+        for (name, value, _) in &self.metrics {
+            root.insert(name.to_string(), json!(value));
+        }
+
+        let dimensions = self
+            .dimensions
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<&str>>();
+        let metrics = self
+            .metrics
+            .iter()
+            .map(|(name, _, unit)| json!({"Name": name, "Unit": unit.as_str()}))
+            .collect::<Vec<Value>>();
+        root.insert(
+            "_aws".to_string(),
+            json!({
+                "Timestamp": timestamp as u64,
+                "CloudWatchMetrics": [{
+                    "Namespace": self.namespace,
+                    "Dimensions": [dimensions],
+                    "Metrics": metrics,
+                }],
+            }),
+        );
+
+        Value::Object(root).to_string()
+    }
+
+    /// Write the JSON line with the current time
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or_default();
+
+        writeln!(writer, "{}", self.to_json(timestamp))
+    }
+
+    /// Write the JSON line to stdout
+    /// A metric that can't be written is dropped, since it must not fail the request.
+    pub fn emit(&self) {
+        let _ = self.write_to(&mut std::io::stdout().lock());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        // Arrange
+        let metric_set = MetricSet::new(NAMESPACE)
+            .dimension("Function", "web-api")
+            .metric("Requests", 1.0, Unit::Count)
+            .metric("Latency", 12.5, Unit::Milliseconds)
+            .property("RequestId", "request-id");
+
+        // Act
+        let result = metric_set.to_json(1_700_000_000_000);
+
+        // Assert
+        assert_eq!(
+            serde_json::from_str::<Value>(&result).unwrap(),
+            json!({
+                "_aws": {
+                    "Timestamp": 1_700_000_000_000_u64,
+                    "CloudWatchMetrics": [{
+                        "Namespace": "VideoStorage",
+                        "Dimensions": [["Function"]],
+                        "Metrics": [
+                            {"Name": "Requests", "Unit": "Count"},
+                            {"Name": "Latency", "Unit": "Milliseconds"}
+                        ]
+                    }]
+                },
+                "Function": "web-api",
+                "Requests": 1.0,
+                "Latency": 12.5,
+                "RequestId": "request-id"
+            })
+        );
+    }
+
+    #[test]
+    fn test_write_to() {
+        // Arrange
+        let metric_set = MetricSet::new(NAMESPACE).metric("BytesUploaded", 1024.0, Unit::Bytes);
+        let mut output = Vec::new();
+
+        // Act
+        metric_set.write_to(&mut output).unwrap();
+
+        // Assert
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.ends_with('\n'));
+        assert_eq!(output.lines().count(), 1);
+        let line = serde_json::from_str::<Value>(&output).unwrap();
+        assert_eq!(line["BytesUploaded"], json!(1024.0));
+        assert!(line["_aws"]["Timestamp"].as_u64().unwrap() > 0);
+    }
+}
@@ -52,6 +52,54 @@ impl PathDateTime {
             iso_string: date_time.to_rfc3339().to_string(),
         })
     }
+
+    /// Returns the key name that follows the bucket convention.
+    /// The month, day, hour, minute, and second are zero-padded, and there is no leading slash.
+    /// {yyyy}/{MM}/{dd}/{yyyy}-{MM}-{dd}-{hh}-{mm}-{ss}.{extension}
+    ///
+    /// # Example
+    /// ```rust
+    /// # use time_file_name::file_datetime::PathDateTime;
+    /// # fn main() {
+    /// let date_time = PathDateTime::parse("/1984/4/4/1984-4-4-12-34-5.video").unwrap();
+    /// assert_eq!(date_time.key_name(), "1984/04/04/1984-04-04-12-34-05.video");
+    /// # }
+    /// ```
+    pub fn key_name(&self) -> String {
+        let date = format!("{}/{:02}/{:02}", self.year, self.month, self.day);
+        let file_name = format!(
+            "{}-{:02}-{:02}-{:02}-{:02}-{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        );
+
+        match self.file_name.split_once('.') {
+            Some((_, extension)) => format!("{date}/{file_name}.{extension}"),
+            None => format!("{date}/{file_name}"),
+        }
+    }
+}
+
+/// Returns the first and the last epoch time (milliseconds) of the day in UTC.
+/// This is used to narrow down the items by the date.
+///
+/// # Example
+/// ```rust
+/// # use time_file_name::file_datetime::day_range;
+/// # fn main() {
+/// let (start, end) = day_range(1970, 1, 2).unwrap();
+/// assert_eq!(start, 86_400_000);
+/// assert_eq!(end, 172_799_999);
+/// # }
+/// ```
+pub fn day_range(year: i32, month: u32, day: u32) -> Result<(i64, i64), String> {
+    let start = match Utc.with_ymd_and_hms(year, month, day, 0, 0, 0) {
+        LocalResult::Single(datetime) => datetime,
+        _ => return Err(format!("invalid date: {}-{}-{}", year, month, day)),
+    };
+
+    let start_millis = start.timestamp_millis();
+
+    Ok((start_millis, start_millis + 24 * 60 * 60 * 1000 - 1))
 }
 
 /// Convert from the file path to the DateTime of Chrono
@@ -202,6 +250,41 @@ mod test_from_file_name_to_date_time {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_key_name_is_not_changed() {
+        // Arrange
+        let path = "1984/04/04/1984-04-04-12-34-56.MOV";
+
+        // Act
+        let result = PathDateTime::parse(path).unwrap().key_name();
+
+        // Assert
+        assert_eq!(result, path);
+    }
+
+    #[test]
+    fn test_day_range() {
+        // Arrange
+        let date_time = Utc.with_ymd_and_hms(1984, 4, 4, 12, 34, 50).unwrap();
+
+        // Act
+        let (start, end) = day_range(1984, 4, 4).unwrap();
+
+        // Assert
+        assert!(start <= date_time.timestamp_millis());
+        assert!(date_time.timestamp_millis() <= end);
+        assert_eq!(end - start, 24 * 60 * 60 * 1000 - 1);
+    }
+
+    #[test]
+    fn test_day_range_invalid_date() {
+        // Act
+        let result = day_range(1984, 2, 30);
+
+        // Assert
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_retrieve_file_name() {
         let path = "1984/04/04/1984-4-4-12-34-56.video";
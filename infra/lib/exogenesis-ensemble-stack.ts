@@ -51,7 +51,8 @@ export class ExogenesisEnsembleStack extends cdk.Stack {
 		standardS3Bucket.grantRead(s3HookFunction);
 
 		// DynamoTable
-		dynamoTable.grantReadData(apiFunction);
+		// the API records the upload sessions
+		dynamoTable.grantReadWriteData(apiFunction);
 		dynamoTable.grantReadWriteData(s3HookFunction);
 
 		// add permission to lambdas to put events
@@ -82,7 +83,7 @@ export class ExogenesisEnsembleStack extends cdk.Stack {
 	}
 
 	private dynamoDb(): dynamodb.Table {
-		return new dynamodb.Table(this, "DynamoDB", {
+		const table = new dynamodb.Table(this, "DynamoDB", {
 			tableName: `${APP_NAME}-table-${this.stage}`,
 			removalPolicy:
 				this.stage === "dev" ? RemovalPolicy.DESTROY : RemovalPolicy.RETAIN,
@@ -95,7 +96,22 @@ export class ExogenesisEnsembleStack extends cdk.Stack {
 				type: AttributeType.NUMBER,
 			},
 			billingMode: BillingMode.PAY_PER_REQUEST,
+			timeToLiveAttribute: "ExpireAt",
 		});
+
+		// must be the same as `aws_clients::dynamodb::schema::global_secondary_indexes`
+		for (const [indexName, partitionKey] of [
+			["VaultIndex", "Vault"],
+			["StorageStateIndex", "StorageState"],
+		]) {
+			table.addGlobalSecondaryIndex({
+				indexName,
+				partitionKey: { name: partitionKey, type: AttributeType.STRING },
+				sortKey: { name: "SK", type: AttributeType.NUMBER },
+			});
+		}
+
+		return table;
 	}
 
 	/**
@@ -116,6 +132,7 @@ export class ExogenesisEnsembleStack extends cdk.Stack {
 			manifestPath: path.join(__dirname, "../../lambdas/web-api-app"),
 			runtime: "provided.al2023",
 			environment: {
+				APP_PROFILE: this.stage,
 				STANDARD_BUCKET_NAME: standardBucketName,
 				TABLE_NAME: tableName,
 			},
@@ -126,6 +143,7 @@ export class ExogenesisEnsembleStack extends cdk.Stack {
 			manifestPath: path.join(__dirname, "../../lambdas/s3-hook-app"),
 			runtime: "provided.al2023",
 			environment: {
+				APP_PROFILE: this.stage,
 				TABLE_NAME: tableName,
 			},
 		});
@@ -1,20 +1,246 @@
+use aws_clients::dynamodb::client::DynamoDbClient;
+use aws_clients::retry::{retry_stats, Retryability};
 use aws_lambda_events::event::s3::S3Event;
+use lambda_runtime::tracing::subscriber::fmt::format::FmtSpan;
+use lambda_runtime::tracing::Instrument;
 use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use shared::config::{ConfigLoader, Setting};
+use shared::metrics::{MetricSet, Unit, NAMESPACE};
+use std::env;
+use std::str::FromStr;
+
+/// The name of the function in the dimensions of the metrics
+const FUNCTION: &str = "s3-hook";
 
 /// This is the main body for the function.
 /// Write your code inside it.
 /// There are some code example in the following URLs:
 /// - https://github.com/awslabs/aws-lambda-rust-runtime/tree/main/examples
 /// - https://github.com/aws-samples/serverless-rust-demo/
+/// The calls of the clients are in the span of the record, so they are correlated by the request ID.
+/// A record that fails doesn't stop the others. The event fails only if a record failed by a transient error,
+/// so the event is retried, and the completed records are skipped then.
+/// The counts of the AWS calls of the invocation are emitted at the end.
 async fn function_handler(event: LambdaEvent<S3Event>) -> Result<(), Error> {
-    // Extract some useful information from the request
+    let request_id = event.context.request_id;
+    let retry_stats_before = retry_stats();
+    let client = DynamoDbClient::from_env().await?;
+
+    let mut transient_error = None;
+    for record in event.payload.records {
+        let bucket = record.s3.bucket.name.unwrap_or_default();
+        let object = record.s3.object;
+        let Some(key) = object.key else {
+            continue;
+        };
+
+        let span = tracing::info_span!("s3_record", request_id, bucket, key);
+        let e_tag = object.e_tag.as_deref();
+        let result = complete_upload_session(&client, &request_id, &key, object.size, e_tag)
+            .instrument(span.clone())
+            .await;
+
+        if let Err(e) = result {
+            let _entered = span.enter();
+            match Retryability::of(&e) {
+                Retryability::Permanent => {
+                    tracing::warn!(error_kind = ?e.kind(), "The record is skipped: {e}")
+                }
+                _ => {
+                    tracing::warn!(
+                        error_kind = ?e.kind(),
+                        "The record failed, and the event is retried: {e}"
+                    );
+                    transient_error.get_or_insert(e);
+                }
+            }
+        }
+    }
+
+    retry_stats()
+        .since(&retry_stats_before)
+        .add_to(MetricSet::new(NAMESPACE).dimension("Function", FUNCTION))
+        .property("RequestId", &request_id)
+        .emit();
+
+    match transient_error {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+/// Mark the upload session of the uploaded object as completed.
+/// The checksum that the client reported is compared with the ETag.
+/// The bytes of the uploaded object are emitted as a metric, if its session is completed.
+async fn complete_upload_session(
+    client: &DynamoDbClient,
+    request_id: &str,
+    key: &str,
+    size: Option<i64>,
+    e_tag: Option<&str>,
+) -> Result<(), shared::error::Error> {
+    let result = client.complete_upload_session(key, size, e_tag, None).await;
+
+    let metric_set = MetricSet::new(NAMESPACE)
+        .dimension("Function", FUNCTION)
+        .property("RequestId", request_id)
+        .property("Key", key);
+    let session = match result {
+        Ok(session) => session,
+        Err(e) => {
+            metric_set
+                .dimension("ErrorKind", format!("{:?}", e.kind()))
+                .metric("Errors", 1.0, Unit::Count)
+                .emit();
+            return Err(e);
+        }
+    };
+
+    match session {
+        Some(session) => {
+            metric_set
+                .metric("Uploads", 1.0, Unit::Count)
+                .metric(
+                    "BytesUploaded",
+                    size.unwrap_or_default() as f64,
+                    Unit::Bytes,
+                )
+                .emit();
+            tracing::info!(
+                state = session.state.as_str(),
+                "The upload session is updated"
+            )
+        }
+        None => tracing::info!("There is no pending upload session"),
+    }
 
     Ok(())
 }
 
+/// The logs are the JSON lines, and the closed spans are logged with their time,
+/// so the latency of the calls of the clients are in the logs.
+/// The level is read from `AWS_LAMBDA_LOG_LEVEL` or `RUST_LOG` as the default subscriber does, and it is INFO by default.
+fn init_tracing() {
+    let level = env::var("AWS_LAMBDA_LOG_LEVEL")
+        .or_else(|_| env::var("RUST_LOG"))
+        .ok()
+        .and_then(|level| tracing::Level::from_str(&level).ok())
+        .unwrap_or(tracing::Level::INFO);
+
+    tracing::subscriber::fmt()
+        .json()
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_target(false)
+        .init();
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    tracing::init_default_subscriber();
+    init_tracing();
+
+    // the settings are validated before the first event
+    ConfigLoader::new().require(Setting::TableName).load()?;
 
     run(service_fn(function_handler)).await
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aws_clients::dynamodb::upload_session::{UploadSession, UploadState};
+    use aws_lambda_events::event::s3::{S3Entity, S3EventRecord, S3Object};
+    use lambda_runtime::Context;
+    use std::time::Duration;
+    use tokio::sync::OnceCell;
+
+    /// The table of the fake that the handler reads from the environment
+    const TABLE_NAME: &str = "s3-hook-app-test";
+
+    /// The client of the table of the environment
+    /// The table is created once, since the tests share the fake.
+    async fn client() -> DynamoDbClient {
+        static TABLE: OnceCell<()> = OnceCell::const_new();
+
+        TABLE
+            .get_or_init(|| async {
+                // the variable is set before the configuration is loaded by any test
+                env::set_var("TABLE_NAME", TABLE_NAME);
+                let client = DynamoDbClient::from_env().await.unwrap();
+                client.ensure_table().await.unwrap();
+            })
+            .await;
+
+        DynamoDbClient::from_env().await.unwrap()
+    }
+
+    /// Save the pending session of the key that expects the size
+    async fn put_pending_session(client: &DynamoDbClient, key_name: &str, size: i64) {
+        let mut session = UploadSession::new(key_name, Duration::from_secs(900), None).unwrap();
+        session.expected_size = Some(size);
+        client.put_upload_session(&session).await.unwrap();
+    }
+
+    /// The event of the objects that are uploaded, whose sizes are 1024
+    fn event(keys: &[&str]) -> LambdaEvent<S3Event> {
+        let records = keys
+            .iter()
+            .map(|key| S3EventRecord {
+                s3: S3Entity {
+                    object: S3Object {
+                        key: Some(key.to_string()),
+                        size: Some(1024),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .collect();
+
+        LambdaEvent::new(S3Event { records }, Context::default())
+    }
+
+    #[tokio::test]
+    async fn test_function_handler_completes_the_sessions() {
+        // Arrange
+        let client = client().await;
+        let completed = "1986/06/06/1986-06-06-12-34-50.MOV";
+        let failed = "1986/06/06/1986-06-06-12-34-51.MOV";
+        put_pending_session(&client, completed, 1024).await;
+        put_pending_session(&client, failed, 2048).await;
+
+        // Act
+        let result = function_handler(event(&[completed, failed])).await;
+
+        // Assert
+        assert!(result.is_ok());
+        let state_of = |session: Option<UploadSession>| session.map(|session| session.state);
+        assert_eq!(
+            state_of(client.get_upload_session(completed).await.unwrap()),
+            Some(UploadState::Completed)
+        );
+        assert_eq!(
+            state_of(client.get_upload_session(failed).await.unwrap()),
+            Some(UploadState::Failed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_function_handler_skips_the_permanent_errors() {
+        // Arrange
+        let client = client().await;
+        let key_name = "1986/07/07/1986-07-07-12-34-50.MOV";
+        let no_session = "1986/07/07/1986-07-07-12-34-51.MOV";
+        put_pending_session(&client, key_name, 1024).await;
+
+        // Act
+        let result = function_handler(event(&["invalid-key", no_session, key_name])).await;
+
+        // Assert
+        assert!(result.is_ok());
+        let session = client.get_upload_session(key_name).await.unwrap().unwrap();
+        assert_eq!(session.state, UploadState::Completed);
+        assert_eq!(client.get_upload_session(no_session).await.unwrap(), None);
+    }
+}
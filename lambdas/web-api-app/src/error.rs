@@ -1,33 +1,132 @@
 use crate::error::WebApiAppError::{DBError, StorageError, ValidationError};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use lambda_http::tracing::log;
+use axum::Json;
+use lambda_http::tracing;
+use serde_json::json;
+use shared::error::{Error, ErrorKind};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum WebApiAppError {
     #[error("{0}")]
-    #[allow(dead_code)] // TODO: review
     ValidationError(String),
     #[error("Storage error: {0}")]
-    StorageError(String),
+    StorageError(#[source] Error),
     #[error("DB error: {0}")]
-    DBError(String),
+    DBError(#[source] Error),
 }
 
 impl WebApiAppError {
+    /// The category of the error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ValidationError(_) => ErrorKind::InvalidInput,
+            StorageError(e) | DBError(e) => e.kind(),
+        }
+    }
+
     /// Return the axum error from the thiserror enum
+    /// The status code is decided by the kind of the error.
+    /// The kind is kept in the extensions of the response, so the metrics of the request have it.
     pub fn return_http_response(&self) -> impl IntoResponse {
-        match self {
-            ValidationError(reason) => (StatusCode::BAD_REQUEST, reason.to_owned()).into_response(),
-            StorageError(reason) => {
-                log::error!("{}", reason);
-                (StatusCode::INTERNAL_SERVER_ERROR, reason.to_owned()).into_response()
-            }
-            DBError(reason) => {
-                log::error!("{}", reason);
-                (StatusCode::INTERNAL_SERVER_ERROR, reason.to_owned()).into_response()
-            }
+        let kind = self.kind();
+        let status_code = status_code(kind);
+        if status_code.is_server_error() {
+            tracing::error!(error_kind = ?kind, "{self}");
+        }
+
+        let mut response = (status_code, Json(json!({"error": self.to_string()}))).into_response();
+        response.extensions_mut().insert(kind);
+        response
+    }
+}
+
+/// The HTTP status code of the error kind
+fn status_code(kind: ErrorKind) -> StatusCode {
+    match kind {
+        ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+        ErrorKind::Conflict => StatusCode::CONFLICT,
+        ErrorKind::Throttled => StatusCode::TOO_MANY_REQUESTS,
+        ErrorKind::UpstreamUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::to_bytes;
+    use serde_json::Value;
+
+    #[test]
+    fn test_status_code() {
+        // Arrange
+        let cases = [
+            (ErrorKind::NotFound, StatusCode::NOT_FOUND),
+            (ErrorKind::InvalidInput, StatusCode::BAD_REQUEST),
+            (ErrorKind::Conflict, StatusCode::CONFLICT),
+            (ErrorKind::Throttled, StatusCode::TOO_MANY_REQUESTS),
+            (
+                ErrorKind::UpstreamUnavailable,
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            (ErrorKind::Internal, StatusCode::INTERNAL_SERVER_ERROR),
+        ];
+
+        for (kind, expected) in cases {
+            // Act
+            let result = status_code(kind);
+
+            // Assert
+            assert_eq!(result, expected, "{kind:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_return_http_response() {
+        // Arrange
+        let cases = [
+            (
+                ValidationError("The limit must be between 1 and 1000: 0".to_string()),
+                StatusCode::BAD_REQUEST,
+                "The limit must be between 1 and 1000: 0",
+            ),
+            (
+                DBError(Error::invalid_input("invalid date: 1984-2-30")),
+                StatusCode::BAD_REQUEST,
+                "DB error: invalid date: 1984-2-30",
+            ),
+            (
+                StorageError(Error::throttled(
+                    "SlowDown: Please reduce your request rate",
+                )),
+                StatusCode::TOO_MANY_REQUESTS,
+                "Storage error: SlowDown: Please reduce your request rate",
+            ),
+            (
+                DBError(Error::internal("KeyName must be a string")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "DB error: KeyName must be a string",
+            ),
+        ];
+
+        for (error, status, message) in cases {
+            // Act
+            let response = error.return_http_response().into_response();
+
+            // Assert
+            assert_eq!(response.status(), status);
+            assert_eq!(
+                response.extensions().get::<ErrorKind>(),
+                Some(&error.kind())
+            );
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(
+                serde_json::from_slice::<Value>(&body).unwrap(),
+                json!({ "error": message })
+            );
         }
     }
 }
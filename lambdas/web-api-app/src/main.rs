@@ -1,30 +1,63 @@
 mod error;
+mod observability;
 mod routes;
 
 use crate::routes::{bucket, db};
+use axum::middleware;
 use axum::response::Json;
 use axum::routing::get;
 use axum::Router;
+use lambda_http::tracing::subscriber::fmt::format::FmtSpan;
 use lambda_http::{run, tracing, Error};
 use serde_json::{json, Value};
-use std::env::set_var;
+use shared::config::{ConfigLoader, Setting};
+use std::env::{self, set_var};
+use std::str::FromStr;
 
 async fn greet() -> Json<Value> {
     Json(json!({"body": "hello world"}))
 }
 
+/// The logs are the JSON lines, and the closed spans are logged with their time,
+/// so the latency of the requests and of the calls of the clients are in the logs.
+/// The level is read from `AWS_LAMBDA_LOG_LEVEL` or `RUST_LOG` as the default subscriber does, and it is INFO by default.
+fn init_tracing() {
+    let level = env::var("AWS_LAMBDA_LOG_LEVEL")
+        .or_else(|_| env::var("RUST_LOG"))
+        .ok()
+        .and_then(|level| tracing::Level::from_str(&level).ok())
+        .unwrap_or(tracing::Level::INFO);
+
+    tracing::subscriber::fmt()
+        .json()
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_target(false)
+        .init();
+}
+
 /// This project uses the [Axum](https://docs.rs/axum/latest/axum/).
 /// The way of adoption of Axum refers to [this repo](https://github.com/awslabs/aws-lambda-rust-runtime/blob/main/examples/http-axum/src/main.rs).
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    set_var("AWS_LAMBDA_HTTP_IGNORE_STAGE_IN_PATH", "true");
+    init_tracing();
+
+    // the settings are validated before the first request
+    let config = ConfigLoader::new()
+        .require(Setting::TableName)
+        .require(Setting::BucketName)
+        .load()?;
 
-    tracing::init_default_subscriber();
+    set_var(
+        Setting::IgnoreStageInPath.key(),
+        config.ignore_stage_in_path.to_string(),
+    );
 
     let app = Router::new()
         .route("/", get(greet))
         .nest("/bucket", bucket::route::bucket_routes())
-        .nest("/db", db::routes::db_routes());
+        .nest("/db", db::routes::db_routes())
+        .layer(middleware::from_fn(observability::observe_request));
 
     run(app).await
 }
@@ -0,0 +1,90 @@
+//! The request ID, the span and the metrics of the requests
+//! The calls of the clients in a request are in its span, so they are correlated by the request ID of the lambda.
+
+use aws_clients::retry::retry_stats;
+use axum::extract::{MatchedPath, Request};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use lambda_http::tracing::{self, Instrument};
+use lambda_http::RequestExt;
+use shared::error::ErrorKind;
+use shared::metrics::{MetricSet, Unit, NAMESPACE};
+use std::time::Instant;
+
+/// The name of the function in the dimensions of the metrics
+const FUNCTION: &str = "web-api";
+
+/// The header of the request ID
+/// It is returned, so the client can tell it when it reports an error.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Run the request in its span, and emit the metrics of it
+/// The error kind is read from the response, which [return_http_response](crate::error::WebApiAppError::return_http_response) sets.
+/// The counts of the AWS calls are the ones during the request, since a lambda runs one request at a time.
+pub async fn observe_request(request: Request, next: Next) -> Response {
+    let request_id = request_id(&request);
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(matched_path) => matched_path.as_str().to_string(),
+        None => "unmatched".to_string(),
+    };
+    let span = tracing::info_span!(
+        "request",
+        request_id,
+        method = %request.method(),
+        route,
+        status = tracing::field::Empty,
+    );
+
+    let started_at = Instant::now();
+    let retry_stats_before = retry_stats();
+    let mut response = next.run(request).instrument(span.clone()).await;
+    let latency = started_at.elapsed().as_secs_f64() * 1000.0;
+
+    let status = response.status().as_u16();
+    span.record("status", status);
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    let metric_set = MetricSet::new(NAMESPACE)
+        .dimension("Function", FUNCTION)
+        .metric("Requests", 1.0, Unit::Count)
+        .metric("Latency", latency, Unit::Milliseconds);
+    retry_stats()
+        .since(&retry_stats_before)
+        .add_to(metric_set)
+        .property("RequestId", &request_id)
+        .property("Route", &route)
+        .property("Status", status.to_string())
+        .emit();
+
+    if let Some(kind) = response.extensions().get::<ErrorKind>() {
+        MetricSet::new(NAMESPACE)
+            .dimension("Function", FUNCTION)
+            .dimension("ErrorKind", format!("{kind:?}"))
+            .metric("Errors", 1.0, Unit::Count)
+            .property("RequestId", &request_id)
+            .property("Route", &route)
+            .emit();
+    }
+
+    response
+}
+
+/// The request ID of the lambda invocation
+/// Out of the lambda, e.g. in the local server, the header of the request is used.
+fn request_id(request: &Request) -> String {
+    if let Some(context) = request.lambda_context_ref() {
+        return context.request_id.to_string();
+    }
+
+    match request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(request_id) => request_id.to_string(),
+        None => "-".to_string(),
+    }
+}
@@ -1,3 +1,4 @@
 pub mod bucket;
 pub mod db;
+pub(crate) mod page;
 pub(crate) mod return_types;
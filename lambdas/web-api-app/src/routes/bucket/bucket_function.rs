@@ -1,64 +1,144 @@
 //! This mod has s3-related functions.
 
 use crate::error::WebApiAppError;
+use crate::routes::page::{read_objects, PageQuery};
 use crate::routes::return_types::return_data_types::{
     DaysVideos, MonthsVideos, VideoObjects, YearsVideos,
 };
-use aws_clients::s3::client::{StandardS3Client, StandardS3ClientTrait};
+use aws_clients::dynamodb::client::DynamoDbClient;
+use aws_clients::dynamodb::upload_session::UploadSession;
+use aws_clients::s3::client::{StandardS3Client, StandardS3ClientTrait, PRE_SIGN_EXPIRING_TIME};
+use shared::cache::{CacheConfig, CachedFileList};
 use shared::traits::GetFileListTrait;
+use time_file_name::file_datetime::PathDateTime;
+use time_file_name::file_path::FilePath;
+use tokio::sync::OnceCell;
+
+/// The lists of the bucket that are kept while the lambda is warm
+/// The objects are uploaded with the pre-signed URLs, and the S3 hook that sees the upload runs in another process,
+/// so it can't invalidate these lists. The uploaded object shows up when the list of its level expires.
+static BUCKET_LISTS: OnceCell<CachedFileList<StandardS3Client>> = OnceCell::const_new();
+
+/// The lists of the bucket of the environment
+/// If the bucket is not configured, returns Err, and it is tried again by the next request.
+async fn bucket_lists() -> Result<&'static CachedFileList<StandardS3Client>, WebApiAppError> {
+    BUCKET_LISTS
+        .get_or_try_init(|| async {
+            match StandardS3Client::new().await {
+                Ok(client) => Ok(CachedFileList::new(client, CacheConfig::default())),
+                Err(e) => Err(WebApiAppError::StorageError(e)),
+            }
+        })
+        .await
+}
+
+/// Invalidate the lists of the date of the key name
+/// The lists that are not cached yet are left as they are.
+fn invalidate_key(key_name: &str) {
+    let (Some(bucket_lists), Ok(path_date_time)) =
+        (BUCKET_LISTS.get(), PathDateTime::parse(key_name))
+    else {
+        return;
+    };
+
+    bucket_lists.invalidate_date(
+        path_date_time.year as usize,
+        path_date_time.month as usize,
+        path_date_time.day as usize,
+    );
+}
 
 /// Read the years that exist items in the s3 bucket.
 pub async fn get_years() -> Result<YearsVideos, WebApiAppError> {
-    match StandardS3Client::new().await.get_years().await {
+    match bucket_lists().await?.get_years().await {
         Ok(years) => Ok(YearsVideos { years }),
-        Err(_) => Err(WebApiAppError::StorageError("Get years failed".to_string())),
+        Err(e) => Err(WebApiAppError::StorageError(e)),
     }
 }
 
 /// Read the month that existing items are narrowed down by year in the s3 bucket.
 pub async fn get_months(year: usize) -> Result<MonthsVideos, WebApiAppError> {
-    match StandardS3Client::new().await.get_months(year).await {
+    match bucket_lists().await?.get_months(year).await {
         Ok(months) => Ok(MonthsVideos { months }),
-        Err(_) => Err(WebApiAppError::StorageError(
-            "Get months failed".to_string(),
-        )),
+        Err(e) => Err(WebApiAppError::StorageError(e)),
     }
 }
 
 /// Read the days that existing items are narrowed down by year and month in the s3 bucket.
 pub async fn get_days(years: usize, months: usize) -> Result<DaysVideos, WebApiAppError> {
-    match StandardS3Client::new().await.get_days(years, months).await {
+    match bucket_lists().await?.get_days(years, months).await {
         Ok(days) => Ok(DaysVideos { days }),
-        Err(_) => Err(WebApiAppError::StorageError("Get days failed".to_string())),
+        Err(e) => Err(WebApiAppError::StorageError(e)),
     }
 }
 
 /// Read the objects that existing items are narrowed down by year, month and day in the s3 bucket.
+/// If the page is queried, a page of the objects is returned.
 pub async fn get_objects(
     year: usize,
     month: usize,
     day: usize,
+    query: PageQuery,
 ) -> Result<VideoObjects, WebApiAppError> {
-    match StandardS3Client::new()
-        .await
-        .get_objects(year, month, day)
-        .await
-    {
-        Ok(objects) => Ok(VideoObjects { objects }),
-        Err(_) => Err(WebApiAppError::StorageError(
-            "Get objects failed".to_string(),
-        )),
+    match read_objects(bucket_lists().await?, (year, month, day), query).await? {
+        Ok(page) => Ok(VideoObjects {
+            objects: page.items,
+            metadata: None,
+            next_cursor: page.next_cursor,
+        }),
+        Err(e) => Err(WebApiAppError::StorageError(e)),
     }
 }
 
+/// generate the pre-signed URL, and record the upload session of it
+/// The session is completed by the S3 hook when the object is uploaded.
+/// The cached lists of the date are dropped here, since this lambda doesn't see the upload itself.
+/// A list that is read again before the upload finishes stays until its TTL.
 pub async fn generate_pre_signed_url_for_upload(
     date_time: &str,
     extension: &str,
+    expectation: UploadExpectation,
 ) -> Result<String, WebApiAppError> {
-    match StandardS3Client::generate_pre_signed_url_for_video(date_time, extension).await {
-        Ok(url) => Ok(url),
-        Err(_) => Err(WebApiAppError::StorageError(
-            "generate_pre_signed_url_for_video failed".to_string(),
-        )),
+    let key_name = match FilePath::new().generate_file_path(date_time, extension) {
+        Ok(key_name) => key_name,
+        Err(e) => return Err(WebApiAppError::ValidationError(e)),
+    };
+
+    let url = match StandardS3Client::generate_pre_signed_url_for_video(date_time, extension).await
+    {
+        Ok(url) => url,
+        Err(e) => return Err(WebApiAppError::StorageError(e)),
+    };
+
+    let session = match UploadSession::new(&key_name, PRE_SIGN_EXPIRING_TIME, None) {
+        Ok(session) => UploadSession {
+            uploader: expectation.uploader,
+            expected_size: expectation.size,
+            expected_checksum: expectation.checksum,
+            ..session
+        },
+        Err(e) => return Err(WebApiAppError::ValidationError(e.to_string())),
+    };
+
+    let client = match DynamoDbClient::from_env().await {
+        Ok(client) => client,
+        Err(e) => return Err(WebApiAppError::DBError(e)),
+    };
+
+    if let Err(e) = client.put_upload_session(&session).await {
+        return Err(WebApiAppError::DBError(e));
     }
+
+    invalidate_key(&key_name);
+
+    Ok(url)
+}
+
+/// What the client reports about the object to upload
+/// They are checked when the object is uploaded.
+#[derive(Default)]
+pub struct UploadExpectation {
+    pub uploader: Option<String>,
+    pub size: Option<i64>,
+    pub checksum: Option<String>,
 }
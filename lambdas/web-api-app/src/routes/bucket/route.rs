@@ -2,8 +2,10 @@
 
 use crate::routes::bucket::bucket_function::{
     generate_pre_signed_url_for_upload, get_days, get_months, get_objects, get_years,
+    UploadExpectation,
 };
-use axum::extract::Path;
+use crate::routes::page::PageQuery;
+use axum::extract::{Path, Query};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::routing::get;
@@ -33,11 +35,7 @@ pub fn bucket_routes() -> Router {
 async fn videos_handler() -> impl IntoResponse {
     match get_years().await {
         Ok(years) => (StatusCode::OK, Json(json!(years))).into_response(),
-        Err(e) => (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => e.return_http_response().into_response(),
     }
 }
 
@@ -45,11 +43,7 @@ async fn videos_handler() -> impl IntoResponse {
 async fn months_videos_handler(Path(year): Path<usize>) -> impl IntoResponse {
     match get_months(year).await {
         Ok(months) => (StatusCode::OK, Json(json!(months))).into_response(),
-        Err(e) => (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => e.return_http_response().into_response(),
     }
 }
 
@@ -57,25 +51,19 @@ async fn months_videos_handler(Path(year): Path<usize>) -> impl IntoResponse {
 async fn days_videos_handler(Path((year, month)): Path<(usize, usize)>) -> impl IntoResponse {
     match get_days(year, month).await {
         Ok(days) => (StatusCode::OK, Json(json!(days))).into_response(),
-        Err(e) => (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => e.return_http_response().into_response(),
     }
 }
 
 /// The wrapper of the get_objects
+/// `?limit=&cursor=` reads a page of the objects.
 async fn get_objects_handler(
     Path((year, month, day)): Path<(usize, usize, usize)>,
+    Query(query): Query<PageQuery>,
 ) -> impl IntoResponse {
-    match get_objects(year, month, day).await {
+    match get_objects(year, month, day, query).await {
         Ok(video_objects) => (StatusCode::OK, Json(json!(video_objects))).into_response(),
-        Err(e) => (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => e.return_http_response().into_response(),
     }
 }
 
@@ -84,12 +72,22 @@ async fn get_objects_handler(
 struct GetPreSingnedUrlPayload {
     dateTime: String,
     extension: String,
+    uploader: Option<String>,
+    size: Option<i64>,
+    checksum: Option<String>,
 }
 
 async fn get_pre_singed_url(Json(payload): Json<GetPreSingnedUrlPayload>) -> impl IntoResponse {
     let date_time = payload.dateTime;
     let extension = payload.extension;
-    match generate_pre_signed_url_for_upload(date_time.as_str(), &extension.as_str()).await {
+    let expectation = UploadExpectation {
+        uploader: payload.uploader,
+        size: payload.size,
+        checksum: payload.checksum,
+    };
+    match generate_pre_signed_url_for_upload(date_time.as_str(), &extension.as_str(), expectation)
+        .await
+    {
         Ok(url) => (StatusCode::OK, url).into_response(),
         Err(e) => e.return_http_response().into_response(),
     }
@@ -1,15 +1,24 @@
 //! DB related functions
 
 use crate::error::WebApiAppError;
+use crate::routes::page::{read_objects, PageQuery};
 use crate::routes::return_types::return_data_types::{
-    DaysVideos, MonthsVideos, VideoObjects, YearsVideos,
+    DaysVideos, MonthsVideos, StaleUploadSessions, VideoObjects, YearsVideos,
 };
-use aws_clients::dynamodb::client::DynamoDbClient;
+use aws_clients::dynamodb::client::{DynamoClientTrait, DynamoDbClient};
 use shared::traits::GetFileListTrait;
 
+/// create the DB client from the environment
+async fn db_client() -> Result<DynamoDbClient, WebApiAppError> {
+    match DynamoDbClient::from_env().await {
+        Ok(client) => Ok(client),
+        Err(e) => Err(WebApiAppError::DBError(e)),
+    }
+}
+
 /// get years that stored in the DB
 pub async fn get_years() -> Result<YearsVideos, WebApiAppError> {
-    match DynamoDbClient::new().await.get_years().await {
+    match db_client().await?.get_years().await {
         Ok(years) => Ok(YearsVideos { years }),
         Err(e) => Err(WebApiAppError::DBError(e)),
     }
@@ -17,7 +26,7 @@ pub async fn get_years() -> Result<YearsVideos, WebApiAppError> {
 
 /// get months that stored in the DB
 pub async fn get_months(year: usize) -> Result<MonthsVideos, WebApiAppError> {
-    match DynamoDbClient::new().await.get_months(year).await {
+    match db_client().await?.get_months(year).await {
         Ok(months) => Ok(MonthsVideos { months }),
         Err(e) => Err(WebApiAppError::DBError(e)),
     }
@@ -25,24 +34,179 @@ pub async fn get_months(year: usize) -> Result<MonthsVideos, WebApiAppError> {
 
 /// get days that stored in the DB
 pub async fn get_days(year: usize, month: usize) -> Result<DaysVideos, WebApiAppError> {
-    match DynamoDbClient::new().await.get_days(year, month).await {
+    match db_client().await?.get_days(year, month).await {
         Ok(days) => Ok(DaysVideos { days }),
         Err(e) => Err(WebApiAppError::DBError(e)),
     }
 }
 
 /// get objects that stored in the DB
+/// The metadata of the objects is also returned.
+/// If the page is queried, a page of the objects and their metadata is returned.
+/// Only the items of the objects in the page are read, not the whole day.
 pub async fn get_objects(
     year: usize,
     month: usize,
     day: usize,
+    query: PageQuery,
 ) -> Result<VideoObjects, WebApiAppError> {
-    match DynamoDbClient::new()
+    let client = db_client().await?;
+
+    let page = match read_objects(&client, (year, month, day), query).await? {
+        Ok(page) => page,
+        Err(e) => return Err(WebApiAppError::DBError(e)),
+    };
+
+    let collections = match client.get_collection_items_of(&page.items).await {
+        Ok(collections) => collections,
+        Err(e) => return Err(WebApiAppError::DBError(e)),
+    };
+
+    Ok(VideoObjects {
+        metadata: Some(collections.into_iter().map(Into::into).collect()),
+        objects: page.items,
+        next_cursor: page.next_cursor,
+    })
+}
+
+/// get the upload sessions whose object was not uploaded before the pre-signed URL expired
+pub async fn get_stale_upload_sessions() -> Result<StaleUploadSessions, WebApiAppError> {
+    match db_client().await?.get_stale_upload_sessions(None).await {
+        Ok(sessions) => Ok(StaleUploadSessions {
+            sessions: sessions.into_iter().map(Into::into).collect(),
+        }),
+        Err(e) => Err(WebApiAppError::DBError(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aws_clients::dynamodb::entities::collection::CollectionItem;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use tokio::sync::OnceCell;
+
+    /// The table of the fake that the functions read from the environment
+    const TABLE_NAME: &str = "web-api-app-test";
+
+    /// The client of the table of the environment
+    /// The table is created once, since the tests share the fake.
+    async fn client() -> DynamoDbClient {
+        static TABLE: OnceCell<()> = OnceCell::const_new();
+
+        TABLE
+            .get_or_init(|| async {
+                // the variable is set before the configuration is loaded by any test
+                std::env::set_var("TABLE_NAME", TABLE_NAME);
+                let client = DynamoDbClient::from_env().await.unwrap();
+                client.ensure_table().await.unwrap();
+            })
+            .await;
+
+        DynamoDbClient::from_env().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_objects_page() {
+        // Arrange
+        let key_names = [
+            "1985/05/05/1985-05-05-12-34-50.MOV",
+            "1985/05/05/1985-05-05-12-34-51.MOV",
+            "1985/05/05/1985-05-05-12-34-52.MOV",
+        ];
+        let collections = key_names
+            .iter()
+            .map(|key_name| CollectionItem::new_object(key_name, "vault").unwrap())
+            .collect::<Vec<CollectionItem>>();
+        client()
+            .await
+            .put_collection_items(&collections)
+            .await
+            .unwrap();
+
+        // Act
+        let first = get_objects(
+            1985,
+            5,
+            5,
+            PageQuery {
+                limit: Some(2),
+                cursor: None,
+            },
+        )
         .await
-        .get_objects(year, month, day)
+        .unwrap();
+        let second = get_objects(
+            1985,
+            5,
+            5,
+            PageQuery {
+                limit: Some(2),
+                cursor: first.next_cursor.clone(),
+            },
+        )
         .await
-    {
-        Ok(objects) => Ok(VideoObjects { objects }),
-        Err(e) => Err(WebApiAppError::DBError(e)),
+        .unwrap();
+
+        // Assert
+        assert_eq!(first.objects, key_names[..2]);
+        assert!(first.next_cursor.is_some());
+        assert_eq!(second.objects, key_names[2..]);
+        assert_eq!(second.next_cursor, None);
+        let metadata_keys = |objects: &VideoObjects| {
+            objects
+                .metadata
+                .iter()
+                .flatten()
+                .map(|metadata| metadata.key_name.clone())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(metadata_keys(&first), first.objects);
+        assert_eq!(metadata_keys(&second), second.objects);
+    }
+
+    #[tokio::test]
+    async fn test_get_objects_with_invalid_query() {
+        // Arrange
+        client().await;
+        let invalid_limit = PageQuery {
+            limit: Some(0),
+            cursor: None,
+        };
+        let invalid_cursor = PageQuery {
+            limit: Some(1),
+            cursor: Some("cursor".to_string()),
+        };
+
+        // Act
+        let limit_error = get_objects(1985, 6, 6, invalid_limit).await.unwrap_err();
+        let cursor_error = get_objects(1985, 6, 6, invalid_cursor).await.unwrap_err();
+
+        // Assert
+        for error in [limit_error, cursor_error] {
+            let response = error.return_http_response().into_response();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_objects_without_page() {
+        // Arrange
+        let key_name = "1985/07/07/1985-07-07-12-34-50.MOV";
+        let collection = CollectionItem::new_object(key_name, "vault").unwrap();
+        client()
+            .await
+            .put_collection_items(&vec![collection])
+            .await
+            .unwrap();
+
+        // Act
+        let result = get_objects(1985, 7, 7, PageQuery::default()).await.unwrap();
+
+        // Assert
+        assert_eq!(result.objects, [key_name]);
+        assert_eq!(result.next_cursor, None);
+        assert_eq!(result.metadata.map(|metadata| metadata.len()), Some(1));
     }
 }
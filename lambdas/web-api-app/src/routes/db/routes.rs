@@ -1,5 +1,8 @@
-use crate::routes::db::db_function::{get_days, get_months, get_objects, get_years};
-use axum::extract::Path;
+use crate::routes::db::db_function::{
+    get_days, get_months, get_objects, get_stale_upload_sessions, get_years,
+};
+use crate::routes::page::PageQuery;
+use axum::extract::{Path, Query};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::routing::get;
@@ -18,7 +21,8 @@ pub fn db_routes() -> Router {
         .route(
             "/videos/years/:year/months/:month/days/:day/objects",
             get(get_objects_handler),
-        );
+        )
+        .route("/uploads/stale", get(stale_upload_sessions_handler));
 
     bucket_route
 }
@@ -28,11 +32,7 @@ pub fn db_routes() -> Router {
 async fn years_videos_handler() -> impl IntoResponse {
     match get_years().await {
         Ok(years) => (StatusCode::OK, Json(json!(years))).into_response(),
-        Err(e) => (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => e.return_http_response().into_response(),
     }
 }
 
@@ -40,11 +40,7 @@ async fn years_videos_handler() -> impl IntoResponse {
 async fn months_videos_handler(Path(year): Path<usize>) -> impl IntoResponse {
     match get_months(year).await {
         Ok(months) => (StatusCode::OK, Json(json!(months))).into_response(),
-        Err(e) => (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => e.return_http_response().into_response(),
     }
 }
 
@@ -52,24 +48,26 @@ async fn months_videos_handler(Path(year): Path<usize>) -> impl IntoResponse {
 async fn days_videos_handler(Path((year, month)): Path<(usize, usize)>) -> impl IntoResponse {
     match get_days(year, month).await {
         Ok(days) => (StatusCode::OK, Json(json!(days))).into_response(),
-        Err(e) => (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => e.return_http_response().into_response(),
     }
 }
 
 /// The wrapper of the get_objects
+/// `?limit=&cursor=` reads a page of the objects.
 async fn get_objects_handler(
     Path((year, month, day)): Path<(usize, usize, usize)>,
+    Query(query): Query<PageQuery>,
 ) -> impl IntoResponse {
-    match get_objects(year, month, day).await {
+    match get_objects(year, month, day, query).await {
         Ok(video_objects) => (StatusCode::OK, Json(json!(video_objects))).into_response(),
-        Err(e) => (
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": e.to_string()})),
-        )
-            .into_response(),
+        Err(e) => e.return_http_response().into_response(),
+    }
+}
+
+/// The wrapper of the get_stale_upload_sessions
+async fn stale_upload_sessions_handler() -> impl IntoResponse {
+    match get_stale_upload_sessions().await {
+        Ok(sessions) => (StatusCode::OK, Json(json!(sessions))).into_response(),
+        Err(e) => e.return_http_response().into_response(),
     }
 }
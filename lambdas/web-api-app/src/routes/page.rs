@@ -0,0 +1,60 @@
+//! The pagination of the object lists
+
+use crate::error::WebApiAppError;
+use serde::Deserialize;
+use shared::error::Error;
+use shared::traits::{GetFileListTrait, Page};
+
+/// The page size when only the cursor is given
+const DEFAULT_PAGE_SIZE: i32 = 100;
+
+/// The largest page size
+const MAX_PAGE_SIZE: i32 = 1000;
+
+/// The query of the paginated endpoints, `?limit=&cursor=`
+/// If neither is given, the whole list is returned.
+#[derive(Deserialize, Debug, Default)]
+pub struct PageQuery {
+    pub limit: Option<i32>,
+    /// The cursor that is returned by the previous page
+    pub cursor: Option<String>,
+}
+
+impl PageQuery {
+    /// The page size, or None if the whole list is requested
+    fn page_size(&self) -> Result<Option<i32>, WebApiAppError> {
+        match (self.limit, &self.cursor) {
+            (None, None) => Ok(None),
+            (None, Some(_)) => Ok(Some(DEFAULT_PAGE_SIZE)),
+            (Some(limit), _) if (1..=MAX_PAGE_SIZE).contains(&limit) => Ok(Some(limit)),
+            (Some(limit), _) => Err(WebApiAppError::ValidationError(format!(
+                "The limit must be between 1 and {MAX_PAGE_SIZE}: {limit}"
+            ))),
+        }
+    }
+}
+
+/// Read the objects of the day, the whole list or a page of it.
+/// The outer error is the invalid query, and the inner one is the error of the client.
+pub async fn read_objects(
+    client: &impl GetFileListTrait,
+    (year, month, day): (usize, usize, usize),
+    query: PageQuery,
+) -> Result<Result<Page, Error>, WebApiAppError> {
+    let page = match query.page_size()? {
+        Some(limit) => {
+            client
+                .get_objects_page(year, month, day, limit, query.cursor)
+                .await
+        }
+        None => client
+            .get_objects(year, month, day)
+            .await
+            .map(|items| Page {
+                items,
+                next_cursor: None,
+            }),
+    };
+
+    Ok(page)
+}
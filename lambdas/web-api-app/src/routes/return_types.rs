@@ -1,6 +1,8 @@
 //! This is the return data type that is defined in the API doc.
 
 pub mod return_data_types {
+    use aws_clients::dynamodb::entities::collection::CollectionItem;
+    use aws_clients::dynamodb::upload_session::UploadSession;
     use serde::Serialize;
 
     /// The years of the videos
@@ -22,8 +24,96 @@ pub mod return_data_types {
     }
 
     /// The video object's name
+    /// The metadata is only provided when it is read from the DB.
     #[derive(Serialize, Debug)]
+    #[serde(rename_all = "camelCase")]
     pub struct VideoObjects {
         pub objects: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub metadata: Option<Vec<VideoObjectMetadata>>,
+        /// The cursor of the next page. It is only provided when the page is requested.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub next_cursor: Option<String>,
+    }
+
+    /// The metadata of the video object
+    #[derive(Serialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct VideoObjectMetadata {
+        pub key_name: String,
+        pub unix_time: i64,
+        pub is_unzipped: bool,
+        pub size: Option<i64>,
+        pub duration: Option<i64>,
+        pub width: Option<u32>,
+        pub height: Option<u32>,
+        pub content_type: Option<String>,
+        pub checksum: Option<String>,
+        pub uploader: Option<String>,
+        pub device: Option<String>,
+        pub content_hash: Option<String>,
+        pub duplicate_of: Option<String>,
+        pub version: u64,
+        pub retain_until: Option<u64>,
+        pub legal_hold: bool,
+    }
+
+    impl From<CollectionItem> for VideoObjectMetadata {
+        fn from(collection: CollectionItem) -> Self {
+            Self {
+                key_name: collection.key_name,
+                unix_time: collection.unix_time,
+                is_unzipped: collection.is_unzipped,
+                size: collection.size,
+                duration: collection.duration,
+                width: collection.resolution.map(|resolution| resolution.width),
+                height: collection.resolution.map(|resolution| resolution.height),
+                content_type: collection.content_type,
+                checksum: collection.checksum,
+                uploader: collection.uploader,
+                device: collection.device,
+                content_hash: collection.content_hash,
+                duplicate_of: collection.duplicate_of,
+                version: collection.version,
+                retain_until: collection
+                    .protection
+                    .retain_until
+                    .map(|retain_until| retain_until as u64),
+                legal_hold: collection.protection.legal_hold,
+            }
+        }
+    }
+
+    /// The upload sessions whose object was not uploaded in time
+    #[derive(Serialize, Debug)]
+    pub struct StaleUploadSessions {
+        pub sessions: Vec<UploadSessionData>,
+    }
+
+    /// The upload session
+    #[derive(Serialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct UploadSessionData {
+        pub key_name: String,
+        pub uploader: Option<String>,
+        pub expected_size: Option<i64>,
+        pub expected_checksum: Option<String>,
+        pub created_at: u64,
+        pub expires_at: u64,
+        pub state: String,
+    }
+
+    impl From<UploadSession> for UploadSessionData {
+        fn from(session: UploadSession) -> Self {
+            Self {
+                key_name: session.key_name,
+                uploader: session.uploader,
+                expected_size: session.expected_size,
+                expected_checksum: session.expected_checksum,
+                created_at: session.created_at as u64,
+                expires_at: session.expires_at as u64,
+                state: session.state.as_str().to_string(),
+            }
+        }
     }
 }